@@ -0,0 +1,550 @@
+use std::cmp::Reverse;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap};
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::sync::{Arc, Mutex};
+
+use oxrdf::Quad;
+use oxrdfio::{RdfFormat, RdfParser};
+
+use crate::sink::{QuadSink, SinkError};
+
+/// Quads the first slice of a `SharedFilter` is sized for; each further
+/// slice doubles it
+const FILTER_CAPACITY: usize = 1 << 20;
+const FILTER_HASHES: u64 = 7;
+/// Possible duplicates buffered before they are checked against the spill
+const PENDING_LIMIT: usize = 100_000;
+/// Lines written since the last spilled run, kept in memory until they
+/// form a run
+const SPILL_RUN_LENGTH: usize = 100_000;
+/// Lines of a spilled run between two entries of its sparse index
+const BLOCK_LINES: usize = 256;
+/// Quads sorted in memory for each run of the external sort
+const RUN_LENGTH: usize = 500_000;
+
+/// The N-Quads line of a quad, which identifies it exactly
+fn nquads_line(quad: &Quad) -> String {
+    format!("{quad} .")
+}
+
+struct BloomFilter {
+    bits: Vec<u64>,
+    capacity: usize,
+    len: usize,
+}
+
+impl BloomFilter {
+    fn new(capacity: usize) -> Self {
+        // About ten bits per quad keeps false positives near 1% with seven
+        // hashes
+        Self {
+            bits: vec![0; (capacity * 10).div_ceil(64).max(1)],
+            capacity,
+            len: 0,
+        }
+    }
+
+    fn positions(&self, (h1, h2): (u64, u64)) -> impl Iterator<Item = (usize, u64)> {
+        let len = self.bits.len() as u64 * 64;
+        (0..FILTER_HASHES).map(move |i| {
+            let position = h1.wrapping_add(i.wrapping_mul(h2)) % len;
+            ((position / 64) as usize, 1 << (position % 64))
+        })
+    }
+
+    fn contains(&self, hashes: (u64, u64)) -> bool {
+        self.positions(hashes)
+            .all(|(word, bit)| self.bits[word] & bit != 0)
+    }
+
+    fn insert(&mut self, hashes: (u64, u64)) {
+        for (word, bit) in self.positions(hashes).collect::<Vec<_>>() {
+            self.bits[word] |= bit;
+        }
+        self.len += 1;
+    }
+
+    fn is_full(&self) -> bool {
+        self.len >= self.capacity
+    }
+}
+
+#[derive(Default)]
+struct Slices {
+    slices: Vec<BloomFilter>,
+    sinks: u64,
+}
+
+/// A Bloom filter shared by the `FilterDedupSink`s of every output, each
+/// hashing its quads with its own salt. It starts small and adds slices of
+/// doubling capacity as they fill up, so that memory follows the number of
+/// quads written rather than the number of outputs.
+#[derive(Clone, Default)]
+pub struct SharedFilter(Arc<Mutex<Slices>>);
+
+impl SharedFilter {
+    /// A salt for a new sink, so that the same quad written to two outputs
+    /// does not look like a duplicate
+    fn register(&self) -> u64 {
+        let mut slices = self.0.lock().unwrap();
+        slices.sinks += 1;
+        slices.sinks
+    }
+
+    /// Add `value`, returning whether it may have been added before
+    fn insert(&self, salt: u64, value: &str) -> bool {
+        let hashes = (
+            seeded_hash(value, 2 * salt),
+            seeded_hash(value, 2 * salt + 1),
+        );
+        let mut slices = self.0.lock().unwrap();
+        if slices.slices.iter().any(|slice| slice.contains(hashes)) {
+            return true;
+        }
+        if slices.slices.last().is_none_or(BloomFilter::is_full) {
+            let capacity = FILTER_CAPACITY << slices.slices.len();
+            slices.slices.push(BloomFilter::new(capacity));
+        }
+        slices.slices.last_mut().unwrap().insert(hashes);
+        false
+    }
+}
+
+fn seeded_hash(value: &str, seed: u64) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    seed.hash(&mut hasher);
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// A sorted file of unique N-Quads lines, with the first line and offset of
+/// every block of `BLOCK_LINES` lines
+struct Run {
+    file: File,
+    len: usize,
+    index: Vec<(String, u64)>,
+}
+
+impl Run {
+    /// Write `lines`, which must be sorted and unique
+    fn write(lines: impl IntoIterator<Item = io::Result<String>>) -> Result<Self, SinkError> {
+        let mut file = tempfile::tempfile()?;
+        let mut writer = BufWriter::new(&mut file);
+        let (mut len, mut offset, mut index) = (0, 0, Vec::new());
+        for line in lines {
+            let line = line?;
+            writeln!(writer, "{line}")?;
+            let written = line.len() as u64 + 1;
+            if len % BLOCK_LINES == 0 {
+                index.push((line, offset));
+            }
+            len += 1;
+            offset += written;
+        }
+        writer.flush()?;
+        drop(writer);
+        Ok(Self { file, len, index })
+    }
+
+    fn lines(&self) -> Result<io::Lines<BufReader<File>>, SinkError> {
+        let mut file = self.file.try_clone()?;
+        file.seek(SeekFrom::Start(0))?;
+        Ok(BufReader::new(file).lines())
+    }
+
+    /// Merge two runs holding different lines
+    fn merge(self, other: Self) -> Result<Self, SinkError> {
+        let (mut left, mut right) = (self.lines()?.peekable(), other.lines()?.peekable());
+        let merged = std::iter::from_fn(|| match (left.peek(), right.peek()) {
+            (Some(Ok(l)), Some(Ok(r))) if r < l => right.next(),
+            (Some(_), _) => left.next(),
+            (None, _) => right.next(),
+        });
+        Self::write(merged)
+    }
+
+    /// Remove the pending lines the run contains. Lines are looked up in
+    /// order, so only the blocks they fall in are read, each at most once.
+    fn remove_from(&self, pending: &mut BTreeMap<String, Quad>) -> Result<(), SinkError> {
+        let mut found = Vec::new();
+        let mut block: Option<(usize, io::Lines<BufReader<File>>, usize, String)> = None;
+        for line in pending.keys() {
+            let Some(number) = self
+                .index
+                .partition_point(|(first, _)| first <= line)
+                .checked_sub(1)
+            else {
+                continue;
+            };
+            if block
+                .as_ref()
+                .is_none_or(|(current, ..)| *current != number)
+            {
+                let mut file = self.file.try_clone()?;
+                file.seek(SeekFrom::Start(self.index[number].1))?;
+                block = Some((number, BufReader::new(file).lines(), 0, String::new()));
+            }
+            let (_, lines, read, last) = block.as_mut().unwrap();
+            while last.as_str() < line.as_str() && *read < BLOCK_LINES {
+                match lines.next() {
+                    Some(next) => *last = next?,
+                    None => break,
+                }
+                *read += 1;
+            }
+            if last == line {
+                found.push(line.clone());
+            }
+        }
+        for line in found {
+            pending.remove(&line);
+        }
+        Ok(())
+    }
+}
+
+/// Every line written by a `FilterDedupSink`: the latest ones in memory,
+/// older ones in sorted runs on disk. A run is merged into the previous one
+/// while it is at least half its size, so there are few runs to check.
+#[derive(Default)]
+struct Spill {
+    recent: BTreeSet<String>,
+    runs: Vec<Run>,
+    run_length: usize,
+}
+
+impl Spill {
+    fn insert(&mut self, line: String) -> Result<(), SinkError> {
+        self.recent.insert(line);
+        if self.recent.len() >= self.run_length {
+            let recent = std::mem::take(&mut self.recent);
+            self.runs.push(Run::write(recent.into_iter().map(Ok))?);
+            while let [.., previous, last] = &self.runs[..]
+                && 2 * last.len >= previous.len
+            {
+                let last = self.runs.pop().unwrap();
+                let previous = self.runs.pop().unwrap();
+                self.runs.push(previous.merge(last)?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the pending lines that were written already
+    fn remove_from(&self, pending: &mut BTreeMap<String, Quad>) -> Result<(), SinkError> {
+        pending.retain(|line, _| !self.recent.contains(line));
+        for run in &self.runs {
+            if pending.is_empty() {
+                break;
+            }
+            run.remove_from(pending)?;
+        }
+        Ok(())
+    }
+}
+
+/// Drops duplicate quads in bounded memory. A Bloom filter lets new quads
+/// through directly, while possible duplicates are checked in batches
+/// against a spill holding every quad written so far.
+pub struct FilterDedupSink {
+    inner: Box<dyn QuadSink>,
+    filter: SharedFilter,
+    salt: u64,
+    spill: Spill,
+    pending: BTreeMap<String, Quad>,
+    pending_limit: usize,
+}
+
+impl FilterDedupSink {
+    pub fn new(inner: Box<dyn QuadSink>, filter: SharedFilter) -> Self {
+        Self {
+            inner,
+            salt: filter.register(),
+            filter,
+            spill: Spill {
+                run_length: SPILL_RUN_LENGTH,
+                ..Spill::default()
+            },
+            pending: BTreeMap::new(),
+            pending_limit: PENDING_LIMIT,
+        }
+    }
+
+    fn emit(&mut self, line: String, quad: &Quad) -> Result<(), SinkError> {
+        self.spill.insert(line)?;
+        self.inner.insert(quad)
+    }
+
+    /// Write the pending quads the spill does not contain
+    fn resolve_pending(&mut self) -> Result<(), SinkError> {
+        self.spill.remove_from(&mut self.pending)?;
+        for (line, quad) in std::mem::take(&mut self.pending) {
+            self.emit(line, &quad)?;
+        }
+        Ok(())
+    }
+}
+
+impl QuadSink for FilterDedupSink {
+    fn insert(&mut self, quad: &Quad) -> Result<(), SinkError> {
+        let line = nquads_line(quad);
+        if !self.filter.insert(self.salt, &line) {
+            return self.emit(line, quad);
+        }
+
+        self.pending.entry(line).or_insert_with(|| quad.clone());
+        if self.pending.len() >= self.pending_limit {
+            self.resolve_pending()?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<(), SinkError> {
+        self.resolve_pending()?;
+        self.inner.finish()
+    }
+}
+
+/// Drops duplicate quads with an external sort: quads are written as sorted
+/// N-Quads runs, which are merged into unique quads once generation is done.
+/// Nothing reaches the inner sink before `finish`.
+pub struct SortDedupSink {
+    inner: Box<dyn QuadSink>,
+    run: Vec<String>,
+    runs: Vec<File>,
+    run_length: usize,
+}
+
+impl SortDedupSink {
+    pub fn new(inner: Box<dyn QuadSink>) -> Self {
+        Self {
+            inner,
+            run: Vec::new(),
+            runs: Vec::new(),
+            run_length: RUN_LENGTH,
+        }
+    }
+
+    fn write_run(&mut self) -> Result<(), SinkError> {
+        self.run.sort_unstable();
+        self.run.dedup();
+
+        let mut file = tempfile::tempfile()?;
+        let mut writer = BufWriter::new(&mut file);
+        for line in self.run.drain(..) {
+            writeln!(writer, "{line}")?;
+        }
+        writer.flush()?;
+        drop(writer);
+
+        file.seek(SeekFrom::Start(0))?;
+        self.runs.push(file);
+        Ok(())
+    }
+
+    /// Merge the sorted runs into a single file of unique N-Quads lines
+    fn merge_runs(&mut self) -> Result<File, SinkError> {
+        let mut runs: Vec<_> = self
+            .runs
+            .drain(..)
+            .map(|run| BufReader::new(run).lines())
+            .collect();
+        let mut heap = BinaryHeap::new();
+        for (index, run) in runs.iter_mut().enumerate() {
+            if let Some(line) = run.next() {
+                heap.push(Reverse((line?, index)));
+            }
+        }
+
+        let mut unique = tempfile::tempfile()?;
+        let mut writer = BufWriter::new(&mut unique);
+        let mut last: Option<String> = None;
+        while let Some(Reverse((line, index))) = heap.pop() {
+            if let Some(next) = runs[index].next() {
+                heap.push(Reverse((next?, index)));
+            }
+            if last.as_ref() != Some(&line) {
+                writeln!(writer, "{line}")?;
+                last = Some(line);
+            }
+        }
+        writer.flush()?;
+        drop(writer);
+
+        unique.seek(SeekFrom::Start(0))?;
+        Ok(unique)
+    }
+}
+
+impl QuadSink for SortDedupSink {
+    fn insert(&mut self, quad: &Quad) -> Result<(), SinkError> {
+        self.run.push(nquads_line(quad));
+        if self.run.len() >= self.run_length {
+            self.write_run()?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<(), SinkError> {
+        if !self.run.is_empty() {
+            self.write_run()?;
+        }
+        let unique = self.merge_runs()?;
+        for quad in RdfParser::from_format(RdfFormat::NQuads).for_reader(BufReader::new(unique)) {
+            self.inner.insert(&quad?)?;
+        }
+        self.inner.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use oxrdf::{Literal, NamedNode};
+
+    use super::*;
+    use crate::sink::tests::Collect;
+    use crate::sink::DedupSink;
+
+    fn quad(value: usize) -> Quad {
+        Quad::new(
+            NamedNode::new_unchecked("http://example.com/s"),
+            NamedNode::new_unchecked("http://example.com/p"),
+            Literal::new_simple_literal(value.to_string()),
+            oxrdf::GraphName::DefaultGraph,
+        )
+    }
+
+    /// 3000 distinct quads, each inserted up to three times in a scrambled
+    /// order
+    fn duplicated_quads() -> Vec<Quad> {
+        (0..9000).map(|i| quad((i * 7919) % 9000 % 3000)).collect()
+    }
+
+    /// Run `sink` over `quads`, checking it writes each distinct quad
+    /// exactly once and finishes the inner sink
+    fn check_unique(sink: impl FnOnce(Box<dyn QuadSink>) -> Box<dyn QuadSink>, quads: &[Quad]) {
+        let collect = Collect::default();
+        let mut sink = sink(Box::new(collect.clone()));
+        for quad in quads {
+            sink.insert(quad).unwrap();
+        }
+        sink.finish().unwrap();
+
+        let (written, finished) = &*collect.0.lock().unwrap();
+        assert!(finished);
+        let mut written: Vec<_> = written.iter().map(nquads_line).collect();
+        written.sort();
+        let mut expected: Vec<_> = quads.iter().map(nquads_line).collect();
+        expected.sort();
+        expected.dedup();
+        assert_eq!(written, expected);
+    }
+
+    #[test]
+    fn memory_strategy_writes_each_quad_once() {
+        check_unique(|inner| Box::new(DedupSink::new(inner)), &duplicated_quads());
+    }
+
+    #[test]
+    fn filter_strategy_writes_each_quad_once() {
+        check_unique(
+            |inner| Box::new(FilterDedupSink::new(inner, SharedFilter::default())),
+            &duplicated_quads(),
+        );
+    }
+
+    #[test]
+    fn filter_strategy_checks_pending_quads_against_spilled_runs() {
+        // Small limits check pending quads many times, against several
+        // merged runs spanning more than one block
+        check_unique(
+            |inner| {
+                let mut sink = FilterDedupSink::new(inner, SharedFilter::default());
+                sink.pending_limit = 5;
+                sink.spill.run_length = 50;
+                Box::new(sink)
+            },
+            &duplicated_quads(),
+        );
+    }
+
+    #[test]
+    fn filter_is_shared_without_mixing_outputs() {
+        let filter = SharedFilter::default();
+        let (first, second) = (Collect::default(), Collect::default());
+        let mut sinks = [
+            FilterDedupSink::new(Box::new(first.clone()), filter.clone()),
+            FilterDedupSink::new(Box::new(second.clone()), filter.clone()),
+        ];
+        for sink in &mut sinks {
+            for value in [1, 2, 1, 3, 2] {
+                sink.insert(&quad(value)).unwrap();
+            }
+        }
+        for sink in sinks {
+            Box::new(sink).finish().unwrap();
+        }
+        assert_eq!(first.0.lock().unwrap().0.len(), 3);
+        assert_eq!(second.0.lock().unwrap().0.len(), 3);
+        assert_eq!(filter.0.lock().unwrap().slices.len(), 1);
+    }
+
+    #[test]
+    fn filter_grows_in_slices() {
+        let filter = SharedFilter::default();
+        let salt = filter.register();
+        // False positives are not added, so filling the first slice takes
+        // slightly more values than its capacity
+        let mut value = 0;
+        while filter.0.lock().unwrap().slices.len() < 2 {
+            filter.insert(salt, &value.to_string());
+            value += 1;
+        }
+        assert!((FILTER_CAPACITY..FILTER_CAPACITY + FILTER_CAPACITY / 20).contains(&value));
+        let slices = &filter.0.lock().unwrap().slices;
+        assert_eq!(slices[0].len, FILTER_CAPACITY);
+        assert_eq!(slices[1].capacity, 2 * FILTER_CAPACITY);
+    }
+
+    #[test]
+    fn run_finds_lines_across_blocks() {
+        let lines: Vec<_> = (0..1000).map(|i| format!("line {i:04}")).collect();
+        let run = Run::write(lines.iter().cloned().map(Ok)).unwrap();
+        assert_eq!(run.len, 1000);
+        assert_eq!(run.index.len(), 1000usize.div_ceil(BLOCK_LINES));
+
+        let mut pending: BTreeMap<_, _> = ["line 0000", "line 0255", "line 0256", "line 0999"]
+            .into_iter()
+            .chain(["a", "line 0255.5", "line 1000", "z"])
+            .map(|line| (line.to_owned(), quad(0)))
+            .collect();
+        run.remove_from(&mut pending).unwrap();
+        assert_eq!(
+            pending.into_keys().collect::<Vec<_>>(),
+            ["a", "line 0255.5", "line 1000", "z"]
+        );
+    }
+
+    #[test]
+    fn sort_strategy_writes_each_quad_once() {
+        check_unique(
+            |inner| Box::new(SortDedupSink::new(inner)),
+            &duplicated_quads(),
+        );
+    }
+
+    #[test]
+    fn sort_strategy_merges_many_runs() {
+        check_unique(
+            |inner| {
+                let mut sink = SortDedupSink::new(inner);
+                sink.run_length = 7;
+                Box::new(sink)
+            },
+            &duplicated_quads(),
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::model::{FunctionError, Return};
+use crate::vocab::grel;
+
+/// Values bound to each parameter IRI of a function execution
+pub type FunctionInputs = HashMap<String, Vec<String>>;
+
+/// An executable function. Implementations may produce several named
+/// outputs, keyed by output IRI, alongside a default output.
+pub trait Function: Send + Sync {
+    fn execute(&self, inputs: &FunctionInputs) -> Result<Return, FunctionError>;
+}
+
+impl<F> Function for F
+where
+    F: Fn(&FunctionInputs) -> Result<Return, FunctionError> + Send + Sync,
+{
+    fn execute(&self, inputs: &FunctionInputs) -> Result<Return, FunctionError> {
+        self(inputs)
+    }
+}
+
+/// Function implementations available to the processor, keyed by function IRI
+#[derive(Clone, Default)]
+pub struct FunctionRegistry {
+    functions: HashMap<String, Arc<dyn Function>>,
+}
+
+impl FunctionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A registry holding the built-in GREL string functions
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::new();
+        registry.register(grel::Functions::TO_LOWER_CASE, |inputs: &FunctionInputs| {
+            string_function(inputs, |value| value.to_lowercase())
+        });
+        registry.register(grel::Functions::TO_UPPER_CASE, |inputs: &FunctionInputs| {
+            string_function(inputs, |value| value.to_uppercase())
+        });
+        registry.register(grel::Functions::STRING_TRIM, |inputs: &FunctionInputs| {
+            string_function(inputs, |value| value.trim().to_string())
+        });
+        registry
+    }
+
+    pub fn register(&mut self, iri: impl Into<String>, function: impl Function + 'static) {
+        self.functions.insert(iri.into(), Arc::new(function));
+    }
+
+    pub fn execute(&self, iri: &str, inputs: &FunctionInputs) -> Result<Return, FunctionError> {
+        self.functions
+            .get(iri)
+            .ok_or_else(|| FunctionError::UnknownFunction(iri.to_string()))?
+            .execute(inputs)
+    }
+}
+
+fn string_function(
+    inputs: &FunctionInputs,
+    op: impl Fn(&str) -> String,
+) -> Result<Return, FunctionError> {
+    let values = inputs
+        .get(grel::Parameters::VALUE)
+        .ok_or_else(|| FunctionError::MissingParameter(grel::Parameters::VALUE.to_string()))?;
+    // An input whose values were all null produces no output
+    let Some(value) = values.first() else {
+        return Ok(Return::new(None));
+    };
+    let result = op(value);
+    let mut output = Return::new(Some(result.clone()));
+    output.put(grel::Outputs::STRING.to_string(), result);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(values: &[&str]) -> FunctionInputs {
+        let values = values.iter().map(|value| value.to_string()).collect();
+        FunctionInputs::from([(grel::Parameters::VALUE.to_string(), values)])
+    }
+
+    #[test]
+    fn runs_builtin_string_functions() {
+        let registry = FunctionRegistry::with_builtins();
+        let cases = [
+            (grel::Functions::TO_UPPER_CASE, " Ann ", " ANN "),
+            (grel::Functions::TO_LOWER_CASE, " Ann ", " ann "),
+            (grel::Functions::STRING_TRIM, " Ann ", "Ann"),
+        ];
+        for (function, input, output) in cases {
+            let result = registry.execute(function, &value(&[input])).unwrap();
+            assert_eq!(result.select(None).unwrap(), output);
+            assert_eq!(result.get(grel::Outputs::STRING).unwrap(), output);
+        }
+    }
+
+    #[test]
+    fn produces_nothing_for_null_inputs() {
+        let registry = FunctionRegistry::with_builtins();
+        let result = registry
+            .execute(grel::Functions::TO_UPPER_CASE, &value(&[]))
+            .unwrap();
+        assert!(result.is_empty());
+    }
+
+    #[test]
+    fn rejects_missing_parameters_and_functions() {
+        let registry = FunctionRegistry::with_builtins();
+        assert_eq!(
+            registry.execute(grel::Functions::TO_UPPER_CASE, &FunctionInputs::new()),
+            Err(FunctionError::MissingParameter(
+                grel::Parameters::VALUE.to_string()
+            ))
+        );
+        assert_eq!(
+            registry.execute("http://example.com/unknown", &value(&["a"])),
+            Err(FunctionError::UnknownFunction(
+                "http://example.com/unknown".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn runs_registered_functions() {
+        let mut registry = FunctionRegistry::new();
+        registry.register("http://example.com/count", |inputs: &FunctionInputs| {
+            let count = inputs.values().map(Vec::len).sum::<usize>();
+            Ok(Return::new(Some(count.to_string())))
+        });
+        let result = registry
+            .execute("http://example.com/count", &value(&["a", "b"]))
+            .unwrap();
+        assert_eq!(result.get_default_value().unwrap(), "2");
+    }
+}
@@ -1,23 +1,32 @@
 use clap::{Parser, ValueEnum};
+use dedup::{FilterDedupSink, SharedFilter, SortDedupSink};
 use env_logger::{Builder, Env};
-use log;
-use oxiri::IriParseError;
+use model::{Compression, LogicalTarget, TriplesMap};
+use oxiri::{Iri, IriParseError};
 use oxrdf::Dataset;
-use oxrdfio::{JsonLdProfileSet, RdfFormat, RdfParseError, RdfParser, RdfSerializer};
-use parse::RmlMappingParser;
+use oxrdfio::{JsonLdProfileSet, RdfFormat, RdfParseError, RdfParser};
+use parse::{ParseError, RmlMappingParser};
+use process::{ProcessError, RmlProcessor};
+use sink::{DedupSink, OutputSinks, QuadSink, SerializerSink, SinkError};
 use std::{
     fs::File,
-    io::{self, Write},
-    path::PathBuf,
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
 };
 use thiserror::Error;
+use vocab::formats::Formats;
 
+pub mod dedup;
+pub mod functions;
 pub mod model;
 pub mod parse;
 pub mod process;
+pub mod sink;
+pub mod source;
+pub mod target;
 pub mod vocab;
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 pub enum OutputFormat {
     /// Turtle format
     #[clap(alias = "ttl")]
@@ -25,17 +34,10 @@ pub enum OutputFormat {
     /// N-Triples format
     Nt,
     /// N-Quads format  
+    #[default]
     Nq,
     /// JSON-LD format
     Jsonld,
-    /// Jelly format
-    Jelly,
-}
-
-impl Default for OutputFormat {
-    fn default() -> Self {
-        OutputFormat::Nq
-    }
 }
 
 impl From<OutputFormat> for RdfFormat {
@@ -47,7 +49,6 @@ impl From<OutputFormat> for RdfFormat {
             OutputFormat::Jsonld => RdfFormat::JsonLd {
                 profile: JsonLdProfileSet::empty(),
             },
-            OutputFormat::Jelly => todo!("Jelly-RS not yet available"),
         }
     }
 }
@@ -59,11 +60,49 @@ impl std::fmt::Display for OutputFormat {
             OutputFormat::Nt => write!(f, "nt"),
             OutputFormat::Nq => write!(f, "nq"),
             OutputFormat::Jsonld => write!(f, "jsonld"),
-            OutputFormat::Jelly => write!(f, "jelly"),
         }
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum OutputCompression {
+    /// No compression
+    None,
+    /// gzip compression
+    #[clap(alias = "gz")]
+    Gzip,
+    /// Zstandard compression
+    #[clap(alias = "zst")]
+    Zstd,
+    /// bzip2 compression
+    #[clap(alias = "bz2")]
+    Bzip2,
+    /// xz compression
+    Xz,
+}
+
+impl From<OutputCompression> for Compression {
+    fn from(value: OutputCompression) -> Self {
+        match value {
+            OutputCompression::None => Compression::None,
+            OutputCompression::Gzip => Compression::Gzip,
+            OutputCompression::Zstd => Compression::Zstd,
+            OutputCompression::Bzip2 => Compression::Bzip2,
+            OutputCompression::Xz => Compression::Xz,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum DedupStrategy {
+    /// Exact, keeping every distinct quad in memory
+    Memory,
+    /// Exact in bounded memory, with a Bloom filter and a spill file
+    Filter,
+    /// Exact, with an external sort of all quads once generation is done
+    Sort,
+}
+
 #[derive(Parser)]
 #[command(name = "rml", about = "Process RML mappings to generate RDF data")]
 pub struct RmlCommand {
@@ -79,9 +118,32 @@ pub struct RmlCommand {
     #[arg(short = 'f', long = "format", default_value_t = OutputFormat::default())]
     pub format: OutputFormat,
 
-    /// Used in resolving relative IRIs produced by the RML mapping
+    /// Used in resolving relative IRIs produced by the RML mapping, over the
+    /// `@base` of the mapping file and the `rml:baseIRI` of triples maps
     #[arg(short = 'b', long = "baseIRI")]
     pub base_iri: Option<String>,
+
+    /// The output compression, guessed from the output file extension when
+    /// not given
+    #[arg(short = 'c', long = "compression")]
+    pub compression: Option<OutputCompression>,
+
+    /// Drop duplicate quads, in memory unless another strategy is given
+    #[arg(long = "dedup", num_args = 0..=1, default_missing_value = "memory")]
+    pub dedup: Option<DedupStrategy>,
+
+    /// Worker threads processing triples maps, one per core when 0
+    #[arg(short = 't', long = "threads", default_value_t = 0)]
+    pub threads: usize,
+
+    /// Keep the quads of large sources split between threads in source order
+    #[arg(long = "ordered")]
+    pub ordered: bool,
+
+    /// Replace blank nodes with IRIs under `/.well-known/genid/` of the base
+    /// IRI
+    #[arg(long = "skolemize")]
+    pub skolemize: bool,
 }
 
 #[derive(Error, Debug)]
@@ -94,11 +156,21 @@ pub enum RmlError {
     OutputUnavailable(#[from] io::Error),
     #[error("Invalid mapping file")]
     InvalidMapping(#[from] RdfParseError),
-    #[error("Invalid base IRI")]
+    #[error("Invalid RML mapping: {0}")]
+    InvalidMappingDefinition(#[from] ParseError),
+    #[error("Invalid base IRI: {0}")]
     InvalidBaseIri(#[from] IriParseError),
+    #[error("RML processing failed: {0}")]
+    Processing(#[from] ProcessError),
+    #[error("Could not write output: {0}")]
+    Output(#[from] SinkError),
+    #[error("Triples maps `{0}` and `{1}` read different logical sources from standard input")]
+    AmbiguousStdIn(String, String),
+    #[error("Skolemizing blank nodes requires a base IRI")]
+    SkolemizeWithoutBaseIri(),
 }
 
-fn mapping_format_from_ext(path: &PathBuf) -> Result<RdfFormat, RmlError> {
+fn mapping_format_from_ext(path: &Path) -> Result<RdfFormat, RmlError> {
     if let Some(path_str) = path.to_str() {
         match path.extension().and_then(|ext| ext.to_str()) {
             Some("ttl") => Ok(RdfFormat::Turtle),
@@ -114,35 +186,174 @@ fn mapping_format_from_ext(path: &PathBuf) -> Result<RdfFormat, RmlError> {
     }
 }
 
-fn write<W: Write>(writer: W, format: RdfFormat, dataset: Dataset) -> Result<i32, RmlError> {
-    Ok(0)
+/// The format of a logical target: its `rml:serialization`, otherwise guessed
+/// from the file extension, otherwise N-Quads
+fn target_format(target: &LogicalTarget) -> Result<RdfFormat, SinkError> {
+    if let Some(serialization) = &target.serialization {
+        return match serialization.as_str() {
+            Formats::JSON_LD => Ok(RdfFormat::JsonLd {
+                profile: JsonLdProfileSet::empty(),
+            }),
+            Formats::N3 => Ok(RdfFormat::N3),
+            Formats::N_QUADS => Ok(RdfFormat::NQuads),
+            Formats::N_TRIPLES => Ok(RdfFormat::NTriples),
+            Formats::RDF_XML => Ok(RdfFormat::RdfXml),
+            Formats::TRIG => Ok(RdfFormat::TriG),
+            Formats::TURTLE => Ok(RdfFormat::Turtle),
+            _ => Err(SinkError::UnsupportedSerialization(
+                target.uri.clone(),
+                serialization.clone(),
+            )),
+        };
+    }
+
+    // Look past the compression extension, eg `out.nt.gz`
+    let path = &target.target.path;
+    let path = match target.target.compression {
+        Compression::None => path.to_path_buf(),
+        _ => PathBuf::from(path.file_stem().unwrap_or_default()),
+    };
+    Ok(path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(RdfFormat::from_extension)
+        .unwrap_or(RdfFormat::NQuads))
+}
+
+/// Open the sink of a logical target, created when its first quad is
+/// generated
+fn open_target(
+    target: &LogicalTarget,
+    dedup: Option<DedupStrategy>,
+    filter: &SharedFilter,
+) -> Result<Box<dyn QuadSink>, SinkError> {
+    log::info!(
+        "Writing logical target {} to {}",
+        target.uri,
+        target.target.path.display()
+    );
+    let sink = SerializerSink::new(
+        &target.uri,
+        BufWriter::new(File::create(&target.target.path)?),
+        target_format(target)?,
+        target.target.compression,
+    )?;
+    Ok(deduplicated(Box::new(sink), dedup, filter))
+}
+
+fn deduplicated(
+    sink: Box<dyn QuadSink>,
+    dedup: Option<DedupStrategy>,
+    filter: &SharedFilter,
+) -> Box<dyn QuadSink> {
+    match dedup {
+        None => sink,
+        Some(DedupStrategy::Memory) => Box::new(DedupSink::new(sink)),
+        Some(DedupStrategy::Filter) => Box::new(FilterDedupSink::new(sink, filter.clone())),
+        Some(DedupStrategy::Sort) => Box::new(SortDedupSink::new(sink)),
+    }
+}
+
+/// Standard input can only be read once, so it can feed a single logical
+/// source, shared by any number of triples maps
+fn check_stdin(triples_maps: &[TriplesMap]) -> Result<(), RmlError> {
+    let mut readers = triples_maps
+        .iter()
+        .filter(|triples_map| triples_map.logical_source.reads_stdin());
+    if let Some(first) = readers.next()
+        && let Some(other) =
+            readers.find(|triples_map| triples_map.logical_source != first.logical_source)
+    {
+        return Err(RmlError::AmbiguousStdIn(
+            first.uri.clone(),
+            other.uri.clone(),
+        ));
+    }
+    Ok(())
 }
 
 impl RmlCommand {
     pub fn run(&self) -> Result<i32, RmlError> {
-        // TODO: Implement RML processing logic here
-        // This would include:
-        // 1. Parse the mapping file
-        // 2. Process the RML mappings
-        // 3. Generate RDF data
-        // 4. Output to file or stdout based on format
-
         let mapping_format = mapping_format_from_ext(&self.mapping_file)?;
         log::info!("Reading mapping file with format: {}", mapping_format);
-        let mapping_parser =
+        let mut mapping_parser =
             RdfParser::from_format(mapping_format).for_reader(File::open(&self.mapping_file)?);
 
-        let quads = mapping_parser.collect::<Result<Vec<_>, _>>()?;
+        // The first `@base` of the mapping file applies to the whole mapping
         let mut mapping_dataset = Dataset::new();
-        mapping_dataset.extend(quads);
+        let mut document_base_iri = None;
+        while let Some(quad) = mapping_parser.next() {
+            mapping_dataset.insert(&quad?);
+            if document_base_iri.is_none() {
+                document_base_iri = mapping_parser.base_iri().map(str::to_string);
+            }
+        }
 
         log::info!("Processing RML mapping: {:?}", self.mapping_file);
         log::info!("Output format: {:?}", self.format);
         let rml_parser = RmlMappingParser::new(mapping_dataset, self.mapping_file.to_owned());
-        let output_dataset = Dataset::new();
-        match &self.output_file {
-            Some(path) => write(File::create(path)?, self.format.into(), output_dataset),
-            None => write(io::stdout(), self.format.into(), output_dataset),
+        let triples_maps = rml_parser.parse()?;
+        for triples_map in &triples_maps {
+            log::debug!(
+                "Triples map {} with {:?}",
+                triples_map.uri,
+                triples_map.logical_source
+            );
+        }
+        check_stdin(&triples_maps)?;
+
+        let base_iri = match self.base_iri.clone().or(document_base_iri) {
+            Some(base_iri) => Some(Iri::parse(base_iri)?.into_inner()),
+            None => None,
+        };
+        if self.skolemize
+            && base_iri.is_none()
+            && triples_maps.iter().any(|triples_map| {
+                triples_map.base_iri.is_none() && triples_map.generates_blank_nodes()
+            })
+        {
+            return Err(RmlError::SkolemizeWithoutBaseIri());
+        }
+
+        // Triples without a logical target go to the default output
+        let default: Box<dyn QuadSink> = match &self.output_file {
+            Some(path) => Box::new(SerializerSink::new(
+                &path.display().to_string(),
+                BufWriter::new(File::create(path)?),
+                self.format.into(),
+                self.output_compression(),
+            )?),
+            None => Box::new(SerializerSink::new(
+                "stdout",
+                BufWriter::new(io::stdout()),
+                self.format.into(),
+                self.output_compression(),
+            )?),
+        };
+        let dedup = self.dedup;
+        // One filter serves every output, so memory does not grow with the
+        // number of logical targets
+        let filter = SharedFilter::default();
+        let mut output = OutputSinks::new(deduplicated(default, dedup, &filter), move |target| {
+            open_target(target, dedup, &filter)
+        });
+
+        let processor = RmlProcessor::new(base_iri)
+            .with_threads(self.threads)
+            .with_ordered(self.ordered)
+            .with_skolemize(self.skolemize);
+        processor.process(&triples_maps, &mut output)?;
+        output.finish()?;
+        Ok(0)
+    }
+
+    /// The `--compression` flag, otherwise the compression implied by the
+    /// output file extension
+    fn output_compression(&self) -> Compression {
+        match (self.compression, &self.output_file) {
+            (Some(compression), _) => compression.into(),
+            (None, Some(path)) => Compression::from_path(path),
+            (None, None) => Compression::None,
         }
     }
 }
@@ -161,3 +372,191 @@ fn main() {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PREFIXES: &str = "
+        @prefix rml: <http://w3id.org/rml/> .
+        @prefix ex: <http://example.com/> .
+    ";
+
+    fn triples_maps(mapping: &str) -> Vec<TriplesMap> {
+        let mut dataset = Dataset::new();
+        let document = format!("{PREFIXES}{mapping}");
+        for quad in RdfParser::from_format(RdfFormat::Turtle).for_reader(document.as_bytes()) {
+            dataset.insert(&quad.unwrap());
+        }
+        RmlMappingParser::new(dataset, PathBuf::from("/data/mapping.ttl"))
+            .parse()
+            .unwrap()
+    }
+
+    /// A triples map over the logical source `source`
+    fn triples_map(name: &str, source: &str) -> String {
+        format!(
+            r#"
+            ex:{name} a rml:TriplesMap;
+              rml:logicalSource {source};
+              rml:subjectMap [ rml:template "http://example.com/{name}/{{id}}" ] .
+            "#
+        )
+    }
+
+    #[test]
+    fn triples_maps_may_share_the_logical_source_read_from_stdin() {
+        let mapping = [
+            triples_map("A", "ex:Input"),
+            triples_map("B", "ex:Input"),
+            triples_map(
+                "C",
+                r#"[ rml:referenceFormulation rml:CSV; rml:source "people.csv" ]"#,
+            ),
+            "ex:Input rml:referenceFormulation rml:CSV; rml:source rml:StdIn .".to_string(),
+        ]
+        .concat();
+        assert!(check_stdin(&triples_maps(&mapping)).is_ok());
+    }
+
+    #[test]
+    fn rejects_different_logical_sources_read_from_stdin() {
+        let mapping = [
+            triples_map(
+                "A",
+                r#"[ rml:referenceFormulation rml:CSV; rml:source "-" ]"#,
+            ),
+            triples_map(
+                "B",
+                r#"[ rml:referenceFormulation rml:JSONPath; rml:iterator "$[*]";
+                     rml:source rml:StdIn ]"#,
+            ),
+        ]
+        .concat();
+        assert!(matches!(
+            check_stdin(&triples_maps(&mapping)),
+            Err(RmlError::AmbiguousStdIn(..))
+        ));
+    }
+
+    /// Run `mapping` over a `people.csv` holding a single row, and read the
+    /// N-Quads written
+    fn run(mapping: &str, args: &[&str]) -> Result<String, RmlError> {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("people.csv"), "id\n1\n").unwrap();
+        let mapping_file = dir.path().join("mapping.ttl");
+        std::fs::write(&mapping_file, format!("{PREFIXES}{mapping}")).unwrap();
+        let output_file = dir.path().join("output.nq");
+
+        let mut command = vec![
+            "drml",
+            "-m",
+            mapping_file.to_str().unwrap(),
+            "-o",
+            output_file.to_str().unwrap(),
+        ];
+        command.extend(args);
+        RmlCommand::try_parse_from(command).unwrap().run()?;
+        Ok(std::fs::read_to_string(output_file).unwrap())
+    }
+
+    /// A triples map over `people.csv` with `subject_map`, giving each
+    /// person an `ex:id`
+    fn people(subject_map: &str) -> String {
+        format!(
+            r#"
+            ex:People a rml:TriplesMap;
+              rml:logicalSource [ rml:referenceFormulation rml:CSV;
+                rml:source [ a rml:RelativePathSource; rml:root rml:MappingDirectory;
+                  rml:path "people.csv" ] ];
+              rml:subjectMap {subject_map};
+              rml:predicateObjectMap [ rml:predicate ex:id; rml:objectMap [ rml:reference "id" ] ] .
+            "#
+        )
+    }
+
+    #[test]
+    fn skolemizes_without_a_base_iri_when_there_are_no_blank_nodes() {
+        let output = run(
+            &people(r#"[ rml:template "http://example.com/person/{id}" ]"#),
+            &["--skolemize"],
+        )
+        .unwrap();
+        assert_eq!(
+            output.trim(),
+            r#"<http://example.com/person/1> <http://example.com/id> "1" ."#
+        );
+    }
+
+    #[test]
+    fn skolemizes_blank_nodes_under_the_base_iri() {
+        let mapping = people(r#"[ rml:template "{id}"; rml:termType rml:BlankNode ]"#);
+        assert!(matches!(
+            run(&mapping, &["--skolemize"]),
+            Err(RmlError::SkolemizeWithoutBaseIri())
+        ));
+
+        let output = run(
+            &mapping,
+            &["--skolemize", "--baseIRI", "http://example.com/"],
+        )
+        .unwrap();
+        assert!(
+            output.starts_with("<http://example.com/.well-known/genid/"),
+            "{output}"
+        );
+    }
+
+    /// The subject written for a person identified by a relative IRI, given
+    /// an `@base`, an `rml:baseIRI` and a `--baseIRI`
+    fn person(document: Option<&str>, triples_map: Option<&str>, command: Option<&str>) -> String {
+        let mut mapping = String::new();
+        if let Some(base_iri) = document {
+            mapping.push_str(&format!("@base <{base_iri}> .\n"));
+        }
+        mapping.push_str(&people(r#"[ rml:template "person/{id}" ]"#));
+        if let Some(base_iri) = triples_map {
+            mapping.push_str(&format!("ex:People rml:baseIRI <{base_iri}> .\n"));
+        }
+        let args: Vec<_> = command
+            .into_iter()
+            .flat_map(|iri| ["--baseIRI", iri])
+            .collect();
+        let output = run(&mapping, &args).unwrap();
+        output.split(' ').next().unwrap().to_string()
+    }
+
+    #[test]
+    fn prefers_the_command_line_base_iri_then_base_then_triples_map_base_iri() {
+        let (document, triples_map, command) = (
+            Some("http://document.example/"),
+            Some("http://triples-map.example/"),
+            Some("http://command.example/"),
+        );
+        assert_eq!(
+            person(None, triples_map, None),
+            "<http://triples-map.example/person/1>"
+        );
+        assert_eq!(
+            person(document, triples_map, None),
+            "<http://document.example/person/1>"
+        );
+        assert_eq!(
+            person(document, triples_map, command),
+            "<http://command.example/person/1>"
+        );
+        assert_eq!(
+            person(None, None, command),
+            "<http://command.example/person/1>"
+        );
+    }
+
+    #[test]
+    fn rejects_an_invalid_base_iri() {
+        let mapping = people(r#"[ rml:template "person/{id}" ]"#);
+        assert!(matches!(
+            run(&mapping, &["--baseIRI", "not an iri"]),
+            Err(RmlError::InvalidBaseIri(_))
+        ));
+    }
+}
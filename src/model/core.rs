@@ -1,6 +1,10 @@
 use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 
-use super::maps::{PredicateObjectMap, SubjectMap};
+use super::expression::Expression;
+use super::maps::{JoinCondition, PredicateObjectMap, SubjectMap};
+use crate::source::jsonpath::JsonPath;
+use crate::vocab::rml;
 
 pub trait LogicalSource {
     fn get_nulls(&self) -> HashSet<String>;
@@ -12,12 +16,31 @@ pub struct TriplesMap {
     pub logical_source: LogicalSourceType,
     pub subject_map: SubjectMap,
     pub predicate_object_maps: Vec<PredicateObjectMap>,
+    /// The `rml:baseIRI` of the triples map, used when no other base IRI is
+    /// given
+    pub base_iri: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum LogicalSourceType {
     File(FileLogicalSource),
     Database(DatabaseLogicalSource),
+    Web(WebLogicalSource),
+    View(LogicalView),
+}
+
+impl LogicalSourceType {
+    /// Whether the iterations are read from standard input
+    pub fn reads_stdin(&self) -> bool {
+        match self {
+            LogicalSourceType::File(file_source) => file_source.source.is_stdin(),
+            LogicalSourceType::Database(_) | LogicalSourceType::Web(_) => false,
+            LogicalSourceType::View(view) => {
+                view.view_on.reads_stdin()
+                    || view.joins.iter().any(|join| join.parent_view.reads_stdin())
+            }
+        }
+    }
 }
 
 impl LogicalSource for LogicalSourceType {
@@ -25,6 +48,8 @@ impl LogicalSource for LogicalSourceType {
         match self {
             LogicalSourceType::File(file_source) => file_source.get_nulls(),
             LogicalSourceType::Database(db_source) => db_source.get_nulls(),
+            LogicalSourceType::Web(web_source) => web_source.get_nulls(),
+            LogicalSourceType::View(view) => view.get_nulls(),
         }
     }
 }
@@ -41,6 +66,7 @@ impl TriplesMap {
             logical_source,
             subject_map,
             predicate_object_maps,
+            base_iri: None,
         }
     }
 
@@ -51,11 +77,31 @@ impl TriplesMap {
             .collect()
     }
 
+    /// Whether any subject, object or graph map generates blank nodes
+    pub fn generates_blank_nodes(&self) -> bool {
+        let subject_map = &self.subject_map;
+        let mut term_types = [&subject_map.term_type]
+            .into_iter()
+            .chain(subject_map.graph_maps.iter().map(|gm| &gm.term_type))
+            .chain(self.predicate_object_maps.iter().flat_map(|pom| {
+                pom.object_maps
+                    .iter()
+                    .map(|om| &om.term_type)
+                    .chain(pom.graph_maps.iter().map(|gm| &gm.term_type))
+            }));
+        term_types.any(|term_type| term_type.as_str() == rml::TermType::BLANK_NODE)
+    }
+
+    /// The fields of the logical source that the term maps of this triples
+    /// map refer to
     pub fn get_all_reference_fields(&self) -> HashSet<String> {
         let mut references = HashSet::new();
 
         // Subject map references
         references.extend(self.subject_map.expression.references());
+        for gm in &self.subject_map.graph_maps {
+            references.extend(gm.expression.references());
+        }
 
         // Predicate and object map references
         for pom in &self.predicate_object_maps {
@@ -67,25 +113,150 @@ impl TriplesMap {
             // Object map references
             for om in &pom.object_maps {
                 references.extend(om.expression.references());
+                if let Some(dm) = &om.datatype_map {
+                    references.extend(dm.expression.references());
+                }
+                if let Some(lm) = &om.language_map {
+                    references.extend(lm.expression.references());
+                }
             }
 
             // Predicate map references
             for pm in &pom.predicate_maps {
                 references.extend(pm.expression.references());
             }
+
+            // Graph map references
+            for gm in &pom.graph_maps {
+                references.extend(gm.expression.references());
+            }
         }
 
         references
-            .into_iter()
-            .map(|s| s.trim_start_matches(&['$', '.'][..]).to_string())
+            .iter()
+            .map(|reference| reference_field(reference).to_string())
             .collect()
     }
 }
 
+/// The field a reference names, without a leading `$` or `.`. References
+/// with wildcard or descendant steps do not name a single field and are
+/// kept whole, so that `$..name` does not become the top-level `name`.
+pub fn reference_field(reference: &str) -> &str {
+    if JsonPath::parse(reference).is_ok_and(|path| path.is_branching()) {
+        return reference;
+    }
+    reference.trim_start_matches(&['$', '.'][..])
+}
+
+/// Compression applied to a source or target file, see
+/// <https://kg-construct.github.io/rml-io/spec/docs/#compression>
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zip,
+    TarXz,
+    TarGz,
+    Zstd,
+    Bzip2,
+    Xz,
+}
+
+impl Compression {
+    /// The compression implied by a file extension, eg `data.csv.gz`
+    pub fn from_path(path: &Path) -> Self {
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        if name.ends_with(".tar.xz") || name.ends_with(".txz") {
+            Compression::TarXz
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Compression::TarGz
+        } else if name.ends_with(".gz") || name.ends_with(".gzip") {
+            Compression::Gzip
+        } else if name.ends_with(".zip") {
+            Compression::Zip
+        } else if name.ends_with(".zst") || name.ends_with(".zstd") {
+            Compression::Zstd
+        } else if name.ends_with(".bz2") {
+            Compression::Bzip2
+        } else if name.ends_with(".xz") {
+            Compression::Xz
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Archives hold several files and can only be read, not written as a
+    /// single stream
+    pub fn is_archive(&self) -> bool {
+        matches!(
+            self,
+            Compression::Zip | Compression::TarXz | Compression::TarGz
+        )
+    }
+}
+
+/// Character encoding of a source file, see
+/// <https://kg-construct.github.io/rml-io/spec/docs/#encoding>
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf16,
+}
+
+/// A local file source, with its path already resolved against the root
+/// declared in the mapping
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SourceDescription {
+    pub path: PathBuf,
+    pub compression: Compression,
+    pub encoding: Encoding,
+}
+
+/// The path standing for standard input
+const STDIN_PATH: &str = "-";
+
+impl SourceDescription {
+    /// A UTF-8 file, compressed as implied by its extension
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            compression: Compression::from_path(&path),
+            path,
+            encoding: Encoding::default(),
+        }
+    }
+
+    /// Uncompressed UTF-8 standard input
+    pub fn stdin() -> Self {
+        Self::new(PathBuf::from(STDIN_PATH))
+    }
+
+    pub fn is_stdin(&self) -> bool {
+        self.path.as_os_str() == STDIN_PATH
+    }
+}
+
+/// An RML-IO logical target: a local file that receives the triples of the
+/// term maps referring to it
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LogicalTarget {
+    pub uri: String,
+    pub target: SourceDescription,
+    /// The `rml:serialization` format IRI, eg `http://www.w3.org/ns/formats/N-Quads`
+    pub serialization: Option<String>,
+}
+
 // Basic logical source implementations
 #[derive(Debug, Clone, PartialEq)]
 pub struct FileLogicalSource {
-    pub source: String,
+    pub source: SourceDescription,
     pub reference_formulation: Option<String>,
     pub iterator: Option<String>,
     pub nulls: HashSet<String>,
@@ -97,9 +268,19 @@ impl LogicalSource for FileLogicalSource {
     }
 }
 
+/// A D2RQ database description. Credentials of the form `${NAME}` are read
+/// from the environment when connecting.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DatabaseDescription {
+    /// A JDBC-style connection URL, eg `jdbc:postgresql://localhost:5432/shop`
+    pub dsn: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct DatabaseLogicalSource {
-    pub source: String,
+    pub source: DatabaseDescription,
     pub query: Option<String>,
     pub nulls: HashSet<String>,
 }
@@ -109,3 +290,86 @@ impl LogicalSource for DatabaseLogicalSource {
         self.nulls.clone()
     }
 }
+
+/// How the pages of a web source are requested after the first one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pagination {
+    /// Follow the `rel="next"` links of `Link` response headers
+    NextLink,
+    /// Request pages of `size` results until a page has fewer. SPARQL
+    /// queries get a `LIMIT` and `OFFSET`, other URLs `limit` and `offset`
+    /// query parameters.
+    Offset { size: usize },
+}
+
+/// A source fetched over HTTP: a web document, read with the reader for its
+/// content type, or a SPARQL endpoint queried with the iterator
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebLogicalSource {
+    pub url: String,
+    pub sparql_endpoint: bool,
+    pub reference_formulation: Option<String>,
+    pub iterator: Option<String>,
+    /// Request headers, eg for content negotiation or authentication
+    pub headers: Vec<(String, String)>,
+    pub pagination: Option<Pagination>,
+    pub nulls: HashSet<String>,
+}
+
+impl LogicalSource for WebLogicalSource {
+    fn get_nulls(&self) -> HashSet<String> {
+        self.nulls.clone()
+    }
+}
+
+/// A logical view over a logical source or another view, see
+/// <https://kg-construct.github.io/rml-lv/spec/docs/>. Each iteration of the
+/// view holds a single value per field, one for every combination of the
+/// field values of an iteration of `view_on`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogicalView {
+    pub view_on: Box<LogicalSourceType>,
+    pub fields: Vec<ViewField>,
+    pub joins: Vec<ViewJoin>,
+    pub nulls: HashSet<String>,
+}
+
+impl LogicalSource for LogicalView {
+    fn get_nulls(&self) -> HashSet<String> {
+        self.nulls.clone()
+    }
+}
+
+/// A named field of a logical view. Nested fields are referenced by their
+/// dotted path, eg `item.type`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViewField {
+    pub name: String,
+    pub kind: FieldKind,
+    pub fields: Vec<ViewField>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldKind {
+    /// The values of an expression. Nested fields read each value as a
+    /// document of their own reference formulation.
+    Expression(Expression),
+    /// The iterations `iterator` selects in the parent iteration, or in the
+    /// parent value when read with `reference_formulation`
+    Iterable {
+        reference_formulation: Option<String>,
+        iterator: Option<String>,
+    },
+}
+
+/// A join of a logical view with a parent view, adding the fields read from
+/// the matching parent iterations
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViewJoin {
+    pub parent_view: Box<LogicalSourceType>,
+    pub join_conditions: Vec<JoinCondition>,
+    pub fields: Vec<ViewField>,
+    /// Inner joins drop the iterations without a matching parent, left joins
+    /// keep them without the joined fields
+    pub inner: bool,
+}
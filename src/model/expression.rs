@@ -1,4 +1,5 @@
-use super::function::{FunctionMap, Input, ReturnMap};
+use super::function::{FunctionMap, Input, ReturnMap, RmlIteration};
+use crate::source::pseudo;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expression {
@@ -11,11 +12,23 @@ pub enum Expression {
     Reference {
         reference: String,
     },
+    /// A reference evaluated on the values of the `document` reference, each
+    /// read as a document of `reference_formulation`. Not part of RML.
+    EmbeddedReference {
+        document: String,
+        reference_formulation: String,
+        reference: String,
+    },
     FunctionExecution {
         function_map: Box<FunctionMap>,
         return_map: Option<Box<ReturnMap>>,
         inputs: Vec<Input>,
     },
+    /// A new blank node for every iteration, generated by the blank node
+    /// term map that declares no expression, described by `term_map`
+    Unlabelled {
+        term_map: String,
+    },
 }
 
 impl Expression {
@@ -26,41 +39,136 @@ impl Expression {
             Expression::Constant { .. } => Vec::new(),
             Expression::Template { template } => extract_template_references(template),
             Expression::Reference { reference } => vec![reference.clone()],
-            Expression::FunctionExecution { .. } => Vec::new(), // Functions don't have direct references
+            Expression::EmbeddedReference { document, .. } => vec![document.clone()],
+            // Told apart by the position of the iteration
+            Expression::Unlabelled { .. } => vec![pseudo::INDEX.to_string()],
+            Expression::FunctionExecution {
+                function_map,
+                inputs,
+                ..
+            } => function_map
+                .expression
+                .references()
+                .into_iter()
+                .chain(
+                    inputs
+                        .iter()
+                        .flat_map(|input| input.input_value_map.expression.references()),
+                )
+                .collect(),
         }
     }
 }
 
-fn extract_template_references(template: &str) -> Vec<String> {
-    let mut references = Vec::new();
-    let mut chars = template.chars().peekable();
+/// A piece of a template: either literal text or a reference to substitute
+#[derive(Debug, Clone, PartialEq)]
+pub enum TemplateSegment {
+    Text(String),
+    Reference(String),
+}
 
-    while let Some(ch) = chars.next() {
-        if ch == '{' && chars.peek() != Some(&'\\') {
-            let mut reference = String::new();
-            let mut escaped = false;
+/// Split a template into literal text and references, resolving `\{`, `\}` and
+/// `\\` escapes. See <https://kg-construct.github.io/rml-core/spec/docs/#dfn-template-expression>
+pub fn template_segments(template: &str) -> Vec<TemplateSegment> {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut in_reference = false;
+    let mut chars = template.chars();
 
-            while let Some(ch) = chars.next() {
-                if ch == '\\' && !escaped {
-                    escaped = true;
-                    continue;
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    current.push(escaped);
                 }
-
-                if ch == '}' && !escaped {
-                    break;
+            }
+            '{' if !in_reference => {
+                if !current.is_empty() {
+                    segments.push(TemplateSegment::Text(std::mem::take(&mut current)));
                 }
+                in_reference = true;
+            }
+            '}' if in_reference => {
+                if !current.is_empty() {
+                    segments.push(TemplateSegment::Reference(std::mem::take(&mut current)));
+                }
+                in_reference = false;
+            }
+            _ => current.push(ch),
+        }
+    }
+
+    if !current.is_empty() {
+        segments.push(TemplateSegment::Text(current));
+    }
+
+    segments
+}
+
+/// Render a template for every combination of the values of its references.
+/// A reference without values, or whose values are all null, yields no
+/// rendered strings at all. With `iri_safe`, values are percent-encoded as
+/// required for IRI term types.
+pub fn render_template(
+    template: &str,
+    iteration: &dyn RmlIteration,
+    iri_safe: bool,
+) -> Vec<String> {
+    let nulls = iteration.get_nulls();
+    let mut rendered = vec![String::new()];
 
-                reference.push(ch);
-                escaped = false;
+    for segment in template_segments(template) {
+        match segment {
+            TemplateSegment::Text(text) => rendered.iter_mut().for_each(|r| r.push_str(&text)),
+            TemplateSegment::Reference(reference) => {
+                let values: Vec<String> = iteration
+                    .get_strings_for(&reference)
+                    .into_iter()
+                    .filter(|value| !nulls.contains(value))
+                    .map(|value| {
+                        if iri_safe {
+                            iri_safe_value(&value)
+                        } else {
+                            value
+                        }
+                    })
+                    .collect();
+                rendered = rendered
+                    .iter()
+                    .flat_map(|prefix| values.iter().map(move |value| format!("{prefix}{value}")))
+                    .collect();
             }
+        }
+    }
+
+    rendered
+}
 
-            if !reference.is_empty() {
-                references.push(reference);
+/// Percent-encode every character outside `iunreserved`, see
+/// <https://kg-construct.github.io/rml-core/spec/docs/#dfn-iri-safe>
+fn iri_safe_value(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | '~') || !c.is_ascii() {
+            encoded.push(c);
+        } else {
+            let mut buffer = [0; 4];
+            for byte in c.encode_utf8(&mut buffer).bytes() {
+                encoded.push_str(&format!("%{byte:02X}"));
             }
         }
     }
+    encoded
+}
 
-    references
+fn extract_template_references(template: &str) -> Vec<String> {
+    template_segments(template)
+        .into_iter()
+        .filter_map(|segment| match segment {
+            TemplateSegment::Reference(reference) => Some(reference),
+            TemplateSegment::Text(_) => None,
+        })
+        .collect()
 }
 
 #[derive(Debug, Clone, PartialEq)]
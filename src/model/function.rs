@@ -1,13 +1,42 @@
-use oxrdf::NamedNode;
+use oxrdf::{NamedNode, NamedNodeRef};
 use std::collections::{HashMap, HashSet};
+use thiserror::Error;
 
 use super::expression::Expression;
 use crate::vocab::rml;
 
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum FunctionError {
+    #[error("No implementation registered for function `{0}`")]
+    UnknownFunction(String),
+    #[error("Missing required parameter `{0}`")]
+    MissingParameter(String),
+    #[error("Function did not produce the requested output `{0}`")]
+    MissingOutput(String),
+    #[error("Function has no default output")]
+    NoDefaultOutput(),
+    #[error("Return map must be a constant output IRI")]
+    InvalidReturnMap(),
+    #[error("Function execution failed: {0}")]
+    ExecutionFailed(String),
+}
+
 pub trait RmlIteration {
     fn get_values_for(&self, reference: &str) -> Vec<String>;
     fn get_strings_for(&self, reference: &str) -> Vec<String>;
-    fn get_nulls(&self) -> HashSet<String>;
+    fn get_nulls(&self) -> &HashSet<String>;
+
+    /// The natural RDF datatype of the values of `reference`, for sources
+    /// with typed values
+    fn get_datatype_for(&self, _reference: &str) -> Option<NamedNodeRef<'_>> {
+        None
+    }
+
+    /// The nested iterations `iterator` selects within this iteration, for
+    /// sources with nested values
+    fn get_iterations_for(&self, _iterator: &str) -> Vec<Box<dyn RmlIteration + Send + Sync>> {
+        Vec::new()
+    }
 }
 
 pub trait Gatherable {
@@ -93,6 +122,14 @@ impl ReturnMap {
             term_type: NamedNode::new_unchecked(rml::TermType::IRI),
         }
     }
+
+    /// The IRI of the function output this return map selects
+    pub fn output(&self) -> Option<&str> {
+        match &self.expression {
+            Expression::Constant { constant } => Some(constant),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -135,6 +172,29 @@ impl Return {
     pub fn get_default_value(&self) -> Option<&String> {
         self.default_value.as_ref()
     }
+
+    /// Whether the function produced no output at all, as when its inputs
+    /// were null
+    pub fn is_empty(&self) -> bool {
+        self.default_value.is_none() && self.returns.is_empty()
+    }
+
+    /// Pick the output named by `return_map`, or the default output when no
+    /// return map is declared on the term map
+    pub fn select(&self, return_map: Option<&ReturnMap>) -> Result<&String, FunctionError> {
+        match return_map {
+            Some(return_map) => {
+                let output = return_map
+                    .output()
+                    .ok_or(FunctionError::InvalidReturnMap())?;
+                self.get(output)
+                    .ok_or_else(|| FunctionError::MissingOutput(output.to_string()))
+            }
+            None => self
+                .get_default_value()
+                .ok_or(FunctionError::NoDefaultOutput()),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -143,3 +203,62 @@ pub struct RMLFunction {
     pub uri: String,
     pub description: Option<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STRING: &str = "http://example.com/string";
+    const LENGTH: &str = "http://example.com/length";
+
+    fn return_map(output: &str) -> ReturnMap {
+        ReturnMap::new(Expression::Constant {
+            constant: output.to_string(),
+        })
+    }
+
+    fn outputs() -> Return {
+        let mut outputs = Return::new(Some("ann".to_string()));
+        outputs.put(STRING.to_string(), "ann".to_string());
+        outputs.put(LENGTH.to_string(), "3".to_string());
+        outputs
+    }
+
+    #[test]
+    fn selects_the_output_of_the_return_map() {
+        assert_eq!(outputs().select(Some(&return_map(LENGTH))).unwrap(), "3");
+        assert_eq!(outputs().select(Some(&return_map(STRING))).unwrap(), "ann");
+    }
+
+    #[test]
+    fn selects_the_default_output_without_a_return_map() {
+        assert_eq!(outputs().select(None).unwrap(), "ann");
+
+        let mut named_only = Return::new(None);
+        named_only.put(STRING.to_string(), "ann".to_string());
+        assert_eq!(
+            named_only.select(None),
+            Err(FunctionError::NoDefaultOutput())
+        );
+    }
+
+    #[test]
+    fn rejects_outputs_that_were_not_produced() {
+        let missing = "http://example.com/missing";
+        assert_eq!(
+            outputs().select(Some(&return_map(missing))),
+            Err(FunctionError::MissingOutput(missing.to_string()))
+        );
+    }
+
+    #[test]
+    fn rejects_return_maps_that_are_not_constant() {
+        let reference = ReturnMap::new(Expression::Reference {
+            reference: "output".to_string(),
+        });
+        assert_eq!(
+            outputs().select(Some(&reference)),
+            Err(FunctionError::InvalidReturnMap())
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use oxrdf::NamedNode;
 
+use super::core::LogicalTarget;
 use super::expression::Expression;
 use super::function::{DatatypeMap, GatherMapMixin, Gatherable, LanguageMap};
 use super::ExpressionMap;
@@ -9,6 +10,7 @@ use crate::vocab::rml;
 pub struct PredicateMap {
     pub expression: Expression,
     pub term_type: NamedNode, // Always IRI for predicate maps
+    pub logical_targets: Vec<LogicalTarget>,
 }
 
 impl PredicateMap {
@@ -16,6 +18,7 @@ impl PredicateMap {
         Self {
             expression,
             term_type: NamedNode::new_unchecked(rml::TermType::IRI),
+            logical_targets: Vec::new(),
         }
     }
 }
@@ -27,6 +30,7 @@ pub struct ObjectMap {
     pub datatype_map: Option<DatatypeMap>,
     pub language_map: Option<LanguageMap>,
     pub gather_map: Option<GatherMapMixin>,
+    pub logical_targets: Vec<LogicalTarget>,
 }
 
 impl ObjectMap {
@@ -38,11 +42,12 @@ impl ObjectMap {
         gather_map: Option<GatherMapMixin>,
     ) -> Self {
         Self {
-            expression: expression,
+            expression,
             term_type: NamedNode::new_unchecked(term_type_iri),
-            datatype_map: datatype_map,
-            language_map: language_map,
-            gather_map: gather_map,
+            datatype_map,
+            language_map,
+            gather_map,
+            logical_targets: Vec::new(),
         }
     }
 }
@@ -57,6 +62,7 @@ impl Gatherable for ObjectMap {
 pub struct GraphMap {
     pub expression: Expression,
     pub term_type: NamedNode,
+    pub logical_targets: Vec<LogicalTarget>,
 }
 
 impl GraphMap {
@@ -64,6 +70,7 @@ impl GraphMap {
         Self {
             expression,
             term_type: NamedNode::new_unchecked(term_type_iri),
+            logical_targets: Vec::new(),
         }
     }
 }
@@ -75,6 +82,7 @@ pub struct SubjectMap {
     pub classes: Vec<String>,
     pub graph_maps: Vec<GraphMap>,
     pub gather_map: Option<GatherMapMixin>,
+    pub logical_targets: Vec<LogicalTarget>,
 }
 
 impl SubjectMap {
@@ -91,6 +99,7 @@ impl SubjectMap {
             classes,
             graph_maps,
             gather_map,
+            logical_targets: Vec::new(),
         }
     }
 }
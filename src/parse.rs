@@ -1,10 +1,91 @@
-use std::path::PathBuf;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
-use oxrdf::Dataset;
+use oxiri::{Iri, IriParseError};
+use oxrdf::vocab::{rdf, xsd};
+use oxrdf::{Dataset, GraphNameRef, NamedNodeRef, SubjectRef, TermRef};
+use thiserror::Error;
+
+use crate::model::{
+    Compression, DatabaseDescription, DatabaseLogicalSource, DatatypeMap, Encoding, Expression,
+    ExpressionMap, FieldKind, FileLogicalSource, FunctionMap, GraphMap, Input, InputValueMap,
+    JoinCondition, LanguageMap, LogicalSourceType, LogicalTarget, LogicalView, ObjectMap,
+    Pagination, ParameterMap, PredicateMap, PredicateObjectMap, ReferencingObjectMap, ReturnMap,
+    SourceDescription, SubjectMap, TriplesMap, ViewField, ViewJoin, WebLogicalSource,
+};
+use crate::vocab::{d2rq, dcat, htv, rml, sd};
+
+#[derive(Error, Debug)]
+pub enum ParseError {
+    #[error("`{0}` declares no constant, template, reference or function execution")]
+    MissingExpression(String),
+    #[error("Function execution `{0}` does not declare a function")]
+    MissingFunction(String),
+    #[error("Input `{0}` does not declare a parameter")]
+    MissingParameter(String),
+    #[error("Input `{0}` does not declare an input value")]
+    MissingInputValue(String),
+    #[error("`{0}` must be an IRI or blank node")]
+    InvalidNode(String),
+    #[error("Triples map `{0}` does not declare a logical source")]
+    MissingLogicalSource(String),
+    #[error("Logical source `{0}` does not declare a source")]
+    MissingSource(String),
+    #[error("Triples map `{0}` does not declare a subject map")]
+    MissingSubjectMap(String),
+    #[error("Join condition `{0}` must declare both a child and a parent")]
+    IncompleteJoinCondition(String),
+    #[error("Source `{0}` does not declare a path")]
+    MissingPath(String),
+    #[error("Database `{0}` does not declare a JDBC DSN")]
+    MissingDsn(String),
+    #[error("Unsupported compression `{0}`")]
+    UnsupportedCompression(String),
+    #[error("Unsupported encoding `{0}`")]
+    UnsupportedEncoding(String),
+    #[error("Logical target `{0}` does not declare a target")]
+    MissingTarget(String),
+    #[error(
+        "Logical target `{0}` cannot write to `-`, leave it out to write to the default output"
+    )]
+    StdinTarget(String),
+    #[error("Header `{0}` must declare both a field name and a field value")]
+    IncompleteHeader(String),
+    #[error("Unsupported pagination `{0}`")]
+    UnsupportedPagination(String),
+    #[error("Invalid page size `{0}`")]
+    InvalidPageSize(String),
+    #[error("Field `{0}` does not declare a field name")]
+    MissingFieldName(String),
+    #[error(
+        "Field `{0}` of an expression field must be an iterable field with a reference formulation"
+    )]
+    InvalidNestedField(String),
+    #[error("Field `{0}` cannot use a function execution")]
+    UnsupportedFieldExpression(String),
+    #[error("Join `{0}` does not declare a parent logical view")]
+    MissingParentView(String),
+    #[error("Embedded reference `{0}` does not declare a reference formulation")]
+    MissingReferenceFormulation(String),
+    #[error("Triples map `{0}` declares invalid base IRI `{1}`: {2}")]
+    InvalidBaseIri(String, String, #[source] IriParseError),
+}
+
+/// A term map given either through a constant shortcut property (eg
+/// `rml:predicate`) or as a term map node (eg `rml:predicateMap`)
+#[derive(Clone, Copy)]
+enum TermMapNode<'a> {
+    Constant(TermRef<'a>),
+    Map(SubjectRef<'a>),
+}
 
 pub struct RmlMappingParser {
     dataset: Dataset,
     mapping_path: PathBuf,
+    /// How often each description of an unlabelled blank node term map was
+    /// seen so far, to tell identical term maps apart
+    unlabelled: RefCell<HashMap<String, usize>>,
 }
 
 impl RmlMappingParser {
@@ -12,14 +93,1153 @@ impl RmlMappingParser {
         Self {
             dataset,
             mapping_path,
+            unlabelled: RefCell::default(),
         }
     }
 
-    pub fn parse(&self) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn parse(&self) -> Result<Vec<TriplesMap>, ParseError> {
         log::info!(
             "Parsing RML mappings from path: {}",
             self.mapping_path.to_str().unwrap_or("")
         );
-        Ok(())
+
+        // Triples maps are not required to be typed, so anything with a
+        // logical source is considered one too
+        let graph = self.dataset.graph(GraphNameRef::DefaultGraph);
+        let mut nodes: Vec<SubjectRef<'_>> = graph
+            .subjects_for_predicate_object(
+                rdf::TYPE,
+                NamedNodeRef::new_unchecked(rml::Classes::TRIPLES_MAP),
+            )
+            .collect();
+        for triple in graph
+            .triples_for_predicate(NamedNodeRef::new_unchecked(rml::Properties::LOGICAL_SOURCE))
+        {
+            if !nodes.contains(&triple.subject) {
+                nodes.push(triple.subject);
+            }
+        }
+
+        let mut triples_maps = nodes
+            .into_iter()
+            .map(|node| self.parse_triples_map(node))
+            .collect::<Result<Vec<_>, _>>()?;
+        triples_maps.sort_by(|a, b| a.uri.cmp(&b.uri));
+
+        log::info!("Parsed {} triples maps", triples_maps.len());
+        Ok(triples_maps)
+    }
+
+    fn parse_triples_map(&self, node: SubjectRef<'_>) -> Result<TriplesMap, ParseError> {
+        let logical_source = self
+            .object(node, rml::Properties::LOGICAL_SOURCE)
+            .ok_or_else(|| ParseError::MissingLogicalSource(node.to_string()))?;
+        let logical_source = self.parse_logical_source(as_subject(logical_source)?)?;
+
+        let subject_map = self
+            .term_map_nodes(node, rml::Properties::SUBJECT, rml::Properties::SUBJECT_MAP)
+            .into_iter()
+            .next()
+            .ok_or_else(|| ParseError::MissingSubjectMap(node.to_string()))?;
+        let subject_map = self.parse_subject_map(subject_map)?;
+
+        let predicate_object_maps = self
+            .objects(node, rml::Properties::PREDICATE_OBJECT_MAP)
+            .into_iter()
+            .map(|pom| self.parse_predicate_object_map(as_subject(pom)?))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut triples_map = TriplesMap::new(
+            subject_value(node),
+            logical_source,
+            subject_map,
+            predicate_object_maps,
+        );
+        if let Some(base_iri) = self.object(node, rml::Properties::BASE_IRI) {
+            let base_iri = term_value(base_iri);
+            Iri::parse(base_iri.as_str())
+                .map_err(|e| ParseError::InvalidBaseIri(node.to_string(), base_iri.clone(), e))?;
+            triples_map.base_iri = Some(base_iri);
+        }
+        Ok(triples_map)
+    }
+
+    fn parse_logical_source(&self, node: SubjectRef<'_>) -> Result<LogicalSourceType, ParseError> {
+        if let Some(view_on) = self.object(node, rml::Properties::VIEW_ON) {
+            return self.parse_logical_view(node, as_subject(view_on)?);
+        }
+        let source = self
+            .object(node, rml::Properties::SOURCE)
+            .ok_or_else(|| ParseError::MissingSource(node.to_string()))?;
+        let reference_formulation = self
+            .object(node, rml::Properties::REFERENCE_FORMULATION)
+            .map(term_value);
+        let iterator = self.object(node, rml::Properties::ITERATOR).map(term_value);
+        // RML-IO declares `rml:null` on the source description, earlier
+        // drafts on the logical source
+        let mut nulls: HashSet<String> = self
+            .objects(node, rml::Properties::NULL)
+            .into_iter()
+            .map(term_value)
+            .collect();
+        if let Ok(description) = as_subject(source) {
+            nulls.extend(
+                self.objects(description, rml::Properties::NULL)
+                    .into_iter()
+                    .map(term_value),
+            );
+        }
+
+        Ok(match reference_formulation.as_deref() {
+            Some(rml::ReferenceFormulation::SQL2008_TABLE) => {
+                LogicalSourceType::Database(DatabaseLogicalSource {
+                    source: self.parse_database_description(source)?,
+                    query: iterator.map(|table| format!("SELECT * FROM {table}")),
+                    nulls,
+                })
+            }
+            Some(rml::ReferenceFormulation::SQL2008_QUERY) => {
+                LogicalSourceType::Database(DatabaseLogicalSource {
+                    source: self.parse_database_description(source)?,
+                    query: iterator,
+                    nulls,
+                })
+            }
+            _ => match self.parse_web_source(source)? {
+                Some(web) => LogicalSourceType::Web(WebLogicalSource {
+                    reference_formulation,
+                    iterator,
+                    nulls,
+                    ..web
+                }),
+                None => LogicalSourceType::File(FileLogicalSource {
+                    source: self.parse_source_description(source)?,
+                    reference_formulation,
+                    iterator,
+                    nulls,
+                }),
+            },
+        })
+    }
+
+    /// Parse a logical view over the logical source or view `view_on`
+    fn parse_logical_view(
+        &self,
+        node: SubjectRef<'_>,
+        view_on: SubjectRef<'_>,
+    ) -> Result<LogicalSourceType, ParseError> {
+        let mut joins = Vec::new();
+        for (property, inner) in [
+            (rml::Properties::LEFT_JOIN, false),
+            (rml::Properties::INNER_JOIN, true),
+        ] {
+            for join in self.objects(node, property) {
+                joins.push(self.parse_view_join(as_subject(join)?, inner)?);
+            }
+        }
+
+        Ok(LogicalSourceType::View(LogicalView {
+            view_on: Box::new(self.parse_logical_source(view_on)?),
+            fields: self.parse_fields(node)?,
+            joins,
+            nulls: self
+                .objects(node, rml::Properties::NULL)
+                .into_iter()
+                .map(term_value)
+                .collect(),
+        }))
+    }
+
+    fn parse_view_join(&self, node: SubjectRef<'_>, inner: bool) -> Result<ViewJoin, ParseError> {
+        let parent_view = self
+            .object(node, rml::Properties::PARENT_LOGICAL_VIEW)
+            .ok_or_else(|| ParseError::MissingParentView(node.to_string()))?;
+        Ok(ViewJoin {
+            parent_view: Box::new(self.parse_logical_source(as_subject(parent_view)?)?),
+            join_conditions: self.parse_join_conditions(node)?,
+            fields: self.parse_fields(node)?,
+            inner,
+        })
+    }
+
+    /// Parse the `rml:field`s of a view, join or field. Fields are iterable
+    /// when typed `rml:IterableField` or declaring an iterator.
+    fn parse_fields(&self, node: SubjectRef<'_>) -> Result<Vec<ViewField>, ParseError> {
+        self.objects(node, rml::Properties::FIELD)
+            .into_iter()
+            .map(|field| {
+                let field = as_subject(field)?;
+                let name = self
+                    .object(field, rml::Properties::FIELD_NAME)
+                    .map(term_value)
+                    .ok_or_else(|| ParseError::MissingFieldName(field.to_string()))?;
+                let fields = self.parse_fields(field)?;
+                let iterator = self
+                    .object(field, rml::Properties::ITERATOR)
+                    .map(term_value);
+                let iterable = iterator.is_some()
+                    || self
+                        .objects(field, rdf::TYPE.as_str())
+                        .iter()
+                        .any(|class| term_value(*class) == rml::Classes::ITERABLE_FIELD);
+
+                let kind = if iterable {
+                    FieldKind::Iterable {
+                        reference_formulation: self
+                            .object(field, rml::Properties::REFERENCE_FORMULATION)
+                            .map(term_value),
+                        iterator,
+                    }
+                } else {
+                    let expression = self.parse_expression(field)?;
+                    if let Expression::FunctionExecution { .. } = expression {
+                        return Err(ParseError::UnsupportedFieldExpression(name));
+                    }
+                    // The values of an expression field can only be read as
+                    // documents
+                    let invalid = fields.iter().find(|nested| {
+                        !matches!(
+                            nested.kind,
+                            FieldKind::Iterable {
+                                reference_formulation: Some(_),
+                                ..
+                            }
+                        )
+                    });
+                    if let Some(nested) = invalid {
+                        return Err(ParseError::InvalidNestedField(nested.name.clone()));
+                    }
+                    FieldKind::Expression(expression)
+                };
+                Ok(ViewField { name, kind, fields })
+            })
+            .collect()
+    }
+
+    /// Parse a D2RQ database description. A plain literal is shorthand for a
+    /// DSN without credentials.
+    fn parse_database_description(
+        &self,
+        source: TermRef<'_>,
+    ) -> Result<DatabaseDescription, ParseError> {
+        let node = match source {
+            TermRef::Literal(dsn) => {
+                return Ok(DatabaseDescription {
+                    dsn: dsn.value().to_string(),
+                    username: None,
+                    password: None,
+                })
+            }
+            source => as_subject(source)?,
+        };
+
+        Ok(DatabaseDescription {
+            dsn: self
+                .object(node, d2rq::Properties::JDBC_DSN)
+                .map(term_value)
+                .ok_or_else(|| ParseError::MissingDsn(node.to_string()))?,
+            username: self
+                .object(node, d2rq::Properties::USERNAME)
+                .map(term_value),
+            password: self
+                .object(node, d2rq::Properties::PASSWORD)
+                .map(term_value),
+        })
+    }
+
+    /// Parse a source fetched over HTTP: an HTTP(S) URL, a DCAT distribution
+    /// or a SPARQL endpoint service description. Gives `None` for other
+    /// sources.
+    fn parse_web_source(
+        &self,
+        source: TermRef<'_>,
+    ) -> Result<Option<WebLogicalSource>, ParseError> {
+        let node = match source {
+            // The IRI of standard input is not fetched
+            TermRef::NamedNode(node) if node.as_str() == rml::Streams::STD_IN => return Ok(None),
+            TermRef::NamedNode(_) | TermRef::BlankNode(_) => Some(as_subject(source)?),
+            _ => None,
+        };
+        let endpoint = node.and_then(|node| self.object(node, sd::Properties::ENDPOINT));
+        let distribution = node.and_then(|node| {
+            self.object(node, dcat::Properties::DOWNLOAD_URL)
+                .or_else(|| self.object(node, dcat::Properties::ACCESS_URL))
+        });
+        let (url, sparql_endpoint) = match (endpoint, distribution) {
+            (Some(endpoint), _) => (term_value(endpoint), true),
+            (None, Some(url)) => (term_value(url), false),
+            (None, None) if is_web_url(&term_value(source)) => (term_value(source), false),
+            (None, None) => return Ok(None),
+        };
+
+        let mut headers = Vec::new();
+        let mut pagination = None;
+        if let Some(node) = node {
+            for header in self.objects(node, htv::Properties::HEADERS) {
+                let header = as_subject(header)?;
+                let name = self.object(header, htv::Properties::FIELD_NAME);
+                let value = self.object(header, htv::Properties::FIELD_VALUE);
+                let (Some(name), Some(value)) = (name, value) else {
+                    return Err(ParseError::IncompleteHeader(header.to_string()));
+                };
+                headers.push((term_value(name), term_value(value)));
+            }
+
+            pagination = match self.object(node, rml::Properties::PAGE_SIZE) {
+                Some(size) => {
+                    let size = term_value(size);
+                    match size.parse::<usize>() {
+                        Ok(size) if size > 0 => Some(Pagination::Offset { size }),
+                        _ => return Err(ParseError::InvalidPageSize(size)),
+                    }
+                }
+                None => match self
+                    .object(node, rml::Properties::PAGINATION)
+                    .map(term_value)
+                {
+                    None => None,
+                    Some(pagination) if pagination == rml::Pagination::NEXT_LINK => {
+                        Some(Pagination::NextLink)
+                    }
+                    Some(pagination) => return Err(ParseError::UnsupportedPagination(pagination)),
+                },
+            };
+        }
+
+        Ok(Some(WebLogicalSource {
+            url,
+            sparql_endpoint,
+            reference_formulation: None,
+            iterator: None,
+            headers,
+            pagination,
+            nulls: HashSet::new(),
+        }))
+    }
+
+    /// Parse an RML-IO source description. A plain literal is shorthand for
+    /// a path relative to the mapping directory, and `rml:StdIn` or `"-"`
+    /// stand for standard input.
+    fn parse_source_description(
+        &self,
+        source: TermRef<'_>,
+    ) -> Result<SourceDescription, ParseError> {
+        let node = match source {
+            TermRef::NamedNode(node) if node.as_str() == rml::Streams::STD_IN => {
+                return Ok(SourceDescription::stdin())
+            }
+            TermRef::Literal(path) if path.value() == "-" => return Ok(SourceDescription::stdin()),
+            TermRef::Literal(path) => {
+                return Ok(SourceDescription::new(
+                    self.resolve_path(None, path.value()),
+                ))
+            }
+            source => as_subject(source)?,
+        };
+
+        let path = self
+            .object(node, rml::Properties::PATH)
+            .map(term_value)
+            .ok_or_else(|| ParseError::MissingPath(node.to_string()))?;
+        let path = self.resolve_path(self.object(node, rml::Properties::ROOT), &path);
+
+        let compression = match self
+            .object(node, rml::Properties::COMPRESSION)
+            .map(term_value)
+        {
+            None => Compression::from_path(&path),
+            Some(compression) => match compression.as_str() {
+                rml::Compression::NONE => Compression::None,
+                rml::Compression::GZIP => Compression::Gzip,
+                rml::Compression::ZIP => Compression::Zip,
+                rml::Compression::TAR_XZ => Compression::TarXz,
+                rml::Compression::TAR_GZ => Compression::TarGz,
+                _ => return Err(ParseError::UnsupportedCompression(compression)),
+            },
+        };
+        let encoding = match self.object(node, rml::Properties::ENCODING).map(term_value) {
+            None => Encoding::Utf8,
+            Some(encoding) => match encoding.as_str() {
+                rml::Encoding::UTF_8 => Encoding::Utf8,
+                rml::Encoding::UTF_16 => Encoding::Utf16,
+                _ => return Err(ParseError::UnsupportedEncoding(encoding)),
+            },
+        };
+
+        Ok(SourceDescription {
+            path,
+            compression,
+            encoding,
+        })
+    }
+
+    /// Resolve a relative path against `rml:root`, which is either
+    /// `rml:MappingDirectory` (the default), `rml:CurrentWorkingDirectory` or
+    /// an explicit directory
+    fn resolve_path(&self, root: Option<TermRef<'_>>, path: &str) -> PathBuf {
+        let path = Path::new(path);
+        if path.is_absolute() {
+            return path.to_path_buf();
+        }
+
+        let root = match root.map(term_value).as_deref() {
+            None | Some(rml::SourceRoot::MAPPING_DIRECTORY) => self
+                .mapping_path
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default(),
+            Some(rml::SourceRoot::CURRENT_WORKING_DIRECTORY) => {
+                std::env::current_dir().unwrap_or_default()
+            }
+            Some(directory) => PathBuf::from(directory),
+        };
+        root.join(path)
+    }
+
+    fn parse_subject_map(&self, node: TermMapNode<'_>) -> Result<SubjectMap, ParseError> {
+        match node {
+            TermMapNode::Constant(constant) => Ok(SubjectMap::new(
+                Expression::Constant {
+                    constant: term_value(constant),
+                },
+                constant_term_type(constant).to_string(),
+                Vec::new(),
+                Vec::new(),
+                None,
+            )),
+            TermMapNode::Map(map) => {
+                let mut subject_map = SubjectMap::new(
+                    self.parse_term_map_expression(map)?,
+                    self.term_type(map, rml::TermType::IRI),
+                    self.objects(map, rml::Properties::CLASS)
+                        .into_iter()
+                        .map(term_value)
+                        .collect(),
+                    self.parse_graph_maps(map)?,
+                    None,
+                );
+                subject_map.logical_targets = self.parse_logical_targets(map)?;
+                Ok(subject_map)
+            }
+        }
+    }
+
+    fn parse_predicate_object_map(
+        &self,
+        node: SubjectRef<'_>,
+    ) -> Result<PredicateObjectMap, ParseError> {
+        let predicate_maps = self
+            .term_map_nodes(
+                node,
+                rml::Properties::PREDICATE,
+                rml::Properties::PREDICATE_MAP,
+            )
+            .into_iter()
+            .map(|predicate| {
+                let mut predicate_map = PredicateMap::new(self.term_map_expression(&predicate)?);
+                predicate_map.logical_targets = self.term_map_targets(&predicate)?;
+                Ok(predicate_map)
+            })
+            .collect::<Result<Vec<_>, ParseError>>()?;
+
+        let mut object_maps = Vec::new();
+        let mut ref_object_maps = Vec::new();
+        for object in
+            self.term_map_nodes(node, rml::Properties::OBJECT, rml::Properties::OBJECT_MAP)
+        {
+            match object {
+                TermMapNode::Map(map)
+                    if self
+                        .object(map, rml::Properties::PARENT_TRIPLES_MAP)
+                        .is_some() =>
+                {
+                    ref_object_maps.push(self.parse_ref_object_map(map)?)
+                }
+                object => object_maps.push(self.parse_object_map(object)?),
+            }
+        }
+
+        Ok(PredicateObjectMap::new(
+            match node {
+                SubjectRef::NamedNode(_) => Some(subject_value(node)),
+                _ => None,
+            },
+            predicate_maps,
+            object_maps,
+            ref_object_maps,
+            self.parse_graph_maps(node)?,
+        ))
+    }
+
+    fn parse_object_map(&self, node: TermMapNode<'_>) -> Result<ObjectMap, ParseError> {
+        match node {
+            TermMapNode::Constant(constant) => {
+                let (datatype_map, language_map) = match constant {
+                    TermRef::Literal(literal) => match literal.language() {
+                        Some(language) => {
+                            (None, Some(LanguageMap::new(constant_expression(language))))
+                        }
+                        None if literal.datatype() != xsd::STRING => (
+                            Some(DatatypeMap::new(constant_expression(
+                                literal.datatype().as_str(),
+                            ))),
+                            None,
+                        ),
+                        None => (None, None),
+                    },
+                    _ => (None, None),
+                };
+                Ok(ObjectMap::new(
+                    constant_expression(&term_value(constant)),
+                    constant_term_type(constant).to_string(),
+                    datatype_map,
+                    language_map,
+                    None,
+                ))
+            }
+            TermMapNode::Map(map) => {
+                let expression = self.parse_term_map_expression(map)?;
+                let datatype_map = self
+                    .parse_shortcut_or_map(
+                        map,
+                        rml::Properties::DATATYPE,
+                        rml::Properties::DATATYPE_MAP,
+                    )?
+                    .map(DatatypeMap::new);
+                let language_map = self
+                    .parse_shortcut_or_map(
+                        map,
+                        rml::Properties::LANGUAGE,
+                        rml::Properties::LANGUAGE_MAP,
+                    )?
+                    .map(LanguageMap::new);
+
+                // Objects default to literals when they are derived from data
+                // or carry a datatype or language, and to IRIs otherwise
+                let default_term_type =
+                    match (&expression, self.object(map, rml::Properties::CONSTANT)) {
+                        (_, Some(constant)) => constant_term_type(constant),
+                        (Expression::Reference { .. }, _)
+                        | (Expression::EmbeddedReference { .. }, _)
+                        | (Expression::FunctionExecution { .. }, _) => rml::TermType::LITERAL,
+                        _ if datatype_map.is_some() || language_map.is_some() => {
+                            rml::TermType::LITERAL
+                        }
+                        _ => rml::TermType::IRI,
+                    };
+
+                let mut object_map = ObjectMap::new(
+                    expression,
+                    self.term_type(map, default_term_type),
+                    datatype_map,
+                    language_map,
+                    None,
+                );
+                object_map.logical_targets = self.parse_logical_targets(map)?;
+                Ok(object_map)
+            }
+        }
+    }
+
+    fn parse_ref_object_map(
+        &self,
+        node: SubjectRef<'_>,
+    ) -> Result<ReferencingObjectMap, ParseError> {
+        let parent_uri = self
+            .object(node, rml::Properties::PARENT_TRIPLES_MAP)
+            .map(term_value)
+            .unwrap_or_default();
+
+        Ok(ReferencingObjectMap::new(
+            match node {
+                SubjectRef::NamedNode(_) => Some(subject_value(node)),
+                _ => None,
+            },
+            parent_uri,
+            self.parse_join_conditions(node)?,
+            None,
+        ))
+    }
+
+    /// Parse the join conditions of a referencing object map or view join
+    fn parse_join_conditions(
+        &self,
+        node: SubjectRef<'_>,
+    ) -> Result<Vec<JoinCondition>, ParseError> {
+        self.objects(node, rml::Properties::JOIN_CONDITION)
+            .into_iter()
+            .map(|join| {
+                let join = as_subject(join)?;
+                let child =
+                    self.parse_join_side(join, rml::Properties::CHILD, rml::Properties::CHILD_MAP)?;
+                let parent = self.parse_join_side(
+                    join,
+                    rml::Properties::PARENT,
+                    rml::Properties::PARENT_MAP,
+                )?;
+                match (child, parent) {
+                    (Some(child), Some(parent)) => Ok(JoinCondition::new(
+                        ExpressionMap::new(parent),
+                        ExpressionMap::new(child),
+                    )),
+                    _ => Err(ParseError::IncompleteJoinCondition(join.to_string())),
+                }
+            })
+            .collect()
+    }
+
+    /// The `rml:child` and `rml:parent` shortcuts are references rather than constants
+    fn parse_join_side(
+        &self,
+        join: SubjectRef<'_>,
+        shortcut: &str,
+        map: &str,
+    ) -> Result<Option<Expression>, ParseError> {
+        if let Some(reference) = self.object(join, shortcut) {
+            return Ok(Some(Expression::Reference {
+                reference: term_value(reference),
+            }));
+        }
+        match self.object(join, map) {
+            Some(map) => self.parse_expression(as_subject(map)?).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn parse_graph_maps(&self, node: SubjectRef<'_>) -> Result<Vec<GraphMap>, ParseError> {
+        self.term_map_nodes(node, rml::Properties::GRAPH, rml::Properties::GRAPH_MAP)
+            .into_iter()
+            .map(|graph| {
+                let term_type = match graph {
+                    TermMapNode::Map(map) => self.term_type(map, rml::TermType::IRI),
+                    TermMapNode::Constant(_) => rml::TermType::IRI.to_string(),
+                };
+                let mut graph_map = GraphMap::new(self.term_map_expression(&graph)?, term_type);
+                graph_map.logical_targets = self.term_map_targets(&graph)?;
+                Ok(graph_map)
+            })
+            .collect()
+    }
+
+    fn term_map_nodes<'a>(
+        &'a self,
+        node: SubjectRef<'_>,
+        shortcut: &str,
+        map: &str,
+    ) -> Vec<TermMapNode<'a>> {
+        let constants = self
+            .objects(node, shortcut)
+            .into_iter()
+            .map(TermMapNode::Constant);
+        let maps = self
+            .objects(node, map)
+            .into_iter()
+            .filter_map(|map| as_subject(map).ok())
+            .map(TermMapNode::Map);
+        constants.chain(maps).collect()
+    }
+
+    fn term_map_expression(&self, node: &TermMapNode<'_>) -> Result<Expression, ParseError> {
+        match node {
+            TermMapNode::Constant(constant) => Ok(constant_expression(&term_value(*constant))),
+            TermMapNode::Map(map) => self.parse_term_map_expression(*map),
+        }
+    }
+
+    /// Parse the expression of a term map. Blank node term maps may leave it
+    /// out to generate a new blank node for every iteration.
+    fn parse_term_map_expression(&self, map: SubjectRef<'_>) -> Result<Expression, ParseError> {
+        match self.parse_expression(map) {
+            Err(ParseError::MissingExpression(_))
+                if self.term_type(map, rml::TermType::IRI) == rml::TermType::BLANK_NODE =>
+            {
+                let description = self.describe_term_map(map);
+                let mut unlabelled = self.unlabelled.borrow_mut();
+                let seen = unlabelled.entry(description.clone()).or_default();
+                let term_map = format!("{description}\0{seen}");
+                *seen += 1;
+                Ok(Expression::Unlabelled { term_map })
+            }
+            result => result,
+        }
+    }
+
+    /// Describe a term map by its place in the mapping and its content, the
+    /// same way whatever ids the blank nodes of the mapping were given
+    fn describe_term_map(&self, map: SubjectRef<'_>) -> String {
+        let mut visited = HashSet::new();
+        let mut description = self.describe_node(map, &mut visited);
+        let mut node = map;
+        // Walk up to the named node the term map belongs to, eg the triples
+        // map or a named predicate-object map
+        while let SubjectRef::BlankNode(blank_node) = node {
+            let mut parents: Vec<_> = self
+                .dataset
+                .graph(GraphNameRef::DefaultGraph)
+                .triples_for_object(blank_node)
+                .map(|triple| (triple.subject, triple.predicate))
+                .filter(|(subject, _)| !visited.contains(subject))
+                .map(|(subject, predicate)| {
+                    let parent = self.describe_node(subject, &mut visited.clone());
+                    (format!("{parent} {predicate}"), subject)
+                })
+                .collect();
+            parents.sort_by(|a, b| a.0.cmp(&b.0));
+            let Some((parent, subject)) = parents.into_iter().next() else {
+                break;
+            };
+            description = format!("{parent} {description}");
+            visited.insert(subject);
+            node = subject;
+        }
+        description
+    }
+
+    /// The IRI of a named node, or the sorted properties of a blank node
+    fn describe_node<'a>(
+        &'a self,
+        node: SubjectRef<'a>,
+        visited: &mut HashSet<SubjectRef<'a>>,
+    ) -> String {
+        let SubjectRef::BlankNode(_) = node else {
+            return node.to_string();
+        };
+        if !visited.insert(node) {
+            return "[]".to_string();
+        }
+        let mut properties: Vec<String> = self
+            .dataset
+            .graph(GraphNameRef::DefaultGraph)
+            .triples_for_subject(node)
+            .map(|triple| match triple.object {
+                TermRef::BlankNode(object) => format!(
+                    "{} {}",
+                    triple.predicate,
+                    self.describe_node(object.into(), visited)
+                ),
+                object => format!("{} {object}", triple.predicate),
+            })
+            .collect();
+        properties.sort();
+        format!("[{}]", properties.join("; "))
+    }
+
+    /// Constant shortcuts cannot declare logical targets
+    fn term_map_targets(&self, node: &TermMapNode<'_>) -> Result<Vec<LogicalTarget>, ParseError> {
+        match node {
+            TermMapNode::Constant(_) => Ok(Vec::new()),
+            TermMapNode::Map(map) => self.parse_logical_targets(*map),
+        }
+    }
+
+    fn parse_logical_targets(&self, map: SubjectRef<'_>) -> Result<Vec<LogicalTarget>, ParseError> {
+        self.objects(map, rml::Properties::LOGICAL_TARGET)
+            .into_iter()
+            .map(|node| {
+                let node = as_subject(node)?;
+                let target = self
+                    .object(node, rml::Properties::TARGET)
+                    .ok_or_else(|| ParseError::MissingTarget(node.to_string()))?;
+                let description = self.parse_source_description(target)?;
+                if description.is_stdin() {
+                    return Err(ParseError::StdinTarget(node.to_string()));
+                }
+                Ok(LogicalTarget {
+                    uri: subject_value(node),
+                    target: description,
+                    serialization: self
+                        .object(node, rml::Properties::SERIALIZATION)
+                        .map(term_value),
+                })
+            })
+            .collect()
+    }
+
+    fn term_type(&self, map: SubjectRef<'_>, default: &str) -> String {
+        self.object(map, rml::Properties::TERM_TYPE)
+            .map(term_value)
+            .unwrap_or_else(|| default.to_string())
+    }
+
+    /// Parse the expression of an expression map node, eg a term map or a
+    /// function input value map
+    pub fn parse_expression(&self, map: SubjectRef<'_>) -> Result<Expression, ParseError> {
+        if let Some(constant) = self.object(map, rml::Properties::CONSTANT) {
+            return Ok(Expression::Constant {
+                constant: term_value(constant),
+            });
+        }
+        if let Some(template) = self.object(map, rml::Properties::TEMPLATE) {
+            return Ok(Expression::Template {
+                template: term_value(template),
+            });
+        }
+        if let Some(reference) = self.object(map, rml::Properties::REFERENCE) {
+            let reference = term_value(reference);
+            // A reference into a document held in the value of another
+            // reference
+            if let Some(document) = self.object(map, rml::Properties::EMBEDDED_IN) {
+                let reference_formulation = self
+                    .object(map, rml::Properties::REFERENCE_FORMULATION)
+                    .map(term_value)
+                    .ok_or_else(|| ParseError::MissingReferenceFormulation(map.to_string()))?;
+                return Ok(Expression::EmbeddedReference {
+                    document: term_value(document),
+                    reference_formulation,
+                    reference,
+                });
+            }
+            return Ok(Expression::Reference { reference });
+        }
+        if let Some(execution) = self.object(map, rml::Properties::FUNCTION_EXECUTION) {
+            return self.parse_function_execution(map, as_subject(execution)?);
+        }
+        Err(ParseError::MissingExpression(map.to_string()))
+    }
+
+    fn parse_function_execution(
+        &self,
+        map: SubjectRef<'_>,
+        execution: SubjectRef<'_>,
+    ) -> Result<Expression, ParseError> {
+        let function = self
+            .parse_shortcut_or_map(
+                execution,
+                rml::Properties::FUNCTION,
+                rml::Properties::FUNCTION_MAP,
+            )?
+            .ok_or_else(|| ParseError::MissingFunction(execution.to_string()))?;
+
+        let inputs = self
+            .objects(execution, rml::Properties::INPUT)
+            .into_iter()
+            .map(|input| self.parse_input(as_subject(input)?))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // The return map lives on the term map that refers to the execution,
+        // so the same execution can be shared by term maps picking different outputs
+        let return_map = self
+            .parse_shortcut_or_map(map, rml::Properties::RETURN, rml::Properties::RETURN_MAP)?
+            .map(|expression| Box::new(ReturnMap::new(expression)));
+
+        Ok(Expression::FunctionExecution {
+            function_map: Box::new(FunctionMap::new(function)),
+            return_map,
+            inputs,
+        })
+    }
+
+    fn parse_input(&self, input: SubjectRef<'_>) -> Result<Input, ParseError> {
+        let parameter = self
+            .parse_shortcut_or_map(
+                input,
+                rml::Properties::PARAMETER,
+                rml::Properties::PARAMETER_MAP,
+            )?
+            .ok_or_else(|| ParseError::MissingParameter(input.to_string()))?;
+
+        let value = self
+            .parse_shortcut_or_map(
+                input,
+                rml::Properties::INPUT_VALUE,
+                rml::Properties::INPUT_VALUE_MAP,
+            )?
+            .ok_or_else(|| ParseError::MissingInputValue(input.to_string()))?;
+
+        // Only input value maps carry term map attributes, the constant
+        // shortcut is always a plain value
+        let (term_type, datatype_map, language_map) = match self
+            .object(input, rml::Properties::INPUT_VALUE_MAP)
+            .map(as_subject)
+            .transpose()?
+        {
+            Some(map) => (
+                self.term_type(map, rml::TermType::LITERAL),
+                self.parse_shortcut_or_map(
+                    map,
+                    rml::Properties::DATATYPE,
+                    rml::Properties::DATATYPE_MAP,
+                )?
+                .map(DatatypeMap::new),
+                self.parse_shortcut_or_map(
+                    map,
+                    rml::Properties::LANGUAGE,
+                    rml::Properties::LANGUAGE_MAP,
+                )?
+                .map(LanguageMap::new),
+            ),
+            None => (rml::TermType::LITERAL.to_string(), None, None),
+        };
+
+        Ok(Input::new(
+            ParameterMap::new(parameter),
+            InputValueMap {
+                expression: value,
+                term_type,
+                datatype_map,
+                language_map,
+            },
+        ))
+    }
+
+    /// Resolve a constant shortcut property (eg `rml:function`) or its
+    /// expression map counterpart (eg `rml:functionMap`)
+    fn parse_shortcut_or_map(
+        &self,
+        node: SubjectRef<'_>,
+        shortcut: &str,
+        map: &str,
+    ) -> Result<Option<Expression>, ParseError> {
+        if let Some(constant) = self.object(node, shortcut) {
+            return Ok(Some(Expression::Constant {
+                constant: term_value(constant),
+            }));
+        }
+        match self.object(node, map) {
+            Some(map) => self.parse_expression(as_subject(map)?).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn object(&self, subject: SubjectRef<'_>, predicate: &str) -> Option<TermRef<'_>> {
+        self.dataset
+            .graph(GraphNameRef::DefaultGraph)
+            .object_for_subject_predicate(subject, NamedNodeRef::new_unchecked(predicate))
+    }
+
+    fn objects(&self, subject: SubjectRef<'_>, predicate: &str) -> Vec<TermRef<'_>> {
+        self.dataset
+            .graph(GraphNameRef::DefaultGraph)
+            .objects_for_subject_predicate(subject, NamedNodeRef::new_unchecked(predicate))
+            .collect()
+    }
+}
+
+fn constant_expression(constant: &str) -> Expression {
+    Expression::Constant {
+        constant: constant.to_string(),
+    }
+}
+
+/// The term type implied by the kind of RDF term used as a constant
+fn constant_term_type(constant: TermRef<'_>) -> &'static str {
+    match constant {
+        TermRef::BlankNode(_) => rml::TermType::BLANK_NODE,
+        TermRef::Literal(_) => rml::TermType::LITERAL,
+        _ => rml::TermType::IRI,
+    }
+}
+
+fn subject_value(subject: SubjectRef<'_>) -> String {
+    match subject {
+        SubjectRef::NamedNode(node) => node.as_str().to_string(),
+        SubjectRef::BlankNode(node) => node.as_str().to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// The lexical value of a term, without N-Triples quoting
+fn term_value(term: TermRef<'_>) -> String {
+    match term {
+        TermRef::NamedNode(node) => node.as_str().to_string(),
+        TermRef::BlankNode(node) => node.as_str().to_string(),
+        TermRef::Literal(literal) => literal.value().to_string(),
+        other => other.to_string(),
+    }
+}
+
+fn is_web_url(value: &str) -> bool {
+    let scheme = value
+        .split_once("://")
+        .map(|(scheme, _)| scheme.to_ascii_lowercase());
+    matches!(scheme.as_deref(), Some("http") | Some("https"))
+}
+
+fn as_subject(term: TermRef<'_>) -> Result<SubjectRef<'_>, ParseError> {
+    match term {
+        TermRef::NamedNode(node) => Ok(SubjectRef::NamedNode(node)),
+        TermRef::BlankNode(node) => Ok(SubjectRef::BlankNode(node)),
+        other => Err(ParseError::InvalidNode(other.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use oxrdfio::{RdfFormat, RdfParser};
+
+    use super::*;
+    use crate::model::{LogicalSourceType, TriplesMap};
+
+    const PREFIXES: &str = "
+        @prefix rml: <http://w3id.org/rml/> .
+        @prefix ex: <http://example.com/> .
+    ";
+
+    fn parse(mapping: &str) -> Result<Vec<TriplesMap>, ParseError> {
+        let mut dataset = Dataset::new();
+        let document = format!("{PREFIXES}{mapping}");
+        for quad in RdfParser::from_format(RdfFormat::Turtle).for_reader(document.as_bytes()) {
+            dataset.insert(&quad.unwrap());
+        }
+        RmlMappingParser::new(dataset, PathBuf::from("/data/mapping.ttl")).parse()
+    }
+
+    /// A triples map over `people.csv`, with `source` added to its source
+    /// description and `logical_source` to its logical source
+    fn people(logical_source: &str, source: &str) -> String {
+        format!(
+            r#"
+            ex:People a rml:TriplesMap;
+              rml:logicalSource [
+                rml:referenceFormulation rml:CSV;
+                rml:source [ a rml:RelativePathSource;
+                  rml:root rml:MappingDirectory; rml:path "people.csv"; {source} ];
+                {logical_source}
+              ];
+              rml:subjectMap [ rml:template "http://example.com/person/{{id}}" ] .
+            "#
+        )
+    }
+
+    fn nulls(triples_maps: &[TriplesMap]) -> Vec<String> {
+        let LogicalSourceType::File(source) = &triples_maps[0].logical_source else {
+            panic!("a file source");
+        };
+        let mut nulls: Vec<_> = source.nulls.iter().cloned().collect();
+        nulls.sort();
+        nulls
+    }
+
+    #[test]
+    fn reads_nulls_of_the_source_description() {
+        let triples_maps = parse(&people("", r#"rml:null "N/A", """#)).unwrap();
+        assert_eq!(nulls(&triples_maps), ["", "N/A"]);
+    }
+
+    fn compression(triples_maps: &[TriplesMap]) -> Compression {
+        let LogicalSourceType::File(source) = &triples_maps[0].logical_source else {
+            panic!("a file source");
+        };
+        source.source.compression
+    }
+
+    /// The unlabelled term maps of the subject and objects, by predicate
+    fn unlabelled(triples_maps: &[TriplesMap]) -> Vec<(String, String)> {
+        let triples_map = &triples_maps[0];
+        let objects = triples_map.predicate_object_maps.iter().flat_map(|pom| {
+            let predicate = match &pom.predicate_maps[0].expression {
+                Expression::Constant { constant } => constant.clone(),
+                other => panic!("a constant predicate, not {other:?}"),
+            };
+            pom.object_maps
+                .iter()
+                .map(move |object_map| (predicate.clone(), &object_map.expression))
+        });
+        let mut term_maps: Vec<_> = [(String::new(), &triples_map.subject_map.expression)]
+            .into_iter()
+            .chain(objects)
+            .filter_map(|(predicate, expression)| match expression {
+                Expression::Unlabelled { term_map } => Some((predicate, term_map.clone())),
+                _ => None,
+            })
+            .collect();
+        term_maps.sort();
+        term_maps
+    }
+
+    #[test]
+    fn describes_unlabelled_term_maps_the_same_way_every_time() {
+        let mapping = r#"
+            ex:People a rml:TriplesMap;
+              rml:logicalSource [ rml:referenceFormulation rml:CSV; rml:source "people.csv" ];
+              rml:subjectMap [ rml:termType rml:BlankNode ];
+              rml:predicateObjectMap
+                [ rml:predicate ex:address; rml:objectMap [ rml:termType rml:BlankNode ] ],
+                [ rml:predicate ex:knows; rml:objectMap [ rml:termType rml:BlankNode ] ],
+                [ rml:predicate ex:knows; rml:objectMap [ rml:termType rml:BlankNode ] ] .
+        "#;
+        let term_maps = unlabelled(&parse(mapping).unwrap());
+        assert_eq!(term_maps.len(), 4);
+        assert_eq!(term_maps.iter().collect::<HashSet<_>>().len(), 4);
+        for _ in 0..5 {
+            assert_eq!(unlabelled(&parse(mapping).unwrap()), term_maps);
+        }
+    }
+
+    #[test]
+    fn reads_stdin_sources() {
+        for source in [r#""-""#, "rml:StdIn"] {
+            let triples_maps = parse(&format!(
+                r#"
+                ex:People a rml:TriplesMap;
+                  rml:logicalSource [ rml:referenceFormulation rml:CSV; rml:source {source} ];
+                  rml:subjectMap [ rml:template "http://example.com/person/{{id}}" ] .
+                "#
+            ))
+            .unwrap();
+            assert!(triples_maps[0].logical_source.reads_stdin(), "{source}");
+        }
+    }
+
+    /// A triples map writing its subjects to a logical target with `target`
+    fn targeted(target: &str) -> String {
+        format!(
+            r#"
+            ex:People a rml:TriplesMap;
+              rml:logicalSource [
+                rml:referenceFormulation rml:CSV;
+                rml:source [ a rml:RelativePathSource; rml:path "people.csv" ]
+              ];
+              rml:subjectMap [
+                rml:template "http://example.com/person/{{id}}";
+                rml:logicalTarget [ rml:target {target} ]
+              ] .
+            "#
+        )
+    }
+
+    #[test]
+    fn reads_logical_targets() {
+        let triples_maps = parse(&targeted(
+            r#"[ a rml:RelativePathSource; rml:path "people.nq" ]"#,
+        ))
+        .unwrap();
+        let targets = &triples_maps[0].subject_map.logical_targets;
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].target.path, PathBuf::from("/data/people.nq"));
+    }
+
+    #[test]
+    fn rejects_logical_targets_writing_to_stdin() {
+        for target in [r#""-""#, "rml:StdIn"] {
+            assert!(
+                matches!(parse(&targeted(target)), Err(ParseError::StdinTarget(_))),
+                "{target}"
+            );
+        }
+    }
+
+    #[test]
+    fn reads_declared_compressions() {
+        for (iri, expected) in [
+            ("rml:none", Compression::None),
+            ("rml:gzip", Compression::Gzip),
+            ("rml:zip", Compression::Zip),
+            ("rml:tarxz", Compression::TarXz),
+            ("rml:targz", Compression::TarGz),
+        ] {
+            let triples_maps = parse(&people("", &format!("rml:compression {iri}"))).unwrap();
+            assert_eq!(compression(&triples_maps), expected, "{iri}");
+        }
+        assert!(matches!(
+            parse(&people("", "rml:compression ex:brotli")),
+            Err(ParseError::UnsupportedCompression(iri)) if iri == "http://example.com/brotli"
+        ));
+    }
+
+    #[test]
+    fn reads_nulls_of_the_logical_source() {
+        let triples_maps = parse(&people(r#"rml:null "N/A""#, r#"rml:null "-""#)).unwrap();
+        assert_eq!(nulls(&triples_maps), ["-", "N/A"]);
     }
 }
@@ -1,28 +1,1421 @@
-use oxrdf::Dataset;
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::{Arc, OnceLock};
+use std::thread;
+
+use oxiri::Iri;
+use oxrdf::vocab::rdf;
+use oxrdf::{BlankNode, GraphName, Literal, NamedNode, NamedNodeRef, Quad, Subject, Term};
+use rayon::prelude::*;
+use thiserror::Error;
+
+use crate::functions::{FunctionInputs, FunctionRegistry};
+use crate::model::{
+    reference_field, render_template, DatatypeMap, Expression, FileLogicalSource, FunctionError,
+    GraphMap, JoinCondition, LanguageMap, LogicalSourceType, LogicalTarget, RmlIteration,
+    TriplesMap,
+};
+use crate::sink::{OutputSinks, SinkError};
+use crate::source::partition::{self, Partition, PARTITION_SIZE};
+use crate::source::pseudo;
+use crate::source::web::{HttpTransport, Transport};
+use crate::source::{self, Record, SourceError};
+use crate::vocab::rml;
+
+#[derive(Error, Debug)]
+pub enum ProcessError {
+    #[error("Function execution did not resolve to a function IRI")]
+    MissingFunction(),
+    #[error("Function input did not resolve to a parameter IRI")]
+    MissingParameter(),
+    #[error("Function `{function}` failed: {source}")]
+    Function {
+        function: String,
+        #[source]
+        source: FunctionError,
+    },
+    #[error("Could not read the logical source of `{0}`: {1}")]
+    Source(String, #[source] Arc<SourceError>),
+    #[error("Triples map `{0}` refers to unknown parent triples map `{1}`")]
+    UnknownParent(String, String),
+    #[error("Could not write generated quads: {0}")]
+    Sink(#[from] SinkError),
+    #[error("Could not start the worker threads")]
+    ThreadPool(#[from] rayon::ThreadPoolBuildError),
+    #[error("Output closed before processing finished")]
+    OutputClosed(),
+}
+
+/// A generated quad with the logical targets it is exported to
+type Generated<'a> = (Quad, Vec<&'a LogicalTarget>);
+
+/// Quads sent to the output at once by a worker
+const BATCH_SIZE: usize = 1024;
+/// Batches waiting to be written before workers block
+const CHANNEL_BOUND: usize = 64;
+
+/// Parent subjects of a referencing object map, indexed by the values of
+/// the first join condition
+#[derive(Default)]
+struct JoinIndex {
+    entries: Vec<(Vec<Vec<String>>, Vec<Term>)>,
+    by_first: HashMap<String, Vec<usize>>,
+}
+
+impl JoinIndex {
+    fn lookup(&self, child_values: &[Vec<String>]) -> Vec<Term> {
+        let candidates: HashSet<usize> = child_values[0]
+            .iter()
+            .filter_map(|value| self.by_first.get(value))
+            .flatten()
+            .copied()
+            .collect();
+
+        let mut candidates: Vec<usize> = candidates.into_iter().collect();
+        candidates.sort_unstable();
+
+        let mut parents = Vec::new();
+        for index in candidates {
+            let (parent_values, subjects) = &self.entries[index];
+            let matches = child_values
+                .iter()
+                .zip(parent_values)
+                .skip(1)
+                .all(|(child, parent)| child.iter().any(|value| parent.contains(value)));
+            if matches {
+                parents.extend(subjects.iter().cloned());
+            }
+        }
+        parents
+    }
+}
+
+/// Position of a referencing object map in its triples map: predicate object
+/// map and referencing object map indices
+type JoinKey = (usize, usize);
+
+type ParsedSource = Result<Arc<Vec<Record>>, Arc<SourceError>>;
+
+struct SharedSource<'a> {
+    logical_source: &'a LogicalSourceType,
+    readers: usize,
+    /// The fields referenced by the parents and parent join conditions
+    projection: HashSet<String>,
+    parsed: OnceLock<ParsedSource>,
+}
+
+/// The logical sources read as the parents of joins. A source indexed by
+/// more than one join is parsed a single time and shared between workers.
+/// Other sources are streamed.
+struct SharedSources<'a> {
+    sources: Vec<SharedSource<'a>>,
+    transport: Arc<dyn Transport>,
+}
+
+impl<'a> SharedSources<'a> {
+    fn new(triples_maps: &'a [TriplesMap], transport: Arc<dyn Transport>) -> Self {
+        let mut sources: Vec<SharedSource<'a>> = Vec::new();
+        for triples_map in triples_maps {
+            let roms = triples_map
+                .joins()
+                .into_iter()
+                .flat_map(|pom| &pom.ref_object_maps)
+                .filter(|rom| !rom.join_conditions.is_empty());
+            for rom in roms {
+                let Some(parent) = triples_maps
+                    .iter()
+                    .find(|candidate| candidate.uri == rom.parent_uri)
+                else {
+                    continue;
+                };
+                let mut projection = parent.get_all_reference_fields();
+                for jc in &rom.join_conditions {
+                    projection.extend(reference_fields(&jc.parent_map.expression));
+                }
+
+                let logical_source = &parent.logical_source;
+                match sources
+                    .iter_mut()
+                    .find(|shared| shared.logical_source == logical_source)
+                {
+                    Some(shared) => {
+                        shared.readers += 1;
+                        shared.projection.extend(projection);
+                    }
+                    None => sources.push(SharedSource {
+                        logical_source,
+                        readers: 1,
+                        projection,
+                        parsed: OnceLock::new(),
+                    }),
+                }
+            }
+        }
+        Self { sources, transport }
+    }
+
+    /// Standard input can only be read once, so a join parent read from it
+    /// is also shared with the group of triples maps over it
+    fn share_stdin(&mut self, groups: &[SourceGroup<'a>]) {
+        for group in groups
+            .iter()
+            .filter(|group| group.logical_source.reads_stdin())
+        {
+            if let Some(shared) = self
+                .sources
+                .iter_mut()
+                .find(|shared| shared.logical_source == group.logical_source)
+            {
+                shared.readers += 1;
+                shared.projection.extend(group.projection.iter().cloned());
+            }
+        }
+    }
+
+    fn contains(&self, logical_source: &LogicalSourceType) -> bool {
+        self.sources
+            .iter()
+            .any(|shared| shared.logical_source == logical_source)
+    }
+
+    /// Call `f` with every iteration of a logical source read as a join
+    /// parent, reporting errors for the triples map `uri`
+    fn for_each_record(
+        &self,
+        uri: &str,
+        logical_source: &LogicalSourceType,
+        mut f: impl FnMut(&dyn RmlIteration) -> Result<(), ProcessError>,
+    ) -> Result<(), ProcessError> {
+        let read = |projection| {
+            source::read_logical_source(logical_source, self.transport.clone(), projection)
+        };
+        let error = |e| ProcessError::Source(uri.to_string(), e);
+
+        let shared = self
+            .sources
+            .iter()
+            .find(|shared| shared.logical_source == logical_source);
+        let projection = shared.map(|shared| &shared.projection);
+
+        match shared {
+            Some(shared) if shared.readers > 1 => {
+                let parsed = shared.parsed.get_or_init(|| {
+                    read(projection)
+                        .and_then(|records| records.collect::<Result<Vec<_>, _>>())
+                        .map(Arc::new)
+                        .map_err(Arc::new)
+                });
+                let records = parsed.as_ref().map_err(|e| error(e.clone()))?;
+                for record in records.iter() {
+                    f(record.as_ref())?;
+                }
+            }
+            _ => {
+                for record in read(projection).map_err(|e| error(Arc::new(e)))? {
+                    let record = record.map_err(|e| error(Arc::new(e)))?;
+                    f(record.as_ref())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Triples maps over equal logical sources, which are read once for all of
+/// them
+struct SourceGroup<'a> {
+    logical_source: &'a LogicalSourceType,
+    maps: Vec<usize>,
+    /// The fields referenced by any of the triples maps
+    projection: HashSet<String>,
+}
+
+impl SourceGroup<'_> {
+    /// The triples map errors about the shared source are reported for
+    fn uri<'a>(&self, triples_maps: &'a [TriplesMap]) -> &'a str {
+        &triples_maps[self.maps[0]].uri
+    }
+}
+
+/// Group triples maps by logical source, keeping the order of `order`
+fn source_groups<'a>(triples_maps: &'a [TriplesMap], order: &[usize]) -> Vec<SourceGroup<'a>> {
+    let mut groups: Vec<SourceGroup<'a>> = Vec::new();
+    for &index in order {
+        let triples_map = &triples_maps[index];
+        let mut projection = triples_map.get_all_reference_fields();
+        // Without join conditions, the parent subject is generated from the
+        // iterations of the child
+        for rom in triples_map
+            .joins()
+            .into_iter()
+            .flat_map(|pom| &pom.ref_object_maps)
+            .filter(|rom| rom.join_conditions.is_empty())
+        {
+            if let Some(parent) = triples_maps.iter().find(|c| c.uri == rom.parent_uri) {
+                projection.extend(reference_fields(&parent.subject_map.expression));
+            }
+        }
+
+        match groups
+            .iter_mut()
+            .find(|group| group.logical_source == &triples_map.logical_source)
+        {
+            Some(group) => {
+                group.maps.push(index);
+                group.projection.extend(projection);
+            }
+            None => groups.push(SourceGroup {
+                logical_source: &triples_map.logical_source,
+                maps: vec![index],
+                projection,
+            }),
+        }
+    }
+    groups
+}
 
 pub struct RmlProcessor {
     base_iri: Option<String>,
+    functions: FunctionRegistry,
+    threads: usize,
+    ordered: bool,
+    transport: Arc<dyn Transport>,
+    skolemize: bool,
 }
 
 impl RmlProcessor {
     pub fn new(base_iri: Option<String>) -> Self {
-        Self { base_iri }
+        Self {
+            base_iri,
+            functions: FunctionRegistry::with_builtins(),
+            threads: 0,
+            ordered: false,
+            transport: Arc::new(HttpTransport::default()),
+            skolemize: false,
+        }
+    }
+
+    /// Replace the function implementations available to function executions
+    pub fn with_functions(mut self, functions: FunctionRegistry) -> Self {
+        self.functions = functions;
+        self
     }
 
-    pub fn process(&self) -> Result<Dataset, Box<dyn std::error::Error>> {
-        // TODO: Implement RML processing logic
-        // This would take the parsed triples maps and process them
-        // to generate RDF data based on the input data sources
+    /// Process triples maps on `threads` worker threads, or one per core
+    /// when 0
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
 
+    /// Keep the quads of a partitioned source in source order, at the cost
+    /// of holding the quads of a window of partitions in memory
+    pub fn with_ordered(mut self, ordered: bool) -> Self {
+        self.ordered = ordered;
+        self
+    }
+
+    /// Send the HTTP requests of web logical sources through `transport`
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Replace blank nodes with IRIs under `/.well-known/genid/` of the base
+    /// IRI, so that the output of repeated runs can be compared. Triples maps
+    /// without a base IRI keep their blank nodes.
+    pub fn with_skolemize(mut self, skolemize: bool) -> Self {
+        self.skolemize = skolemize;
+        self
+    }
+
+    /// Generate the quads of all triples maps, streaming them into `output`
+    /// as they are generated. Triples maps are processed concurrently, while
+    /// quads are written from the calling thread.
+    pub fn process(
+        &self,
+        triples_maps: &[TriplesMap],
+        output: &mut OutputSinks,
+    ) -> Result<(), ProcessError> {
         log::info!("Processing RML mappings");
 
         if let Some(base_iri) = &self.base_iri {
             log::info!("Using base IRI: {}", base_iri);
         }
 
-        // Create an empty dataset for now
-        let dataset = Dataset::new();
+        let parents = self.join_parents(triples_maps)?;
+        let mut sources = SharedSources::new(triples_maps, self.transport.clone());
+        let groups = source_groups(triples_maps, &schedule(&parents));
+        sources.share_stdin(&groups);
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.threads)
+            .build()?;
+        let (sender, receiver) = mpsc::sync_channel(CHANNEL_BOUND);
+
+        thread::scope(|scope| -> Result<(), ProcessError> {
+            let workers = scope.spawn(|| {
+                pool.install(|| {
+                    groups
+                        .par_iter()
+                        .try_for_each_with(sender, |sender, group| {
+                            self.process_group(group, triples_maps, &sources, sender)
+                        })
+                })
+            });
+
+            // Dropping the receiver on a write error stops the workers
+            let mut written = Ok(());
+            for batch in receiver {
+                written = batch
+                    .iter()
+                    .try_for_each(|(quad, targets)| output.insert(quad, targets));
+                if written.is_err() {
+                    break;
+                }
+            }
+
+            let processed = workers
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+            written?;
+            processed
+        })
+    }
+
+    /// The parents each triples map joins with, one per referencing object
+    /// map with join conditions. This is the dependency graph of the mapping.
+    fn join_parents(&self, triples_maps: &[TriplesMap]) -> Result<Vec<Vec<usize>>, ProcessError> {
+        triples_maps
+            .iter()
+            .map(|triples_map| {
+                triples_map
+                    .joins()
+                    .into_iter()
+                    .flat_map(|pom| &pom.ref_object_maps)
+                    .filter(|rom| !rom.join_conditions.is_empty())
+                    .map(|rom| {
+                        triples_maps
+                            .iter()
+                            .position(|candidate| candidate.uri == rom.parent_uri)
+                            .ok_or_else(|| {
+                                ProcessError::UnknownParent(
+                                    triples_map.uri.clone(),
+                                    rom.parent_uri.clone(),
+                                )
+                            })
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Read the logical source of a group once, handing each iteration to
+    /// every triples map of the group
+    fn process_group<'a>(
+        &self,
+        group: &SourceGroup,
+        triples_maps: &'a [TriplesMap],
+        sources: &SharedSources,
+        sender: &SyncSender<Vec<Generated<'a>>>,
+    ) -> Result<(), ProcessError> {
+        let uris: Vec<&str> = group
+            .maps
+            .iter()
+            .map(|index| triples_maps[*index].uri.as_str())
+            .collect();
+        log::info!("Processing triples maps {}", uris.join(", "));
+
+        let uri = group.uri(triples_maps);
+        let joins = group
+            .maps
+            .iter()
+            .map(|index| self.join_indexes(&triples_maps[*index], triples_maps, sources))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Only local files are split into partitions, and only when no triples
+        // map refers to the position of the iterations in the file
+        if let LogicalSourceType::File(file) = group.logical_source
+            && !group
+                .projection
+                .iter()
+                .any(|field| pseudo::is_pseudo_reference(field))
+            && let Some(partitions) = self.partitions(uri, file)?
+        {
+            return self.process_partitions(group, triples_maps, &joins, file, &partitions, sender);
+        }
+
+        self.process_iterations(
+            &group.maps,
+            triples_maps,
+            &joins,
+            |f| {
+                if group.logical_source.reads_stdin() && sources.contains(group.logical_source) {
+                    return sources.for_each_record(uri, group.logical_source, f);
+                }
+                let error = |e| ProcessError::Source(uri.to_string(), Arc::new(e));
+                let records = source::read_logical_source(
+                    group.logical_source,
+                    self.transport.clone(),
+                    Some(&group.projection),
+                );
+                for record in records.map_err(error)? {
+                    f(record.map_err(error)?.as_ref())?;
+                }
+                Ok(())
+            },
+            |batch| send(sender, batch),
+        )
+    }
+
+    /// The partitions of a large source, when there are several workers to
+    /// process them
+    fn partitions(
+        &self,
+        uri: &str,
+        file: &FileLogicalSource,
+    ) -> Result<Option<Vec<Partition>>, ProcessError> {
+        if rayon::current_num_threads() < 2 {
+            return Ok(None);
+        }
+        partition::partitions(file, PARTITION_SIZE)
+            .map_err(|e| ProcessError::Source(uri.to_string(), Arc::new(e)))
+    }
+
+    /// Process the partitions of a source concurrently. In ordered mode, the
+    /// quads of a window of partitions are collected, then sent in source
+    /// order.
+    fn process_partitions<'a>(
+        &self,
+        group: &SourceGroup,
+        triples_maps: &'a [TriplesMap],
+        joins: &[HashMap<JoinKey, JoinIndex>],
+        file: &FileLogicalSource,
+        partitions: &[Partition],
+        sender: &SyncSender<Vec<Generated<'a>>>,
+    ) -> Result<(), ProcessError> {
+        let uri = group.uri(triples_maps);
+        log::info!("Processing {} partitions of {}", partitions.len(), uri);
+
+        let each_partition_record =
+            |partition: &Partition,
+             f: &mut dyn FnMut(&dyn RmlIteration) -> Result<(), ProcessError>| {
+                let error = |e| ProcessError::Source(uri.to_string(), Arc::new(e));
+                let records = partition::read_partition(file, partition, Some(&group.projection))
+                    .map_err(error)?;
+                for record in records {
+                    f(record.map_err(error)?.as_ref())?;
+                }
+                Ok(())
+            };
+
+        if !self.ordered {
+            return partitions
+                .par_iter()
+                .try_for_each_with(sender.clone(), |sender, partition| {
+                    self.process_iterations(
+                        &group.maps,
+                        triples_maps,
+                        joins,
+                        |f| each_partition_record(partition, f),
+                        |batch| send(sender, batch),
+                    )
+                });
+        }
+
+        for window in partitions.chunks(rayon::current_num_threads()) {
+            let generated = window
+                .par_iter()
+                .map(|partition| {
+                    let mut generated = Vec::new();
+                    self.process_iterations(
+                        &group.maps,
+                        triples_maps,
+                        joins,
+                        |f| each_partition_record(partition, f),
+                        |batch| {
+                            generated.extend(batch);
+                            Ok(())
+                        },
+                    )?;
+                    Ok(generated)
+                })
+                .collect::<Result<Vec<_>, ProcessError>>()?;
+            for quads in generated {
+                send(sender, quads)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Process the iterations that `each` calls its argument with for every
+    /// triples map in `maps`, handing generated quads to `emit` in batches
+    fn process_iterations<'a>(
+        &self,
+        maps: &[usize],
+        triples_maps: &'a [TriplesMap],
+        joins: &[HashMap<JoinKey, JoinIndex>],
+        each: impl FnOnce(
+            &mut dyn FnMut(&dyn RmlIteration) -> Result<(), ProcessError>,
+        ) -> Result<(), ProcessError>,
+        mut emit: impl FnMut(Vec<Generated<'a>>) -> Result<(), ProcessError>,
+    ) -> Result<(), ProcessError> {
+        let mut batch = Vec::with_capacity(BATCH_SIZE);
+        each(&mut |record| {
+            for (index, joins) in maps.iter().zip(joins) {
+                self.process_record(*index, triples_maps, joins, record, &mut batch)?;
+            }
+            if batch.len() >= BATCH_SIZE {
+                emit(std::mem::take(&mut batch))?;
+            }
+            Ok(())
+        })?;
+
+        if !batch.is_empty() {
+            emit(batch)?;
+        }
+        Ok(())
+    }
+
+    fn process_record<'a>(
+        &self,
+        index: usize,
+        triples_maps: &'a [TriplesMap],
+        joins: &HashMap<JoinKey, JoinIndex>,
+        record: &dyn RmlIteration,
+        output: &mut Vec<Generated<'a>>,
+    ) -> Result<(), ProcessError> {
+        let triples_map = &triples_maps[index];
+        let subject_map = &triples_map.subject_map;
+
+        let subjects = self.subjects(triples_map, record)?;
+        if subjects.is_empty() {
+            return Ok(());
+        }
+        let subject_graphs = self.graphs(triples_map, &subject_map.graph_maps, record)?;
+
+        for subject in &subjects {
+            for class in &subject_map.classes {
+                let class = NamedNode::new_unchecked(class.as_str());
+                for (graph, graph_targets) in with_default_graph(&subject_graphs) {
+                    let quad = Quad::new(subject.clone(), rdf::TYPE, class.clone(), graph.clone());
+                    output.push((
+                        quad,
+                        targets(&[&subject_map.logical_targets, graph_targets]),
+                    ));
+                }
+            }
+        }
+
+        for (pom_index, pom) in triples_map.predicate_object_maps.iter().enumerate() {
+            let mut predicates = Vec::new();
+            for predicate_map in &pom.predicate_maps {
+                for term in self.terms(
+                    triples_map,
+                    &predicate_map.expression,
+                    predicate_map.term_type.as_str(),
+                    None,
+                    None,
+                    record,
+                )? {
+                    if let Term::NamedNode(predicate) = term {
+                        predicates.push((predicate, predicate_map.logical_targets.as_slice()));
+                    }
+                }
+            }
+
+            let mut objects = Vec::new();
+            for object_map in &pom.object_maps {
+                for term in self.terms(
+                    triples_map,
+                    &object_map.expression,
+                    object_map.term_type.as_str(),
+                    object_map.datatype_map.as_ref(),
+                    object_map.language_map.as_ref(),
+                    record,
+                )? {
+                    objects.push((term, object_map.logical_targets.as_slice()));
+                }
+            }
+            for (rom_index, rom) in pom.ref_object_maps.iter().enumerate() {
+                let parents = match joins.get(&(pom_index, rom_index)) {
+                    Some(join) => {
+                        let child_values =
+                            self.join_values(&rom.join_conditions, record, |jc| {
+                                &jc.child_map.expression
+                            })?;
+                        join.lookup(&child_values)
+                    }
+                    // Without join conditions, the parent subject is generated
+                    // from the same iteration as the child
+                    None => {
+                        let parent = self.parent(triples_map, &rom.parent_uri, triples_maps)?;
+                        self.subjects(parent, record)?
+                            .into_iter()
+                            .map(Term::from)
+                            .collect()
+                    }
+                };
+                // Parent subjects are exported with the targets of the other
+                // term maps only
+                let no_targets: &[LogicalTarget] = &[];
+                objects.extend(parents.into_iter().map(|term| (term, no_targets)));
+            }
+
+            let mut graphs = subject_graphs.clone();
+            graphs.extend(self.graphs(triples_map, &pom.graph_maps, record)?);
+
+            for subject in &subjects {
+                for (predicate, predicate_targets) in &predicates {
+                    for (object, object_targets) in &objects {
+                        for (graph, graph_targets) in with_default_graph(&graphs) {
+                            let quad = Quad::new(
+                                subject.clone(),
+                                predicate.clone(),
+                                object.clone(),
+                                graph.clone(),
+                            );
+                            output.push((
+                                quad,
+                                targets(&[
+                                    &subject_map.logical_targets,
+                                    predicate_targets,
+                                    object_targets,
+                                    graph_targets,
+                                ]),
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn parent<'a>(
+        &self,
+        triples_map: &TriplesMap,
+        parent_uri: &str,
+        triples_maps: &'a [TriplesMap],
+    ) -> Result<&'a TriplesMap, ProcessError> {
+        triples_maps
+            .iter()
+            .find(|candidate| candidate.uri == parent_uri)
+            .ok_or_else(|| {
+                ProcessError::UnknownParent(triples_map.uri.clone(), parent_uri.to_string())
+            })
+    }
+
+    /// Index the parent subjects of each referencing object map of
+    /// `triples_map` with join conditions
+    fn join_indexes(
+        &self,
+        triples_map: &TriplesMap,
+        triples_maps: &[TriplesMap],
+        sources: &SharedSources,
+    ) -> Result<HashMap<JoinKey, JoinIndex>, ProcessError> {
+        let mut joins = HashMap::new();
+
+        for (pom_index, pom) in triples_map.predicate_object_maps.iter().enumerate() {
+            for (rom_index, rom) in pom.ref_object_maps.iter().enumerate() {
+                if rom.join_conditions.is_empty() {
+                    continue;
+                }
+                let parent = self.parent(triples_map, &rom.parent_uri, triples_maps)?;
+                let mut join = JoinIndex::default();
+                sources.for_each_record(&parent.uri, &parent.logical_source, |record| {
+                    let parent_values = self.join_values(&rom.join_conditions, record, |jc| {
+                        &jc.parent_map.expression
+                    })?;
+                    let subjects: Vec<Term> = self
+                        .subjects(parent, record)?
+                        .into_iter()
+                        .map(Term::from)
+                        .collect();
+                    if subjects.is_empty() {
+                        return Ok(());
+                    }
+                    for value in &parent_values[0] {
+                        join.by_first
+                            .entry(value.clone())
+                            .or_default()
+                            .push(join.entries.len());
+                    }
+                    join.entries.push((parent_values, subjects));
+                    Ok(())
+                })?;
+                joins.insert((pom_index, rom_index), join);
+            }
+        }
+
+        Ok(joins)
+    }
+
+    fn join_values(
+        &self,
+        join_conditions: &[JoinCondition],
+        record: &dyn RmlIteration,
+        side: impl Fn(&JoinCondition) -> &Expression,
+    ) -> Result<Vec<Vec<String>>, ProcessError> {
+        join_conditions
+            .iter()
+            .map(|jc| self.evaluate(side(jc), record))
+            .collect()
+    }
+
+    fn subjects(
+        &self,
+        triples_map: &TriplesMap,
+        record: &dyn RmlIteration,
+    ) -> Result<Vec<Subject>, ProcessError> {
+        let subject_map = &triples_map.subject_map;
+        Ok(self
+            .terms(
+                triples_map,
+                &subject_map.expression,
+                subject_map.term_type.as_str(),
+                None,
+                None,
+                record,
+            )?
+            .into_iter()
+            .filter_map(|term| match term {
+                Term::NamedNode(node) => Some(Subject::NamedNode(node)),
+                Term::BlankNode(node) => Some(Subject::BlankNode(node)),
+                _ => None,
+            })
+            .collect())
+    }
+
+    fn graphs<'a>(
+        &self,
+        triples_map: &TriplesMap,
+        graph_maps: &'a [GraphMap],
+        record: &dyn RmlIteration,
+    ) -> Result<Vec<(GraphName, &'a [LogicalTarget])>, ProcessError> {
+        let mut graphs = Vec::new();
+        for graph_map in graph_maps {
+            for term in self.terms(
+                triples_map,
+                &graph_map.expression,
+                graph_map.term_type.as_str(),
+                None,
+                None,
+                record,
+            )? {
+                let graph = match term {
+                    Term::NamedNode(node) if node.as_str() == rml::Graphs::DEFAULT_GRAPH => {
+                        GraphName::DefaultGraph
+                    }
+                    Term::NamedNode(node) => GraphName::NamedNode(node),
+                    Term::BlankNode(node) => GraphName::BlankNode(node),
+                    _ => continue,
+                };
+                graphs.push((graph, graph_map.logical_targets.as_slice()));
+            }
+        }
+        Ok(graphs)
+    }
+
+    /// Generate the RDF terms of a term map of `triples_map` for one iteration
+    fn terms(
+        &self,
+        triples_map: &TriplesMap,
+        expression: &Expression,
+        term_type: &str,
+        datatype_map: Option<&DatatypeMap>,
+        language_map: Option<&LanguageMap>,
+        record: &dyn RmlIteration,
+    ) -> Result<Vec<Term>, ProcessError> {
+        match term_type {
+            rml::TermType::IRI => {
+                // Values substituted in IRI templates must be IRI-safe
+                let values = match expression {
+                    Expression::Template { template } => render_template(template, record, true),
+                    _ => self.evaluate(expression, record)?,
+                };
+                Ok(values
+                    .iter()
+                    .filter_map(|value| self.iri(triples_map, value))
+                    .map(Term::from)
+                    .collect())
+            }
+            rml::TermType::BLANK_NODE => {
+                // Blank nodes of the same triples map and value are the same
+                // node, while unlabelled ones differ between term maps
+                let scope = match expression {
+                    Expression::Unlabelled { term_map } => {
+                        format!("{}\0{term_map}", triples_map.uri)
+                    }
+                    _ => triples_map.uri.clone(),
+                };
+                let skolem_prefix = match self.base_iri(triples_map) {
+                    Some(base_iri) if self.skolemize => skolem_prefix(base_iri),
+                    _ => None,
+                };
+                Ok(self
+                    .evaluate(expression, record)?
+                    .iter()
+                    .map(|value| {
+                        let label = blank_node_label(&scope, value);
+                        match &skolem_prefix {
+                            Some(prefix) => {
+                                Term::from(NamedNode::new_unchecked(format!("{prefix}{label}")))
+                            }
+                            None => Term::from(BlankNode::new_unchecked(label)),
+                        }
+                    })
+                    .collect())
+            }
+            _ => {
+                let language = match language_map {
+                    Some(map) => self.evaluate(&map.expression, record)?.into_iter().next(),
+                    None => None,
+                };
+                let datatype = match (datatype_map, expression) {
+                    (Some(map), _) => self
+                        .evaluate(&map.expression, record)?
+                        .into_iter()
+                        .next()
+                        .and_then(|datatype| self.iri(triples_map, &datatype)),
+                    // Values of typed sources give natural RDF literals
+                    (None, Expression::Reference { reference }) => record
+                        .get_datatype_for(reference)
+                        .map(NamedNodeRef::into_owned),
+                    (None, _) => None,
+                };
+
+                let mut literals = Vec::new();
+                for value in self.evaluate(expression, record)? {
+                    let literal = match (&language, &datatype) {
+                        (Some(language), _) => {
+                            match Literal::new_language_tagged_literal(value, language) {
+                                Ok(literal) => literal,
+                                Err(e) => {
+                                    log::warn!(
+                                        "Skipping literal with language `{}`: {}",
+                                        language,
+                                        e
+                                    );
+                                    continue;
+                                }
+                            }
+                        }
+                        (None, Some(datatype)) => {
+                            Literal::new_typed_literal(value, datatype.clone())
+                        }
+                        (None, None) => Literal::new_simple_literal(value),
+                    };
+                    literals.push(Term::from(literal));
+                }
+                Ok(literals)
+            }
+        }
+    }
+
+    /// The base IRI given to the processor, otherwise the `rml:baseIRI` of
+    /// `triples_map`
+    fn base_iri<'a>(&'a self, triples_map: &'a TriplesMap) -> Option<&'a str> {
+        self.base_iri.as_deref().or(triples_map.base_iri.as_deref())
+    }
+
+    /// An absolute IRI, or one made absolute by prepending the base IRI
+    fn iri(&self, triples_map: &TriplesMap, value: &str) -> Option<NamedNode> {
+        if let Ok(iri) = NamedNode::new(value) {
+            return Some(iri);
+        }
+        let resolved = match self.base_iri(triples_map) {
+            Some(base_iri) => NamedNode::new(format!("{base_iri}{value}")),
+            None => NamedNode::new(value),
+        };
+        match resolved {
+            Ok(iri) => Some(iri),
+            Err(e) => {
+                log::warn!("Skipping invalid IRI `{}`: {}", value, e);
+                None
+            }
+        }
+    }
+
+    /// Evaluate an expression against a single iteration of a logical source
+    pub fn evaluate(
+        &self,
+        expression: &Expression,
+        iteration: &dyn RmlIteration,
+    ) -> Result<Vec<String>, ProcessError> {
+        match expression {
+            Expression::Constant { constant } => Ok(vec![constant.clone()]),
+            Expression::Reference { reference } => {
+                let nulls = iteration.get_nulls();
+                Ok(iteration
+                    .get_values_for(reference)
+                    .into_iter()
+                    .filter(|value| !nulls.contains(value))
+                    .collect())
+            }
+            Expression::EmbeddedReference {
+                document,
+                reference_formulation,
+                reference,
+            } => Ok(source::embedded_values(
+                iteration,
+                document,
+                reference_formulation,
+                reference,
+            )),
+            Expression::Template { template } => Ok(render_template(template, iteration, false)),
+            Expression::Unlabelled { .. } => Ok(iteration.get_values_for(pseudo::INDEX)),
+            Expression::FunctionExecution {
+                function_map,
+                return_map,
+                inputs,
+            } => {
+                let function = self
+                    .evaluate(&function_map.expression, iteration)?
+                    .into_iter()
+                    .next()
+                    .ok_or(ProcessError::MissingFunction())?;
+
+                let mut arguments = FunctionInputs::new();
+                for input in inputs {
+                    let parameter = self
+                        .evaluate(&input.parameter_map.expression, iteration)?
+                        .into_iter()
+                        .next()
+                        .ok_or(ProcessError::MissingParameter())?;
+                    let values = self.evaluate(&input.input_value_map.expression, iteration)?;
+                    arguments.entry(parameter).or_default().extend(values);
+                }
+
+                let error = |source| ProcessError::Function {
+                    function: function.clone(),
+                    source,
+                };
+                let result = self
+                    .functions
+                    .execute(&function, &arguments)
+                    .map_err(error)?;
+                if result.is_empty() {
+                    return Ok(Vec::new());
+                }
+                let output = result.select(return_map.as_deref()).map_err(error)?;
+                Ok(vec![output.clone()])
+            }
+        }
+    }
+}
+
+/// The graphs of a term map, falling back to the default graph when no
+/// graph map applies
+fn with_default_graph<'g, 't>(
+    graphs: &'g [(GraphName, &'t [LogicalTarget])],
+) -> Vec<(&'g GraphName, &'t [LogicalTarget])> {
+    if graphs.is_empty() {
+        return vec![(&GraphName::DefaultGraph, &[])];
+    }
+    graphs
+        .iter()
+        .map(|(graph, targets)| (graph, *targets))
+        .collect()
+}
+
+/// The logical targets of all the term maps that contributed to a quad
+fn targets<'a>(term_map_targets: &[&'a [LogicalTarget]]) -> Vec<&'a LogicalTarget> {
+    let mut targets: Vec<&LogicalTarget> = Vec::new();
+    for target in term_map_targets.iter().flat_map(|targets| targets.iter()) {
+        if !targets.contains(&target) {
+            targets.push(target);
+        }
+    }
+    targets
+}
+
+/// The prefix of the skolem IRIs of blank nodes, at the root of the authority
+/// of the base IRI as in RFC 7511
+fn skolem_prefix(base_iri: &str) -> Option<String> {
+    Iri::parse(base_iri)
+        .and_then(|base_iri| base_iri.resolve("/.well-known/genid/"))
+        .map(Iri::into_inner)
+        .ok()
+}
+
+/// A blank node label hashed from the generated value and the triples map
+/// generating it, so that repeated runs label blank nodes alike. The hash is
+/// 128-bit FNV-1a, which unlike the standard library hasher is stable.
+fn blank_node_label(scope: &str, value: &str) -> String {
+    const OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+    const PRIME: u128 = 0x0000000001000000000000000000013b;
+
+    // 0xff never occurs in UTF-8, so it separates the scope from the value
+    let mut hash = OFFSET_BASIS;
+    for byte in scope.bytes().chain([0xff]).chain(value.bytes()) {
+        hash ^= u128::from(byte);
+        hash = hash.wrapping_mul(PRIME);
+    }
+    format!("b{hash:032x}")
+}
+
+/// The fields an expression refers to, named as in
+/// [`TriplesMap::get_all_reference_fields`]
+fn reference_fields(expression: &Expression) -> impl Iterator<Item = String> {
+    expression
+        .references()
+        .into_iter()
+        .map(|reference| reference_field(&reference).to_string())
+}
+
+fn send<'a>(
+    sender: &SyncSender<Vec<Generated<'a>>>,
+    batch: Vec<Generated<'a>>,
+) -> Result<(), ProcessError> {
+    sender.send(batch).map_err(|_| ProcessError::OutputClosed())
+}
+
+/// Order triples maps so that join parents come before their children, and
+/// children find the sources of their parents already parsed. Joins never
+/// need the parent to be processed first, so cycles are simply broken.
+fn schedule(parents: &[Vec<usize>]) -> Vec<usize> {
+    fn visit(index: usize, parents: &[Vec<usize>], visited: &mut [bool], order: &mut Vec<usize>) {
+        if visited[index] {
+            return;
+        }
+        visited[index] = true;
+        for parent in &parents[index] {
+            visit(*parent, parents, visited, order);
+        }
+        order.push(index);
+    }
+
+    let mut visited = vec![false; parents.len()];
+    let mut order = Vec::with_capacity(parents.len());
+    for index in 0..parents.len() {
+        visit(index, parents, &mut visited, &mut order);
+    }
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use oxrdf::Dataset;
+    use oxrdfio::{RdfFormat, RdfParser};
+
+    use super::*;
+    use crate::parse::RmlMappingParser;
+    use crate::sink::tests::Collect;
+    use crate::sink::QuadSink;
+
+    const MAPPING: &str = r#"
+        @prefix rml: <http://w3id.org/rml/> .
+        @prefix ex: <http://example.com/> .
+
+        ex:People a rml:TriplesMap;
+          rml:logicalSource ex:PeopleSource;
+          rml:subjectMap [ rml:template "http://example.com/person/{id}" ];
+          rml:predicateObjectMap [
+            rml:predicate ex:name;
+            rml:objectMap [ rml:reference "name" ]
+          ], [
+            rml:predicate ex:plays;
+            rml:objectMap [
+              rml:parentTriplesMap ex:Sports;
+              rml:joinCondition [ rml:child "sport"; rml:parent "id" ]
+            ]
+          ] .
+
+        ex:Sports a rml:TriplesMap;
+          rml:logicalSource [
+            rml:referenceFormulation rml:CSV;
+            rml:source [ a rml:RelativePathSource;
+              rml:root rml:MappingDirectory; rml:path "sports.csv" ]
+          ];
+          rml:subjectMap [ rml:template "http://example.com/sport/{id}" ];
+          rml:predicateObjectMap [
+            rml:predicate ex:label;
+            rml:objectMap [ rml:reference "label" ]
+          ] .
+
+        ex:Ages a rml:TriplesMap;
+          rml:logicalSource ex:PeopleSource;
+          rml:subjectMap [ rml:template "http://example.com/person/{id}" ];
+          rml:predicateObjectMap [
+            rml:predicate ex:age;
+            rml:objectMap [ rml:reference "age" ]
+          ] .
+
+        ex:PeopleSource rml:referenceFormulation rml:CSV;
+          rml:source [ a rml:RelativePathSource;
+            rml:root rml:MappingDirectory; rml:path "people.csv" ] .
+    "#;
+
+    /// Write `mapping` and its CSV sources to `dir`, and parse it
+    fn triples_maps(dir: &Path, mapping: &str) -> Vec<TriplesMap> {
+        let mut people = String::from("id,name,age,sport\n");
+        for id in 0..500 {
+            people.push_str(&format!("{id},Person {id},{},{}\n", id % 90, id % 7));
+        }
+        fs::write(dir.join("people.csv"), people).unwrap();
+        let sports: String = (0..7).map(|id| format!("{id},Sport {id}\n")).collect();
+        fs::write(dir.join("sports.csv"), format!("id,label\n{sports}")).unwrap();
+
+        let mut dataset = Dataset::new();
+        for quad in RdfParser::from_format(RdfFormat::Turtle).for_reader(mapping.as_bytes()) {
+            dataset.insert(&quad.unwrap());
+        }
+        RmlMappingParser::new(dataset, dir.join("mapping.ttl"))
+            .parse()
+            .unwrap()
+    }
+
+    fn no_targets(_: &LogicalTarget) -> Result<Box<dyn QuadSink>, SinkError> {
+        unreachable!("the mapping has no logical targets")
+    }
+
+    /// The sorted N-Quads lines generated with `processor`
+    fn generate(processor: &RmlProcessor, triples_maps: &[TriplesMap]) -> Vec<String> {
+        let collect = Collect::default();
+        let mut output = OutputSinks::new(Box::new(collect.clone()), no_targets);
+        processor.process(triples_maps, &mut output).unwrap();
+        output.finish().unwrap();
+
+        let (quads, finished) = &*collect.0.lock().unwrap();
+        assert!(finished);
+        let mut lines: Vec<_> = quads.iter().map(|quad| format!("{quad} .")).collect();
+        lines.sort();
+        lines
+    }
+
+    #[test]
+    fn schedules_parents_before_children() {
+        assert_eq!(schedule(&[vec![1], vec![2], vec![]]), [2, 1, 0]);
+        assert_eq!(schedule(&[vec![], vec![0], vec![0, 1]]), [0, 1, 2]);
+    }
+
+    #[test]
+    fn schedules_every_map_of_a_cycle_once() {
+        let mut order = schedule(&[vec![1], vec![2], vec![0], vec![3]]);
+        order.sort();
+        assert_eq!(order, [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn threads_generate_the_same_quads() {
+        let dir = tempfile::tempdir().unwrap();
+        let triples_maps = triples_maps(dir.path(), MAPPING);
+
+        let single = generate(&RmlProcessor::new(None).with_threads(1), &triples_maps);
+        // A name, an age and a sport per person, and a label per sport
+        assert_eq!(single.len(), 500 * 3 + 7);
+        assert!(single.contains(
+            &"<http://example.com/person/8> <http://example.com/plays> <http://example.com/sport/1> ."
+                .to_string()
+        ));
+        for threads in [2, 4, 8] {
+            let processor = RmlProcessor::new(None).with_threads(threads);
+            assert_eq!(generate(&processor, &triples_maps), single);
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_parents() {
+        let dir = tempfile::tempdir().unwrap();
+        let mapping = MAPPING.replace(
+            "rml:parentTriplesMap ex:Sports",
+            "rml:parentTriplesMap ex:Missing",
+        );
+        let triples_maps = triples_maps(dir.path(), &mapping);
+        let mut output = OutputSinks::new(Box::new(Collect::default()), no_targets);
+        let error = RmlProcessor::new(None)
+            .process(&triples_maps, &mut output)
+            .unwrap_err();
+        assert!(matches!(error, ProcessError::UnknownParent(child, parent)
+            if child == "http://example.com/People" && parent == "http://example.com/Missing"));
+    }
+
+    /// `MAPPING` with the ages read from their own logical source on `path`
+    fn separate_ages(path: &str) -> String {
+        let shared = "ex:Ages a rml:TriplesMap;\n          rml:logicalSource ex:PeopleSource;";
+        assert!(MAPPING.contains(shared));
+        MAPPING.replace(
+            shared,
+            &format!(
+                "ex:Ages a rml:TriplesMap;
+          rml:logicalSource [ rml:referenceFormulation rml:CSV;
+            rml:source [ a rml:RelativePathSource;
+              rml:root rml:MappingDirectory; rml:path \"{path}\" ] ];"
+            ),
+        )
+    }
+
+    #[test]
+    fn groups_triples_maps_over_equal_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        // The same source declared twice is still read once
+        for mapping in [MAPPING.to_string(), separate_ages("people.csv")] {
+            let triples_maps = triples_maps(dir.path(), &mapping);
+            let groups = source_groups(&triples_maps, &schedule(&[vec![], vec![], vec![]]));
+            assert_eq!(groups.len(), 2);
+
+            let people = groups
+                .iter()
+                .find(|group| group.maps.len() == 2)
+                .expect("a group for people.csv");
+            let mut uris: Vec<_> = people
+                .maps
+                .iter()
+                .map(|index| triples_maps[*index].uri.as_str())
+                .collect();
+            uris.sort();
+            assert_eq!(
+                uris,
+                ["http://example.com/Ages", "http://example.com/People"]
+            );
+            let mut projection: Vec<_> = people.projection.iter().map(String::as_str).collect();
+            projection.sort();
+            assert_eq!(projection, ["age", "id", "name", "sport"]);
+        }
+    }
+
+    #[test]
+    fn fans_a_shared_source_out_to_every_triples_map() {
+        let dir = tempfile::tempdir().unwrap();
+        let shared = generate(&RmlProcessor::new(None), &triples_maps(dir.path(), MAPPING));
+
+        fs::copy(dir.path().join("people.csv"), dir.path().join("ages.csv")).unwrap();
+        let separate = triples_maps(dir.path(), &separate_ages("ages.csv"));
+        assert_eq!(source_groups(&separate, &[0, 1, 2]).len(), 3);
+        assert_eq!(generate(&RmlProcessor::new(None), &separate), shared);
+    }
+
+    #[test]
+    fn parses_a_parent_of_several_joins_once() {
+        let dir = tempfile::tempdir().unwrap();
+        // Ages also joins with the sports
+        let mapping = MAPPING.replace(
+            "rml:objectMap [ rml:reference \"age\" ]",
+            "rml:objectMap [ rml:reference \"age\" ]
+          ], [
+            rml:predicate ex:ageGroupSport;
+            rml:objectMap [
+              rml:parentTriplesMap ex:Sports;
+              rml:joinCondition [ rml:child \"age\"; rml:parent \"id\" ]
+            ]",
+        );
+        let triples_maps = triples_maps(dir.path(), &mapping);
+        let sources = SharedSources::new(&triples_maps, Arc::new(HttpTransport::default()));
+        assert_eq!(sources.sources.len(), 1);
+        let sports = &sources.sources[0];
+        assert_eq!(sports.readers, 2);
+        assert!(sports.projection.contains("id") && sports.projection.contains("label"));
+
+        for _ in 0..2 {
+            let mut labels = Vec::new();
+            sources
+                .for_each_record(
+                    "http://example.com/Sports",
+                    sports.logical_source,
+                    |record| {
+                        labels.extend(record.get_values_for("label"));
+                        Ok(())
+                    },
+                )
+                .unwrap();
+            assert_eq!(labels.len(), 7);
+        }
+        let parsed = sports.parsed.get().expect("a parsed source");
+        assert_eq!(parsed.as_ref().unwrap().len(), 7);
+    }
+
+    /// The quads generated from `mapping` over a `people.csv` of `rows`
+    fn generate_people(mapping: &str, rows: &str) -> Vec<String> {
+        let dir = tempfile::tempdir().unwrap();
+        let triples_maps = triples_maps(dir.path(), &format!("{PREFIXES}{mapping}"));
+        fs::write(dir.path().join("people.csv"), rows).unwrap();
+        generate(&RmlProcessor::new(None), &triples_maps)
+    }
+
+    const PREFIXES: &str = "
+        @prefix rml: <http://w3id.org/rml/> .
+        @prefix grel: <http://users.ugent.be/~bjdmeest/function/grel.ttl#> .
+        @prefix ex: <http://example.com/> .
+    ";
+
+    #[test]
+    fn nulls_of_the_source_description_produce_no_terms() {
+        let mapping = r#"
+            ex:Names a rml:TriplesMap;
+              rml:logicalSource [
+                rml:referenceFormulation rml:CSV;
+                rml:source [ a rml:RelativePathSource;
+                  rml:root rml:MappingDirectory; rml:path "people.csv"; rml:null "N/A" ]
+              ];
+              rml:subjectMap [ rml:template "http://example.com/person/{name}" ];
+              rml:predicateObjectMap [
+                rml:predicate ex:id;
+                rml:objectMap [ rml:reference "id" ]
+              ] .
+        "#;
+        let quads = generate_people(mapping, "id,name\n1,Ann\n2,N/A\n");
+        assert_eq!(
+            quads,
+            [r#"<http://example.com/person/Ann> <http://example.com/id> "1" ."#]
+        );
+    }
+
+    #[test]
+    fn functions_over_null_inputs_produce_no_terms() {
+        let mapping = r#"
+            ex:Names a rml:TriplesMap;
+              rml:logicalSource [
+                rml:referenceFormulation rml:CSV;
+                rml:source [ a rml:RelativePathSource;
+                  rml:root rml:MappingDirectory; rml:path "people.csv"; rml:null "N/A" ]
+              ];
+              rml:subjectMap [ rml:template "http://example.com/person/{id}" ];
+              rml:predicateObjectMap [
+                rml:predicate ex:name;
+                rml:objectMap [
+                  rml:functionExecution [
+                    rml:function grel:toUpperCase;
+                    rml:input [
+                      rml:parameter grel:valueParam;
+                      rml:inputValueMap [ rml:reference "name" ]
+                    ]
+                  ]
+                ]
+              ] .
+        "#;
+        let quads = generate_people(mapping, "id,name\n1,Ann\n2,N/A\n");
+        assert_eq!(
+            quads,
+            [r#"<http://example.com/person/1> <http://example.com/name> "ANN" ."#]
+        );
+    }
+
+    /// Fails on the first quad
+    struct Failing;
+
+    impl QuadSink for Failing {
+        fn insert(&mut self, _: &Quad) -> Result<(), SinkError> {
+            Err(SinkError::Write(std::io::Error::other("disk full")))
+        }
+
+        fn finish(self: Box<Self>) -> Result<(), SinkError> {
+            Ok(())
+        }
+    }
 
-        Ok(dataset)
+    #[test]
+    fn write_errors_stop_the_workers() {
+        let dir = tempfile::tempdir().unwrap();
+        let triples_maps = triples_maps(dir.path(), MAPPING);
+        let mut output = OutputSinks::new(Box::new(Failing), no_targets);
+        let error = RmlProcessor::new(None)
+            .with_threads(4)
+            .process(&triples_maps, &mut output)
+            .unwrap_err();
+        assert!(matches!(error, ProcessError::Sink(SinkError::Write(_))));
     }
 }
@@ -0,0 +1,435 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{self, Path, PathBuf};
+
+use oxrdf::{Quad, TripleRef};
+use oxrdfio::{RdfFormat, RdfParseError, RdfSerializer, WriterQuadSerializer};
+use thiserror::Error;
+
+use crate::model::{Compression, LogicalTarget};
+use crate::target::CompressedWriter;
+
+#[derive(Error, Debug)]
+pub enum SinkError {
+    #[error("Could not write output")]
+    Write(#[from] io::Error),
+    #[error("Unsupported compression {1:?} for output `{0}`")]
+    UnsupportedCompression(String, Compression),
+    #[error("Unsupported serialization `{1}` for logical target `{0}`")]
+    UnsupportedSerialization(String, String),
+    #[error("Could not read back spilled quads")]
+    Spill(#[from] RdfParseError),
+    #[error("Logical targets `{1}` and `{2}` write to `{0}` with different serializations or compressions")]
+    ConflictingTargets(String, String, String),
+}
+
+/// Receives quads as they are generated
+pub trait QuadSink: Send {
+    fn insert(&mut self, quad: &Quad) -> Result<(), SinkError>;
+
+    /// Flush everything inserted so far and close the output
+    fn finish(self: Box<Self>) -> Result<(), SinkError>;
+}
+
+/// Serializes quads straight to a, possibly compressed, writer. Formats
+/// without graphs get every quad as a triple, with a warning the first time
+/// a graph name is dropped.
+pub struct SerializerSink<W: Write> {
+    name: String,
+    serializer: WriterQuadSerializer<CompressedWriter<W>>,
+    datasets: bool,
+    dropped_graphs: bool,
+}
+
+impl<W: Write> SerializerSink<W> {
+    pub fn new(
+        name: &str,
+        writer: W,
+        format: RdfFormat,
+        compression: Compression,
+    ) -> Result<Self, SinkError> {
+        if compression.is_archive() {
+            return Err(SinkError::UnsupportedCompression(
+                name.to_string(),
+                compression,
+            ));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            serializer: RdfSerializer::from_format(format)
+                .for_writer(CompressedWriter::new(writer, compression)?),
+            datasets: format.supports_datasets(),
+            dropped_graphs: false,
+        })
+    }
+}
+
+impl<W: Write + Send> QuadSink for SerializerSink<W> {
+    fn insert(&mut self, quad: &Quad) -> Result<(), SinkError> {
+        if self.datasets {
+            self.serializer.serialize_quad(quad)?;
+        } else {
+            if !quad.graph_name.is_default_graph() && !self.dropped_graphs {
+                log::warn!(
+                    "Dropping the graph names of quads written to `{}`, whose format has no graphs",
+                    self.name
+                );
+                self.dropped_graphs = true;
+            }
+            self.serializer
+                .serialize_triple(TripleRef::from(quad.as_ref()))?;
+        }
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), SinkError> {
+        self.serializer.finish()?.finish()?;
+        Ok(())
+    }
+}
+
+/// Drops quads that were already inserted, keeping every distinct quad in
+/// memory
+pub struct DedupSink {
+    inner: Box<dyn QuadSink>,
+    seen: HashSet<Quad>,
+}
+
+impl DedupSink {
+    pub fn new(inner: Box<dyn QuadSink>) -> Self {
+        Self {
+            inner,
+            seen: HashSet::new(),
+        }
+    }
+}
+
+impl QuadSink for DedupSink {
+    fn insert(&mut self, quad: &Quad) -> Result<(), SinkError> {
+        if self.seen.insert(quad.clone()) {
+            self.inner.insert(quad)?;
+        }
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), SinkError> {
+        self.inner.finish()
+    }
+}
+
+type OpenTarget = dyn Fn(&LogicalTarget) -> Result<Box<dyn QuadSink>, SinkError> + Send;
+
+/// Routes generated quads to the default output, or to the sinks of their
+/// logical targets, which are opened on first use. Logical targets writing
+/// to the same file share its sink.
+pub struct OutputSinks {
+    default: Box<dyn QuadSink>,
+    sinks: Vec<Box<dyn QuadSink>>,
+    /// The sink of each logical target, by index in `sinks`
+    targets: HashMap<LogicalTarget, usize>,
+    /// The sink writing to each file, with the logical target that opened it
+    files: HashMap<PathBuf, (usize, LogicalTarget)>,
+    open_target: Box<OpenTarget>,
+}
+
+impl OutputSinks {
+    pub fn new(
+        default: Box<dyn QuadSink>,
+        open_target: impl Fn(&LogicalTarget) -> Result<Box<dyn QuadSink>, SinkError> + Send + 'static,
+    ) -> Self {
+        Self {
+            default,
+            sinks: Vec::new(),
+            targets: HashMap::new(),
+            files: HashMap::new(),
+            open_target: Box::new(open_target),
+        }
+    }
+
+    /// Insert a quad into the sinks of `targets`, or into the default sink
+    /// when the quad has no logical target
+    pub fn insert(&mut self, quad: &Quad, targets: &[&LogicalTarget]) -> Result<(), SinkError> {
+        if targets.is_empty() {
+            return self.default.insert(quad);
+        }
+        let mut sinks = targets
+            .iter()
+            .map(|target| self.sink(target))
+            .collect::<Result<Vec<_>, _>>()?;
+        // Targets sharing a file get the quad once
+        sinks.sort_unstable();
+        sinks.dedup();
+        for index in sinks {
+            self.sinks[index].insert(quad)?;
+        }
+        Ok(())
+    }
+
+    /// The index of the sink of `target`, opening it unless another target
+    /// already writes to the same file
+    fn sink(&mut self, target: &LogicalTarget) -> Result<usize, SinkError> {
+        if let Some(index) = self.targets.get(target) {
+            return Ok(*index);
+        }
+        let file = target_file(&target.target.path);
+        let index = match self.files.get(&file) {
+            Some((index, opened)) => {
+                if opened.serialization != target.serialization
+                    || opened.target.compression != target.target.compression
+                {
+                    return Err(SinkError::ConflictingTargets(
+                        file.display().to_string(),
+                        opened.uri.clone(),
+                        target.uri.clone(),
+                    ));
+                }
+                *index
+            }
+            None => {
+                self.sinks.push((self.open_target)(target)?);
+                let index = self.sinks.len() - 1;
+                self.files.insert(file, (index, target.clone()));
+                index
+            }
+        };
+        self.targets.insert(target.clone(), index);
+        Ok(index)
+    }
+
+    pub fn finish(self) -> Result<(), SinkError> {
+        for sink in self.sinks {
+            sink.finish()?;
+        }
+        self.default.finish()
+    }
+}
+
+/// The file a target path names, with its directory resolved, so that
+/// `out.nq` and `./out.nq` are recognised as the same file
+fn target_file(path: &Path) -> PathBuf {
+    let absolute = path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let directory = absolute
+        .parent()
+        .and_then(|parent| fs::canonicalize(parent).ok());
+    match (directory, absolute.file_name()) {
+        (Some(directory), Some(name)) => directory.join(name),
+        _ => absolute,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::fs;
+    use std::io::Read;
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+
+    use oxrdf::{GraphName, NamedNode};
+
+    use super::*;
+    use crate::model::SourceDescription;
+
+    /// Collects inserted quads, and whether the sink was finished
+    #[derive(Clone, Default)]
+    pub(crate) struct Collect(pub(crate) Arc<Mutex<(Vec<Quad>, bool)>>);
+
+    impl QuadSink for Collect {
+        fn insert(&mut self, quad: &Quad) -> Result<(), SinkError> {
+            self.0.lock().unwrap().0.push(quad.clone());
+            Ok(())
+        }
+
+        fn finish(self: Box<Self>) -> Result<(), SinkError> {
+            self.0.lock().unwrap().1 = true;
+            Ok(())
+        }
+    }
+
+    fn quad(object: &str, graph: Option<&str>) -> Quad {
+        Quad::new(
+            NamedNode::new_unchecked("http://example.com/s"),
+            NamedNode::new_unchecked("http://example.com/p"),
+            NamedNode::new_unchecked(format!("http://example.com/{object}")),
+            graph.map_or(GraphName::DefaultGraph, |graph| {
+                NamedNode::new_unchecked(graph).into()
+            }),
+        )
+    }
+
+    fn target(path: &str) -> LogicalTarget {
+        LogicalTarget {
+            uri: format!("http://example.com/{path}"),
+            target: SourceDescription::new(PathBuf::from(path)),
+            serialization: None,
+        }
+    }
+
+    fn serialize(format: RdfFormat, compression: Compression, quads: &[Quad]) -> Vec<u8> {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let mut sink = Box::new(
+            SerializerSink::new("test", file.reopen().unwrap(), format, compression).unwrap(),
+        );
+        for quad in quads {
+            sink.insert(quad).unwrap();
+        }
+        sink.finish().unwrap();
+        fs::read(file.path()).unwrap()
+    }
+
+    #[test]
+    fn serializes_quads_with_their_graphs() {
+        let output = serialize(
+            RdfFormat::NQuads,
+            Compression::None,
+            &[quad("o", Some("http://example.com/g"))],
+        );
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "<http://example.com/s> <http://example.com/p> <http://example.com/o> <http://example.com/g> .\n"
+        );
+    }
+
+    #[test]
+    fn writes_quads_as_triples_to_formats_without_graphs() {
+        let output = serialize(
+            RdfFormat::NTriples,
+            Compression::None,
+            &[quad("o", Some("http://example.com/g")), quad("o2", None)],
+        );
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "<http://example.com/s> <http://example.com/p> <http://example.com/o> .\n\
+             <http://example.com/s> <http://example.com/p> <http://example.com/o2> .\n"
+        );
+    }
+
+    #[test]
+    fn compresses_the_serialization() {
+        let output = serialize(RdfFormat::NQuads, Compression::Gzip, &[quad("o", None)]);
+        let mut decoded = String::new();
+        flate2::read::GzDecoder::new(output.as_slice())
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(
+            decoded,
+            "<http://example.com/s> <http://example.com/p> <http://example.com/o> .\n"
+        );
+    }
+
+    #[test]
+    fn rejects_archive_compression() {
+        assert!(matches!(
+            SerializerSink::new("test", Vec::new(), RdfFormat::NQuads, Compression::Zip),
+            Err(SinkError::UnsupportedCompression(_, Compression::Zip))
+        ));
+    }
+
+    #[test]
+    fn dedup_sink_forwards_each_quad_once() {
+        let collect = Collect::default();
+        let mut sink = Box::new(DedupSink::new(Box::new(collect.clone())));
+        for object in ["a", "b", "a", "b", "c"] {
+            sink.insert(&quad(object, None)).unwrap();
+        }
+        sink.finish().unwrap();
+
+        let (quads, finished) = &*collect.0.lock().unwrap();
+        assert_eq!(quads, &[quad("a", None), quad("b", None), quad("c", None)]);
+        assert!(finished);
+    }
+
+    #[test]
+    fn output_sinks_route_quads_to_their_targets() {
+        let default = Collect::default();
+        let opened = Arc::new(Mutex::new(HashMap::<String, Collect>::new()));
+        let sinks = opened.clone();
+        let mut output = OutputSinks::new(Box::new(default.clone()), move |target| {
+            let sink = Collect::default();
+            sinks
+                .lock()
+                .unwrap()
+                .insert(target.uri.clone(), sink.clone());
+            Ok(Box::new(sink) as Box<dyn QuadSink>)
+        });
+
+        let (a, b) = (target("a.nq"), target("b.nq"));
+        output.insert(&quad("1", None), &[]).unwrap();
+        output.insert(&quad("2", None), &[&a]).unwrap();
+        output.insert(&quad("3", None), &[&a, &b]).unwrap();
+        assert_eq!(opened.lock().unwrap().len(), 2);
+        output.finish().unwrap();
+
+        let opened = opened.lock().unwrap();
+        let quads = |sink: &Collect| sink.0.lock().unwrap().clone();
+        assert_eq!(quads(&default), (vec![quad("1", None)], true));
+        assert_eq!(
+            quads(&opened[&a.uri]),
+            (vec![quad("2", None), quad("3", None)], true)
+        );
+        assert_eq!(quads(&opened[&b.uri]), (vec![quad("3", None)], true));
+    }
+
+    #[test]
+    fn output_sinks_report_targets_that_cannot_be_opened() {
+        let mut output = OutputSinks::new(Box::new(Collect::default()), |target| {
+            Err(SinkError::UnsupportedSerialization(
+                target.uri.clone(),
+                "http://example.com/format".to_string(),
+            ))
+        });
+        assert!(output.insert(&quad("o", None), &[&target("a.nq")]).is_err());
+    }
+
+    #[test]
+    fn output_sinks_share_a_sink_between_targets_writing_to_the_same_file() {
+        let directory = tempfile::tempdir().unwrap();
+        fs::create_dir(directory.path().join("sub")).unwrap();
+        let path = |path: &str| directory.path().join(path).display().to_string();
+        let (a, b, c) = (
+            target(&path("same.nq")),
+            target(&path("./same.nq")),
+            target(&path("sub/../same.nq")),
+        );
+        let opened = Arc::new(Mutex::new(Vec::<Collect>::new()));
+        let sinks = opened.clone();
+        let mut output = OutputSinks::new(Box::new(Collect::default()), move |_| {
+            let sink = Collect::default();
+            sinks.lock().unwrap().push(sink.clone());
+            Ok(Box::new(sink) as Box<dyn QuadSink>)
+        });
+
+        output.insert(&quad("1", None), &[&a]).unwrap();
+        output.insert(&quad("2", None), &[&b]).unwrap();
+        output.insert(&quad("3", None), &[&a, &b, &c]).unwrap();
+        output.finish().unwrap();
+
+        let opened = opened.lock().unwrap();
+        assert_eq!(opened.len(), 1);
+        assert_eq!(
+            *opened[0].0.lock().unwrap(),
+            (
+                vec![quad("1", None), quad("2", None), quad("3", None)],
+                true
+            )
+        );
+    }
+
+    #[test]
+    fn output_sinks_reject_targets_writing_differently_to_the_same_file() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("same.nq").display().to_string();
+        let a = target(&path);
+        let mut b = target(&format!("{}/./same.nq", directory.path().display()));
+        b.target.compression = Compression::Gzip;
+        let mut output = OutputSinks::new(Box::new(Collect::default()), |_| {
+            Ok(Box::new(Collect::default()) as Box<dyn QuadSink>)
+        });
+
+        output.insert(&quad("1", None), &[&a]).unwrap();
+        assert!(matches!(
+            output.insert(&quad("2", None), &[&b]),
+            Err(SinkError::ConflictingTargets(..))
+        ));
+    }
+}
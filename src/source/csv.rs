@@ -0,0 +1,193 @@
+use std::{
+    collections::HashSet,
+    io::{self, Read},
+    sync::Arc,
+};
+
+use ::csv::{ByteRecord, Reader, ReaderBuilder, StringRecord};
+
+use super::{pseudo, Record, Records, SourceError};
+use crate::model::{reference_field, RmlIteration};
+
+/// A CSV row, where references name columns of the header row
+pub struct CsvRecord {
+    headers: Arc<StringRecord>,
+    record: StringRecord,
+    nulls: Arc<HashSet<String>>,
+}
+
+impl RmlIteration for CsvRecord {
+    fn get_values_for(&self, reference: &str) -> Vec<String> {
+        if reference == pseudo::LINE {
+            return self
+                .record
+                .position()
+                .map(|position| vec![position.line().to_string()])
+                .unwrap_or_default();
+        }
+        self.headers
+            .iter()
+            .position(|header| header == reference)
+            .and_then(|index| self.record.get(index))
+            .map(|value| vec![value.to_string()])
+            .unwrap_or_default()
+    }
+
+    fn get_strings_for(&self, reference: &str) -> Vec<String> {
+        self.get_values_for(reference)
+    }
+
+    fn get_nulls(&self) -> &HashSet<String> {
+        &self.nulls
+    }
+}
+
+/// The rows of a CSV file with a header row. With a `projection`, only the
+/// columns it names are kept.
+pub fn records(
+    reader: Box<dyn Read + Send>,
+    projection: Option<&HashSet<String>>,
+    nulls: Arc<HashSet<String>>,
+) -> Result<Records, SourceError> {
+    let mut reader = ReaderBuilder::new().from_reader(reader);
+    let headers = reader.headers()?.clone();
+    Ok(into_records(reader, &headers, projection, nulls))
+}
+
+/// The header row of a CSV file, and the byte offset where its data rows
+/// start
+pub fn headers(reader: impl Read) -> Result<(StringRecord, u64), SourceError> {
+    let mut reader = ReaderBuilder::new().from_reader(reader);
+    let headers = reader.headers()?.clone();
+    Ok((headers, reader.position().byte()))
+}
+
+/// The rows of a slice of a CSV file without its header row, eg a partition
+pub fn rows(
+    reader: Box<dyn Read + Send>,
+    headers: &StringRecord,
+    projection: Option<&HashSet<String>>,
+    nulls: Arc<HashSet<String>>,
+) -> Records {
+    let reader = ReaderBuilder::new().has_headers(false).from_reader(reader);
+    into_records(reader, headers, projection, nulls)
+}
+
+fn into_records(
+    reader: Reader<Box<dyn Read + Send>>,
+    headers: &StringRecord,
+    projection: Option<&HashSet<String>>,
+    nulls: Arc<HashSet<String>>,
+) -> Records {
+    let Some(fields) = projection else {
+        let headers = Arc::new(headers.clone());
+        return Box::new(reader.into_records().map(move |record| {
+            Ok(Box::new(CsvRecord {
+                headers: headers.clone(),
+                record: record?,
+                nulls: nulls.clone(),
+            }) as Record)
+        }));
+    };
+
+    let columns: Vec<usize> = headers
+        .iter()
+        .enumerate()
+        .filter(|(_, header)| fields.contains(reference_field(header)))
+        .map(|(index, _)| index)
+        .collect();
+    let headers = Arc::new(columns.iter().map(|index| &headers[*index]).collect());
+    Box::new(ProjectedRows {
+        reader,
+        row: ByteRecord::new(),
+        headers,
+        columns,
+        nulls,
+    })
+}
+
+/// Rows read into a reused buffer, from which only the referenced columns
+/// are decoded and copied
+struct ProjectedRows {
+    reader: Reader<Box<dyn Read + Send>>,
+    row: ByteRecord,
+    headers: Arc<StringRecord>,
+    columns: Vec<usize>,
+    nulls: Arc<HashSet<String>>,
+}
+
+impl ProjectedRows {
+    fn project(&self) -> Result<StringRecord, SourceError> {
+        let mut record = StringRecord::with_capacity(0, self.columns.len());
+        for index in &self.columns {
+            let field = self.row.get(*index).unwrap_or_default();
+            let field = std::str::from_utf8(field)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            record.push_field(field);
+        }
+        record.set_position(self.row.position().cloned());
+        Ok(record)
+    }
+}
+
+impl Iterator for ProjectedRows {
+    type Item = Result<Record, SourceError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.reader.read_byte_record(&mut self.row) {
+            Ok(true) => Some(self.project().map(|record| {
+                Box::new(CsvRecord {
+                    headers: self.headers.clone(),
+                    record,
+                    nulls: self.nulls.clone(),
+                }) as Record
+            })),
+            Ok(false) => None,
+            Err(e) => Some(Err(e.into())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    const PEOPLE: &str = "name,bio\nAlice,\"one\ntwo\nthree\"\nBob,four\n\"Carol\",\"\"\n";
+
+    fn lines(projection: Option<&HashSet<String>>) -> Vec<(String, String)> {
+        let reader = Box::new(Cursor::new(PEOPLE.as_bytes().to_vec()));
+        records(reader, projection, Arc::default())
+            .unwrap()
+            .map(|record| {
+                let record = record.unwrap();
+                (
+                    record.get_values_for("name").concat(),
+                    record.get_values_for(pseudo::LINE).concat(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn numbers_lines_past_multi_line_fields() {
+        let expected = [("Alice", "2"), ("Bob", "5"), ("Carol", "6")]
+            .map(|(name, line)| (name.to_string(), line.to_string()));
+        assert_eq!(lines(None), expected);
+        assert_eq!(lines(Some(&HashSet::from(["name".to_string()]))), expected);
+    }
+
+    #[test]
+    fn keeps_only_projected_columns() {
+        let reader = Box::new(Cursor::new(PEOPLE.as_bytes().to_vec()));
+        let projection = HashSet::from(["bio".to_string()]);
+        let record = records(reader, Some(&projection), Arc::default())
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.get_values_for("bio"), ["one\ntwo\nthree"]);
+        assert!(record.get_values_for("name").is_empty());
+    }
+}
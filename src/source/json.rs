@@ -0,0 +1,816 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fmt,
+    io::{BufRead, BufReader, Read},
+    sync::{mpsc, Arc, RwLock},
+    thread,
+};
+
+use serde::de::{
+    DeserializeSeed, Deserializer, Error as _, IgnoredAny, MapAccess, SeqAccess, Visitor,
+};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use super::jsonpath::{JsonPath, Segment};
+use super::{pseudo, Record, Records, SourceError};
+use crate::model::RmlIteration;
+
+/// A JSON value selected by the logical source iterator, where references
+/// are JSONPath expressions rooted at the value
+pub struct JsonRecord {
+    value: Value,
+    nulls: Arc<HashSet<String>>,
+    line: Option<usize>,
+    paths: Arc<JsonPaths>,
+}
+
+impl JsonRecord {
+    pub fn new(value: Value, nulls: Arc<HashSet<String>>) -> Self {
+        Self {
+            value,
+            nulls,
+            line: None,
+            paths: Arc::default(),
+        }
+    }
+
+    /// A value read from the given line of a JSON Lines source
+    pub fn with_line(mut self, line: usize) -> Self {
+        self.line = Some(line);
+        self
+    }
+
+    /// Share parsed references with the other records of a source
+    pub fn with_paths(mut self, paths: Arc<JsonPaths>) -> Self {
+        self.paths = paths;
+        self
+    }
+}
+
+/// References parsed as JSONPath once, for all the records of a source.
+/// Invalid references are logged once and select nothing.
+#[derive(Default)]
+pub struct JsonPaths(RwLock<HashMap<String, Option<Arc<JsonPath>>>>);
+
+impl JsonPaths {
+    fn get(&self, reference: &str) -> Option<Arc<JsonPath>> {
+        if let Some(path) = self.0.read().unwrap().get(reference) {
+            return path.clone();
+        }
+        let path = match JsonPath::parse(reference) {
+            Ok(path) => Some(Arc::new(path)),
+            Err(e) => {
+                log::warn!("{}", e);
+                None
+            }
+        };
+        self.0
+            .write()
+            .unwrap()
+            .insert(reference.to_string(), path.clone());
+        path
+    }
+}
+
+impl RmlIteration for JsonRecord {
+    fn get_values_for(&self, reference: &str) -> Vec<String> {
+        if reference == pseudo::LINE {
+            return self.line.iter().map(|line| line.to_string()).collect();
+        }
+        match self.paths.get(reference) {
+            Some(path) => path
+                .select(&self.value)
+                .into_iter()
+                .filter_map(scalar_value)
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn get_strings_for(&self, reference: &str) -> Vec<String> {
+        self.get_values_for(reference)
+    }
+
+    fn get_nulls(&self) -> &HashSet<String> {
+        &self.nulls
+    }
+
+    fn get_iterations_for(&self, iterator: &str) -> Vec<Record> {
+        match self.paths.get(iterator) {
+            Some(path) => path
+                .select(&self.value)
+                .into_iter()
+                .map(|value| {
+                    let record = JsonRecord::new(value.clone(), self.nulls.clone())
+                        .with_paths(self.paths.clone());
+                    Box::new(record) as Record
+                })
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// The lexical form of a scalar JSON value. JSON `null`, arrays and objects
+/// have no lexical form and produce no value.
+pub fn scalar_value(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Null | Value::Array(_) | Value::Object(_) => None,
+    }
+}
+
+/// The parts of a JSON document that references can reach. Everything else
+/// is skipped while parsing, without being built into values.
+#[derive(Debug, Clone, Default)]
+pub struct JsonProjection {
+    /// The whole value is kept
+    all: bool,
+    /// Object members, by name
+    members: HashMap<String, JsonProjection>,
+    /// Every object member or array item
+    items: Option<Box<JsonProjection>>,
+}
+
+impl JsonProjection {
+    /// Keep the whole document
+    pub fn all() -> Self {
+        Self {
+            all: true,
+            ..Self::default()
+        }
+    }
+
+    /// Keep the values selected by `iterator`, and the `fields` below them.
+    /// Descendant steps keep whole subtrees, and so do fields with wildcard
+    /// or descendant steps, or that are not valid paths.
+    pub fn new(iterator: &JsonPath, fields: &HashSet<String>) -> Self {
+        let mut projection = Self::default();
+        // Iterations are kept even when none of their fields are referenced
+        projection.insert(iterator.segments().iter(), false);
+        for field in fields {
+            match JsonPath::parse(field) {
+                Ok(path) if !path.is_branching() => {
+                    projection.insert(iterator.segments().iter().chain(path.segments()), true)
+                }
+                _ => projection.insert(iterator.segments().iter(), true),
+            }
+        }
+        projection
+    }
+
+    fn insert<'s>(&mut self, mut segments: impl Iterator<Item = &'s Segment>, leaf: bool) {
+        if self.all {
+            return;
+        }
+        match segments.next() {
+            None => self.all = leaf,
+            Some(Segment::Child(name)) => self
+                .members
+                .entry(name.clone())
+                .or_default()
+                .insert(segments, leaf),
+            Some(Segment::Wildcard | Segment::Index(_)) => self
+                .items
+                .get_or_insert_with(Box::default)
+                .insert(segments, leaf),
+            // Filters may test any part of the items they select
+            Some(Segment::Descendant(_) | Segment::Filter(_)) => self.all = true,
+        }
+    }
+
+    fn merge(&mut self, other: &JsonProjection) {
+        self.all |= other.all;
+        for (name, projection) in &other.members {
+            self.members
+                .entry(name.clone())
+                .or_default()
+                .merge(projection);
+        }
+        if let Some(items) = &other.items {
+            self.items.get_or_insert_with(Box::default).merge(items);
+        }
+    }
+
+    /// The projection of an object member, if any of it is kept
+    fn member(&self, name: &str) -> Option<Cow<'_, JsonProjection>> {
+        match (self.members.get(name), &self.items) {
+            (Some(member), None) => Some(Cow::Borrowed(member)),
+            (None, Some(items)) => Some(Cow::Borrowed(items)),
+            (Some(member), Some(items)) => {
+                let mut merged = member.clone();
+                merged.merge(items);
+                Some(Cow::Owned(merged))
+            }
+            (None, None) => None,
+        }
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for &JsonProjection {
+    type Value = Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        if self.all {
+            Value::deserialize(deserializer)
+        } else {
+            deserializer.deserialize_any(self)
+        }
+    }
+}
+
+impl<'de> Visitor<'de> for &JsonProjection {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSON value")
+    }
+
+    fn visit_bool<E>(self, value: bool) -> Result<Value, E> {
+        Ok(Value::Bool(value))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<Value, E> {
+        Ok(Value::from(value))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<Value, E> {
+        Ok(Value::from(value))
+    }
+
+    fn visit_f64<E>(self, value: f64) -> Result<Value, E> {
+        Ok(Value::from(value))
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Value, E> {
+        Ok(Value::from(value))
+    }
+
+    fn visit_string<E>(self, value: String) -> Result<Value, E> {
+        Ok(Value::String(value))
+    }
+
+    fn visit_unit<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut items = Vec::new();
+        match &self.items {
+            Some(projection) => {
+                while let Some(item) = seq.next_element_seed(projection.as_ref())? {
+                    items.push(item);
+                }
+            }
+            None => while seq.next_element::<IgnoredAny>()?.is_some() {},
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut members = Map::new();
+        while let Some(name) = map.next_key::<String>()? {
+            match self.member(&name) {
+                Some(projection) => {
+                    let value = map.next_value_seed(projection.as_ref())?;
+                    members.insert(name, value);
+                }
+                None => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(Value::Object(members))
+    }
+}
+
+/// Records parsed ahead by a streaming reader before it blocks
+const STREAM_BOUND: usize = 1024;
+
+/// The values selected by `iterator` in a JSON document. With a
+/// `projection`, unreferenced parts of the document are skipped.
+pub fn records(
+    reader: Box<dyn Read + Send>,
+    iterator: Option<&str>,
+    projection: Option<&HashSet<String>>,
+    nulls: Arc<HashSet<String>>,
+) -> Result<Records, SourceError> {
+    let iterator = JsonPath::parse(iterator.unwrap_or("$"))?;
+    if is_streamable(&iterator) {
+        let projection = match projection {
+            Some(fields) => JsonProjection::new(&JsonPath::root(), fields),
+            None => JsonProjection::all(),
+        };
+        return Ok(stream(reader, iterator, projection, nulls));
+    }
+
+    let projection = match projection {
+        Some(fields) => JsonProjection::new(&iterator, fields),
+        None => JsonProjection::all(),
+    };
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let document = (&projection).deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(select(&document, &iterator, nulls))
+}
+
+/// The values selected by `iterator` in a parsed document
+pub fn select(document: &Value, iterator: &JsonPath, nulls: Arc<HashSet<String>>) -> Records {
+    let values: Vec<Value> = iterator.select(document).into_iter().cloned().collect();
+    let paths = Arc::new(JsonPaths::default());
+    Box::new(values.into_iter().map(move |value| {
+        let record = JsonRecord::new(value, nulls.clone()).with_paths(paths.clone());
+        Ok(Box::new(record) as Record)
+    }))
+}
+
+/// Iterators made of child, wildcard and non-negative index steps select
+/// values in document order, so they can be evaluated while parsing
+fn is_streamable(iterator: &JsonPath) -> bool {
+    iterator.segments().iter().all(|segment| match segment {
+        Segment::Child(_) | Segment::Wildcard => true,
+        Segment::Index(index) => *index >= 0,
+        Segment::Descendant(_) | Segment::Filter(_) => false,
+    })
+}
+
+/// Parse the document on its own thread, sending each selected value as a
+/// record as soon as it is parsed. Only the value being parsed is held in
+/// memory, and parsing stops once the records are dropped.
+fn stream(
+    reader: Box<dyn Read + Send>,
+    iterator: JsonPath,
+    projection: JsonProjection,
+    nulls: Arc<HashSet<String>>,
+) -> Records {
+    let (sender, receiver) = mpsc::sync_channel(STREAM_BOUND);
+    thread::spawn(move || {
+        let paths = Arc::new(JsonPaths::default());
+        let mut emit = |value: Value| {
+            let record = JsonRecord::new(value, nulls.clone()).with_paths(paths.clone());
+            let record = Box::new(record) as Record;
+            sender.send(Ok(record)).is_ok()
+        };
+        let mut deserializer = serde_json::Deserializer::from_reader(reader);
+        let selected = Select {
+            segments: iterator.segments(),
+            projection: &projection,
+            emit: &mut emit,
+        }
+        .deserialize(&mut deserializer)
+        .and_then(|()| deserializer.end());
+        if let Err(e) = selected {
+            // Fails silently when the records were dropped
+            let _ = sender.send(Err(e.into()));
+        }
+    });
+    Box::new(receiver.into_iter())
+}
+
+/// Follows the remaining steps of a streamable iterator, skipping every
+/// value they do not lead to
+struct Select<'s, F> {
+    segments: &'s [Segment],
+    projection: &'s JsonProjection,
+    emit: &'s mut F,
+}
+
+impl<'s, F: FnMut(Value) -> bool> Select<'s, F> {
+    fn next<'n>(&'n mut self, segments: &'s [Segment]) -> Select<'n, F>
+    where
+        's: 'n,
+    {
+        Select {
+            segments,
+            projection: self.projection,
+            emit: &mut *self.emit,
+        }
+    }
+}
+
+impl<'de, F: FnMut(Value) -> bool> DeserializeSeed<'de> for Select<'_, F> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        if self.segments.is_empty() {
+            let value = self.projection.deserialize(deserializer)?;
+            return if (self.emit)(value) {
+                Ok(())
+            } else {
+                Err(D::Error::custom("records dropped"))
+            };
+        }
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, F: FnMut(Value) -> bool> Visitor<'de> for Select<'_, F> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSON value")
+    }
+
+    // Scalars have no children to select
+    fn visit_bool<E>(self, _: bool) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_u64<E>(self, _: u64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_str<E>(self, _: &str) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_unit<E>(self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<(), A::Error> {
+        let (segment, rest) = (&self.segments[0], &self.segments[1..]);
+        let mut position = 0;
+        loop {
+            let selected = match segment {
+                Segment::Wildcard => true,
+                Segment::Index(index) => *index == position,
+                _ => false,
+            };
+            let more = if selected {
+                seq.next_element_seed(self.next(rest))?.is_some()
+            } else {
+                seq.next_element::<IgnoredAny>()?.is_some()
+            };
+            if !more {
+                return Ok(());
+            }
+            position += 1;
+        }
+    }
+
+    fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<(), A::Error> {
+        let (segment, rest) = (&self.segments[0], &self.segments[1..]);
+        while let Some(name) = map.next_key::<Cow<str>>()? {
+            let selected = match segment {
+                Segment::Wildcard => true,
+                Segment::Child(child) => *child == name,
+                _ => false,
+            };
+            if selected {
+                map.next_value_seed(self.next(rest))?;
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// JSON Lines, where every non-empty line is a document. The `iterator`
+/// selects the records of each line, and references are relative to them.
+pub fn lines(
+    reader: Box<dyn Read + Send>,
+    iterator: Option<&str>,
+    projection: Option<&HashSet<String>>,
+    nulls: Arc<HashSet<String>>,
+) -> Result<Records, SourceError> {
+    let iterator = JsonPath::parse(iterator.unwrap_or("$"))?;
+    let projection = match projection {
+        Some(fields) => JsonProjection::new(&iterator, fields),
+        None => JsonProjection::all(),
+    };
+    let parse = move |line: &str| -> Result<Vec<Value>, SourceError> {
+        let mut deserializer = serde_json::Deserializer::from_str(line);
+        let value = (&projection).deserialize(&mut deserializer)?;
+        deserializer.end()?;
+        if iterator.segments().is_empty() {
+            return Ok(vec![value]);
+        }
+        Ok(iterator.select(&value).into_iter().cloned().collect())
+    };
+
+    let paths = Arc::new(JsonPaths::default());
+    Ok(Box::new(
+        BufReader::new(reader)
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !matches!(line, Ok(line) if line.trim().is_empty()))
+            .flat_map(move |(index, line)| {
+                let records: Vec<Result<Record, SourceError>> = match line
+                    .map_err(SourceError::from)
+                    .and_then(|line| parse(&line))
+                {
+                    Ok(values) => values
+                        .into_iter()
+                        .map(|value| {
+                            let record = JsonRecord::new(value, nulls.clone())
+                                .with_line(index + 1)
+                                .with_paths(paths.clone());
+                            Ok(Box::new(record) as Record)
+                        })
+                        .collect(),
+                    Err(e) => vec![Err(e)],
+                };
+                records
+            }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::model::reference_field;
+
+    const PEOPLE: &str = r#"{"people": [
+        {"name": "Ann", "age": 30, "address": {"city": "Ghent", "name": "Home"}},
+        {"name": "Bob", "age": 40, "address": {"city": "Paris"}}
+    ]}"#;
+
+    /// The values of `reference` in each record, projected on that reference
+    /// alone as the processor would
+    fn projected_values(
+        read: impl Fn(Box<dyn Read + Send>, &HashSet<String>) -> Result<Records, SourceError>,
+        document: &str,
+        reference: &str,
+    ) -> Vec<Vec<String>> {
+        let fields = HashSet::from([reference_field(reference).to_string()]);
+        let reader = Box::new(Cursor::new(document.to_string()));
+        read(reader, &fields)
+            .unwrap()
+            .map(|record| {
+                let mut values = record.unwrap().get_values_for(reference);
+                values.sort();
+                values
+            })
+            .collect()
+    }
+
+    fn document_values(iterator: &str, reference: &str) -> Vec<Vec<String>> {
+        let read = |reader, fields: &HashSet<String>| {
+            records(reader, Some(iterator), Some(fields), Arc::default())
+        };
+        projected_values(read, PEOPLE, reference)
+    }
+
+    #[test]
+    fn streams_the_values_a_parsed_document_selects() {
+        let document = r#"{
+            "a": [{"b": 1, "c": [true, null]}, {"b": "two"}, 3, [4, 5]],
+            "d": {"b": {"e": "f"}, "\u0068": 6},
+            "g": null
+        }"#;
+        let parsed: Value = serde_json::from_str(document).unwrap();
+        let iterators = [
+            "$",
+            "$.a",
+            "$.a[*]",
+            "$.a[1]",
+            "$.a[9]",
+            "$.a[*].b",
+            "$.a[*].c[*]",
+            "$.a[3][0]",
+            "$.*",
+            "$.*.b",
+            "$.*[*]",
+            "$.d.b.e",
+            "$.d.h",
+            "$.g",
+            "$.g[*]",
+            "$.missing.b",
+        ];
+        for iterator in iterators {
+            let path = JsonPath::parse(iterator).unwrap();
+            assert!(is_streamable(&path), "{iterator}");
+            let reader = Box::new(Cursor::new(document.to_string()));
+            let streamed = stream(reader, path.clone(), JsonProjection::all(), Arc::default());
+            let selected = select(&parsed, &path, Arc::default());
+            let streamed = streamed
+                .map(|record| record.unwrap().get_values_for("$"))
+                .collect::<Vec<_>>();
+            let selected = selected
+                .map(|record| record.unwrap().get_values_for("$"))
+                .collect::<Vec<_>>();
+            assert_eq!(streamed, selected, "{iterator}");
+        }
+    }
+
+    #[test]
+    fn streams_containers_and_skips_other_values() {
+        let document = r#"{"items": [{"id": 1, "tags": ["x"]}, "skipped", {"id": 2}]}"#;
+        let path = JsonPath::parse("$.items[*]").unwrap();
+        let reader = Box::new(Cursor::new(document.to_string()));
+        let records: Vec<_> = stream(reader, path, JsonProjection::all(), Arc::default())
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].get_values_for("$.tags[0]"), ["x"]);
+        assert_eq!(records[1].get_values_for("$"), ["skipped"]);
+        assert_eq!(records[2].get_values_for("id"), ["2"]);
+    }
+
+    #[test]
+    fn streams_records_before_a_syntax_error() {
+        let document = r#"{"items": [{"id": 1}, {"id": 2}, {"id": "#;
+        let path = JsonPath::parse("$.items[*]").unwrap();
+        let reader = Box::new(Cursor::new(document.to_string()));
+        let records: Vec<_> = stream(reader, path, JsonProjection::all(), Arc::default()).collect();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[1].as_ref().unwrap().get_values_for("id"), ["2"]);
+        assert!(matches!(records[2], Err(SourceError::Json(_))));
+    }
+
+    /// Counts the bytes read through it
+    struct Counting(Cursor<String>, Arc<AtomicUsize>);
+
+    impl Read for Counting {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let read = self.0.read(buf)?;
+            self.1.fetch_add(read, Ordering::SeqCst);
+            Ok(read)
+        }
+    }
+
+    #[test]
+    fn stops_streaming_once_records_are_dropped() {
+        // Many more records than the channel holds, so the reader blocks
+        // until the records are dropped
+        let items = vec![r#"{"id": 1}"#; 100 * STREAM_BOUND].join(",");
+        let document = format!(r#"{{"items": [{items}]}}"#);
+        let length = document.len();
+        let read = Arc::new(AtomicUsize::new(0));
+        let reader = Box::new(Counting(Cursor::new(document), read.clone()));
+        let path = JsonPath::parse("$.items[*]").unwrap();
+
+        let mut records = stream(reader, path, JsonProjection::all(), Arc::default());
+        assert_eq!(records.next().unwrap().unwrap().get_values_for("id"), ["1"]);
+        drop(records);
+
+        // The reader is dropped with the parsing thread
+        for _ in 0..500 {
+            if Arc::strong_count(&read) == 1 {
+                break;
+            }
+            thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(Arc::strong_count(&read), 1);
+        assert!(read.load(Ordering::SeqCst) < length / 10);
+    }
+
+    fn json_lines(document: &str, iterator: Option<&str>) -> Vec<Result<Record, SourceError>> {
+        let reader = Box::new(Cursor::new(document.to_string()));
+        lines(reader, iterator, None, Arc::default())
+            .unwrap()
+            .collect()
+    }
+
+    #[test]
+    fn reads_a_record_per_line() {
+        let records = json_lines("{\"id\": 1}\n\n  \n{\"id\": 2}", None);
+        assert_eq!(records.len(), 2);
+        let record = records[1].as_ref().unwrap();
+        assert_eq!(record.get_values_for("$.id"), ["2"]);
+        // Blank lines still count
+        assert_eq!(record.get_values_for(pseudo::LINE), ["4"]);
+    }
+
+    #[test]
+    fn iterates_within_each_line() {
+        let document = concat!(
+            r#"{"order": 1, "items": [{"sku": "a"}, {"sku": "b"}]}"#,
+            "\n",
+            r#"{"order": 2, "items": []}"#,
+            "\n",
+            r#"{"order": 3, "items": [{"sku": "c"}]}"#,
+        );
+        let skus: Vec<_> = json_lines(document, Some("$.items[*]"))
+            .into_iter()
+            .map(|record| {
+                let record = record.unwrap();
+                let sku = record.get_values_for("sku").join(",");
+                format!(
+                    "{sku} on line {}",
+                    record.get_values_for(pseudo::LINE).join(",")
+                )
+            })
+            .collect();
+        assert_eq!(skus, ["a on line 1", "b on line 1", "c on line 3"]);
+    }
+
+    #[test]
+    fn reports_invalid_lines_and_reads_on() {
+        let records = json_lines("{\"id\": 1}\n{\"id\": \n{\"id\": 3}\n", None);
+        assert_eq!(records.len(), 3);
+        assert!(matches!(records[1], Err(SourceError::Json(_))));
+        assert_eq!(records[2].as_ref().unwrap().get_values_for("id"), ["3"]);
+    }
+
+    #[test]
+    fn rejects_invalid_iterators() {
+        let reader = Box::new(Cursor::new(String::new()));
+        assert!(lines(reader, Some("$.items[1:2]"), None, Arc::default()).is_err());
+    }
+
+    #[test]
+    fn evaluates_filter_iterators_on_the_parsed_document() {
+        assert_eq!(
+            document_values("$.people[?(@.age > 35)]", "name"),
+            [["Bob"]]
+        );
+        assert_eq!(
+            document_values("$.people[?(@.address.name)]", "address.city"),
+            [["Ghent"]]
+        );
+        assert_eq!(
+            document_values("$.people[*]", "$.address[?(@ == 'Paris')]"),
+            [vec![], vec!["Paris"]]
+        );
+    }
+
+    #[test]
+    fn parses_each_reference_once() {
+        let paths = JsonPaths::default();
+        let path = paths.get("$.name").unwrap();
+        assert!(Arc::ptr_eq(&path, &paths.get("$.name").unwrap()));
+        assert!(paths.get("$.name[").is_none());
+        assert_eq!(paths.0.read().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn keeps_only_referenced_fields() {
+        let fields = HashSet::from(["name".to_string()]);
+        let reader = Box::new(Cursor::new(PEOPLE.to_string()));
+        let records: Vec<_> = records(reader, Some("$.people[*]"), Some(&fields), Arc::default())
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(records[0].get_values_for("$.name"), ["Ann"]);
+        assert!(records[0].get_values_for("$.age").is_empty());
+    }
+
+    #[test]
+    fn keeps_fields_reached_by_descendant_steps() {
+        // `$..name` must not be read as the top-level `name` only
+        assert_eq!(reference_field("$..name"), "$..name");
+        // Streamed and parsed iterators build their projections differently
+        for iterator in ["$.people[*]", "$..people[*]"] {
+            assert_eq!(
+                document_values(iterator, "$..name"),
+                [vec!["Ann", "Home"], vec!["Bob"]]
+            );
+            assert_eq!(document_values(iterator, "$..city"), [["Ghent"], ["Paris"]]);
+        }
+    }
+
+    #[test]
+    fn keeps_fields_reached_by_wildcard_steps() {
+        assert_eq!(reference_field("$.*"), "$.*");
+        assert_eq!(reference_field("$.address.*"), "$.address.*");
+        assert_eq!(reference_field("$.address.city"), "address.city");
+        for iterator in ["$.people[*]", "$..people[*]"] {
+            assert_eq!(
+                document_values(iterator, "$.*"),
+                [vec!["30", "Ann"], vec!["40", "Bob"]]
+            );
+            assert_eq!(
+                document_values(iterator, "$.address.*"),
+                [vec!["Ghent", "Home"], vec!["Paris"]]
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_branching_fields_of_json_lines() {
+        let document = concat!(
+            r#"{"id": 1, "tags": {"a": "x", "b": "y"}}"#,
+            "\n",
+            r#"{"id": 2, "nested": {"tags": {"c": "z"}}}"#,
+            "\n"
+        );
+        let read =
+            |reader, fields: &HashSet<String>| lines(reader, None, Some(fields), Arc::default());
+        assert_eq!(
+            projected_values(read, document, "$..tags.*"),
+            [vec!["x", "y"], vec!["z"]]
+        );
+        assert_eq!(
+            projected_values(read, document, "$.tags[*]"),
+            [vec!["x", "y"], vec![]]
+        );
+    }
+}
@@ -0,0 +1,433 @@
+use std::cmp::Ordering;
+
+use serde_json::Value;
+
+use super::SourceError;
+
+/// A step of a JSONPath expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    /// `.name` or `['name']`
+    Child(String),
+    /// `[0]`, negative indices count from the end
+    Index(i64),
+    /// `.*` or `[*]`
+    Wildcard,
+    /// `..name`, or `..*` when no name is given
+    Descendant(Option<String>),
+    /// `[?(...)]`, the items or members for which the filter holds
+    Filter(Filter),
+}
+
+/// A filter of comparisons between `@` paths and literals, combined with
+/// `&&` and `||`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    /// `@.name`, holding when the path selects a value
+    Exists(JsonPath),
+    /// `@.name < 10`, holding when any selected value compares as given
+    Compare(JsonPath, Comparison, Value),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    /// Numbers compare as numbers and strings as strings. Other values are
+    /// only equal or not, and values of different types are never equal.
+    fn holds(self, value: &Value, literal: &Value) -> bool {
+        let ordering = match (value, literal) {
+            (Value::Number(a), Value::Number(b)) => a
+                .as_f64()
+                .zip(b.as_f64())
+                .and_then(|(a, b)| a.partial_cmp(&b)),
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            _ => None,
+        };
+        match self {
+            Comparison::Equal => value == literal || ordering.is_some_and(Ordering::is_eq),
+            Comparison::NotEqual => !Comparison::Equal.holds(value, literal),
+            Comparison::Less => ordering.is_some_and(Ordering::is_lt),
+            Comparison::LessOrEqual => ordering.is_some_and(Ordering::is_le),
+            Comparison::Greater => ordering.is_some_and(Ordering::is_gt),
+            Comparison::GreaterOrEqual => ordering.is_some_and(Ordering::is_ge),
+        }
+    }
+}
+
+/// The subset of JSONPath made of child, index, wildcard, descendant and
+/// filter steps. Slices and filter functions are rejected when parsing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPath {
+    segments: Vec<Segment>,
+}
+
+impl JsonPath {
+    pub fn parse(path: &str) -> Result<Self, SourceError> {
+        let invalid = || SourceError::InvalidPath(path.to_string());
+        let mut segments = Vec::new();
+        let chars: Vec<char> = path.trim().chars().collect();
+        let mut pos = 0;
+
+        if chars.first() == Some(&'$') {
+            pos += 1;
+        } else if chars.first().is_some_and(|c| *c != '.' && *c != '[') {
+            // A bare name, eg `Name`, is shorthand for `$.Name`
+            let name = take_name(&chars, &mut pos);
+            segments.push(Segment::Child(name));
+        }
+
+        while pos < chars.len() {
+            match chars[pos] {
+                '.' if chars.get(pos + 1) == Some(&'.') => {
+                    pos += 2;
+                    match chars.get(pos) {
+                        Some('*') => {
+                            pos += 1;
+                            segments.push(Segment::Descendant(None));
+                        }
+                        Some(_) => {
+                            let name = take_name(&chars, &mut pos);
+                            if name.is_empty() {
+                                return Err(invalid());
+                            }
+                            segments.push(Segment::Descendant(Some(name)));
+                        }
+                        None => return Err(invalid()),
+                    }
+                }
+                '.' => {
+                    pos += 1;
+                    match chars.get(pos) {
+                        // `$.['name']` is tolerated as `$['name']`
+                        Some('[') => {}
+                        Some('*') => {
+                            pos += 1;
+                            segments.push(Segment::Wildcard);
+                        }
+                        Some(_) => {
+                            let name = take_name(&chars, &mut pos);
+                            if name.is_empty() {
+                                return Err(invalid());
+                            }
+                            segments.push(Segment::Child(name));
+                        }
+                        None => return Err(invalid()),
+                    }
+                }
+                '[' => {
+                    pos += 1;
+                    let selector = take_bracket(&chars, &mut pos).ok_or_else(invalid)?;
+                    segments.push(parse_selector(&selector).ok_or_else(invalid)?);
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(Self { segments })
+    }
+
+    /// The path `$`, selecting the whole document
+    pub fn root() -> Self {
+        Self {
+            segments: Vec::new(),
+        }
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// Whether a wildcard, descendant or filter step can select several
+    /// values, rather than follow a single branch
+    pub fn is_branching(&self) -> bool {
+        self.segments.iter().any(|segment| {
+            matches!(
+                segment,
+                Segment::Wildcard | Segment::Descendant(_) | Segment::Filter(_)
+            )
+        })
+    }
+
+    /// Evaluate the path with `value` as the root `$`
+    pub fn select<'a>(&self, value: &'a Value) -> Vec<&'a Value> {
+        let mut current = vec![value];
+        for segment in &self.segments {
+            current = current
+                .into_iter()
+                .flat_map(|value| apply(segment, value))
+                .collect();
+        }
+        current
+    }
+}
+
+fn apply<'a>(segment: &Segment, value: &'a Value) -> Vec<&'a Value> {
+    match segment {
+        Segment::Child(name) => value.get(name.as_str()).into_iter().collect(),
+        Segment::Index(index) => match value {
+            Value::Array(items) => {
+                let index = if *index < 0 {
+                    items.len() as i64 + index
+                } else {
+                    *index
+                };
+                usize::try_from(index)
+                    .ok()
+                    .and_then(|index| items.get(index))
+                    .into_iter()
+                    .collect()
+            }
+            _ => Vec::new(),
+        },
+        Segment::Wildcard => children(value),
+        Segment::Descendant(name) => {
+            let mut nodes = Vec::new();
+            self_and_descendants(value, &mut nodes);
+            nodes
+                .into_iter()
+                .flat_map(|node| match name {
+                    Some(name) => node.get(name.as_str()).into_iter().collect(),
+                    None => children(node),
+                })
+                .collect()
+        }
+        Segment::Filter(filter) => children(value)
+            .into_iter()
+            .filter(|child| filter.holds(child))
+            .collect(),
+    }
+}
+
+impl Filter {
+    fn holds(&self, value: &Value) -> bool {
+        match self {
+            Filter::Exists(path) => !path.select(value).is_empty(),
+            Filter::Compare(path, comparison, literal) => path
+                .select(value)
+                .into_iter()
+                .any(|selected| comparison.holds(selected, literal)),
+            Filter::And(left, right) => left.holds(value) && right.holds(value),
+            Filter::Or(left, right) => left.holds(value) || right.holds(value),
+        }
+    }
+
+    /// Parse a filter without its `?(` and `)`
+    fn parse(filter: &str) -> Option<Self> {
+        let filter = filter.trim();
+        if let Some((left, right)) = split_outside(filter, "||") {
+            return Some(Filter::Or(
+                Box::new(Self::parse(left)?),
+                Box::new(Self::parse(right)?),
+            ));
+        }
+        if let Some((left, right)) = split_outside(filter, "&&") {
+            return Some(Filter::And(
+                Box::new(Self::parse(left)?),
+                Box::new(Self::parse(right)?),
+            ));
+        }
+        if let Some(inner) = filter.strip_prefix('(').and_then(|f| f.strip_suffix(')')) {
+            return Self::parse(inner);
+        }
+
+        // Longer operators first, so that `<=` is not taken for `<`
+        for (operator, comparison) in [
+            ("==", Comparison::Equal),
+            ("!=", Comparison::NotEqual),
+            ("<=", Comparison::LessOrEqual),
+            (">=", Comparison::GreaterOrEqual),
+            ("<", Comparison::Less),
+            (">", Comparison::Greater),
+        ] {
+            if let Some((path, literal)) = split_outside(filter, operator) {
+                return Some(Filter::Compare(
+                    relative_path(path)?,
+                    comparison,
+                    parse_literal(literal)?,
+                ));
+            }
+        }
+        relative_path(filter).map(Filter::Exists)
+    }
+}
+
+/// A path from the current value `@`
+fn relative_path(path: &str) -> Option<JsonPath> {
+    let rest = path.trim().strip_prefix('@')?;
+    if !rest.is_empty() && !rest.starts_with(['.', '[']) {
+        return None;
+    }
+    JsonPath::parse(&format!("${rest}")).ok()
+}
+
+/// A string in single or double quotes, or a JSON number, boolean or null
+fn parse_literal(literal: &str) -> Option<Value> {
+    let literal = literal.trim();
+    if let Some(string) = literal
+        .strip_prefix('\'')
+        .and_then(|l| l.strip_suffix('\''))
+    {
+        return Some(Value::String(string.to_string()));
+    }
+    match serde_json::from_str(literal).ok()? {
+        Value::Array(_) | Value::Object(_) => None,
+        value => Some(value),
+    }
+}
+
+/// Split at the first `separator` outside quotes and parentheses
+fn split_outside<'a>(text: &'a str, separator: &str) -> Option<(&'a str, &'a str)> {
+    let mut depth = 0;
+    let mut quote = None;
+    for (index, c) in text.char_indices() {
+        match (c, quote) {
+            ('\'' | '"', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            ('(' | '[', None) => depth += 1,
+            (')' | ']', None) => depth -= 1,
+            (_, None) if depth == 0 && text[index..].starts_with(separator) => {
+                return Some((&text[..index], &text[index + separator.len()..]));
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn children(value: &Value) -> Vec<&Value> {
+    match value {
+        Value::Array(items) => items.iter().collect(),
+        Value::Object(fields) => fields.values().collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn self_and_descendants<'a>(value: &'a Value, nodes: &mut Vec<&'a Value>) {
+    nodes.push(value);
+    for child in children(value) {
+        self_and_descendants(child, nodes);
+    }
+}
+
+fn take_name(chars: &[char], pos: &mut usize) -> String {
+    let start = *pos;
+    while *pos < chars.len() && chars[*pos] != '.' && chars[*pos] != '[' {
+        *pos += 1;
+    }
+    chars[start..*pos].iter().collect()
+}
+
+/// Consume a bracketed selector up to its closing `]`, ignoring brackets
+/// inside quoted names and nested in filters
+fn take_bracket(chars: &[char], pos: &mut usize) -> Option<String> {
+    let mut selector = String::new();
+    let mut quote = None;
+    let mut depth = 0;
+    while let Some(&c) = chars.get(*pos) {
+        *pos += 1;
+        match (c, quote) {
+            (']', None) if depth == 0 => return Some(selector),
+            ('[', None) => depth += 1,
+            (']', None) => depth -= 1,
+            ('\'' | '"', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            _ => {}
+        }
+        selector.push(c);
+    }
+    None
+}
+
+fn parse_selector(selector: &str) -> Option<Segment> {
+    let selector = selector.trim();
+    if selector == "*" {
+        return Some(Segment::Wildcard);
+    }
+    if let Some(filter) = selector
+        .strip_prefix('?')
+        .map(str::trim_start)
+        .and_then(|s| s.strip_prefix('('))
+        .and_then(|s| s.strip_suffix(')'))
+    {
+        return Filter::parse(filter).map(Segment::Filter);
+    }
+    for quote in ['\'', '"'] {
+        if let Some(name) = selector
+            .strip_prefix(quote)
+            .and_then(|s| s.strip_suffix(quote))
+        {
+            return Some(Segment::Child(name.to_string()));
+        }
+    }
+    selector.parse().ok().map(Segment::Index)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn select(path: &str, value: &Value) -> Vec<Value> {
+        JsonPath::parse(path)
+            .unwrap()
+            .select(value)
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn filters_items_by_comparison() {
+        let items = json!({"items": [
+            {"id": 1, "price": 8, "name": "a"},
+            {"id": 2, "price": 12.5, "name": "b", "tags": ["x]"]},
+            {"id": 3, "price": "12", "name": "c"},
+            {"id": 4, "name": null}
+        ]});
+        let ids =
+            |filter: &str| -> Vec<Value> { select(&format!("$.items[?({filter})].id"), &items) };
+        assert_eq!(ids("@.price < 10"), [json!(1)]);
+        assert_eq!(ids("@.price >= 8"), [json!(1), json!(2)]);
+        assert_eq!(ids("@.price == '12'"), [json!(3)]);
+        assert_eq!(ids("@.name != 'a'"), [json!(2), json!(3), json!(4)]);
+        assert_eq!(ids("@.name == null"), [json!(4)]);
+        assert_eq!(ids("@.price"), [json!(1), json!(2), json!(3)]);
+        assert_eq!(ids("@.tags[0] == 'x]'"), [json!(2)]);
+        assert_eq!(ids("@.price > 10 && @.name == 'b'"), [json!(2)]);
+        assert_eq!(
+            ids("@.id == 1 || (@.id > 2 && @.price)"),
+            [json!(1), json!(3)]
+        );
+    }
+
+    #[test]
+    fn filters_members_and_scalars() {
+        let value = json!({"a": 1, "b": 2, "c": [1, 2, 3]});
+        assert_eq!(select("$[?(@ > 1)]", &value), [json!(2)]);
+        assert_eq!(select("$.c[?(@ != 2)]", &value), [json!(1), json!(3)]);
+    }
+
+    #[test]
+    fn rejects_invalid_filters() {
+        for path in [
+            "$[?(@.a <)]",
+            "$[?(a == 1)]",
+            "$[?(@.a == [1])]",
+            "$[?(length(@) > 1)]",
+            "$[1:2]",
+        ] {
+            assert!(JsonPath::parse(path).is_err(), "{path}");
+        }
+    }
+}
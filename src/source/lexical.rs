@@ -0,0 +1,57 @@
+use std::fmt;
+
+/// The `xsd:double` lexical form, which spells infinities `INF`
+pub fn double_value<F: fmt::Display + Into<f64> + Copy>(value: F) -> String {
+    let float: f64 = value.into();
+    if float == f64::INFINITY {
+        "INF".to_string()
+    } else if float == f64::NEG_INFINITY {
+        "-INF".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// The `xsd:date` of a number of days since the Unix epoch
+pub fn date_value(days: i64) -> String {
+    // Civil from days, see <https://howardhinnant.github.io/date_algorithms.html>
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// The `xsd:dateTime` of a timestamp with `digits` fractional second digits.
+/// Timestamps of typed sources need not be in UTC, so no timezone is given.
+pub fn date_time_value(timestamp: i64, digits: u32) -> String {
+    let per_second = 10_i64.pow(digits);
+    let (seconds, fraction) = (
+        timestamp.div_euclid(per_second),
+        timestamp.rem_euclid(per_second),
+    );
+    let (days, time) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400));
+    let mut value = format!(
+        "{}T{:02}:{:02}:{:02}",
+        date_value(days),
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    );
+    if fraction != 0 {
+        let fraction = format!("{fraction:0width$}", width = digits as usize);
+        value.push('.');
+        value.push_str(fraction.trim_end_matches('0'));
+    }
+    value
+}
@@ -0,0 +1,604 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{self, BufReader, Cursor, Read, Seek},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use bzip2::read::MultiBzDecoder;
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use flate2::read::MultiGzDecoder;
+use oxrdfio::RdfFormat;
+use thiserror::Error;
+use xz2::read::XzDecoder;
+
+use crate::model::{
+    Compression, Encoding, FileLogicalSource, LogicalSourceType, RmlIteration, SourceDescription,
+};
+use crate::vocab::rml;
+use web::Transport;
+
+pub mod csv;
+pub mod database;
+pub mod json;
+pub mod jsonpath;
+pub mod lexical;
+pub mod parquet;
+pub mod partition;
+pub mod pseudo;
+pub mod sparql;
+pub mod spreadsheet;
+pub mod sql;
+pub mod toml;
+pub mod view;
+pub mod web;
+pub mod xml;
+pub mod yaml;
+
+#[derive(Error, Debug)]
+pub enum SourceError {
+    #[error("Could not open source `{}`", .0.display())]
+    Open(PathBuf, #[source] io::Error),
+    #[error("Could not read source")]
+    Read(#[from] io::Error),
+    #[error("Archive `{}` contains no file", .0.display())]
+    EmptyArchive(PathBuf),
+    #[error("Invalid zip archive")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("Invalid CSV")]
+    Csv(#[from] ::csv::Error),
+    #[error("Invalid JSON")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid XML")]
+    Xml(#[from] roxmltree::Error),
+    #[error("Invalid YAML")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("Invalid TOML")]
+    Toml(#[from] ::toml::de::Error),
+    #[error("Invalid RDF")]
+    Rdf(#[from] oxrdfio::RdfParseError),
+    #[error("Invalid SPARQL query")]
+    Sparql(#[from] spargebra::SparqlSyntaxError),
+    #[error("SPARQL query evaluation failed")]
+    QueryEvaluation(#[from] spareval::QueryEvaluationError),
+    #[error("Only SPARQL SELECT queries can iterate a source: `{0}`")]
+    UnsupportedQuery(String),
+    #[error("Could not determine the RDF format of `{}`", .0.display())]
+    UnknownRdfFormat(PathBuf),
+    #[error("Request to `{0}` failed")]
+    Http(String, #[source] Box<ureq::Error>),
+    #[error("Unsupported content type `{1}` from `{0}`")]
+    UnsupportedContentType(String, String),
+    #[error("Unsupported database `{0}`")]
+    UnsupportedDatabase(String),
+    #[error("Database source `{0}` has no query")]
+    MissingQuery(String),
+    #[error("Environment variable `{0}` is not set")]
+    MissingEnvironmentVariable(String),
+    #[error("PostgreSQL query failed")]
+    Postgres(#[from] postgres::Error),
+    #[error("MySQL query failed")]
+    MySql(#[from] mysql::Error),
+    #[error("Invalid Parquet file")]
+    Parquet(#[from] ::parquet::errors::ParquetError),
+    #[error("Invalid spreadsheet")]
+    Spreadsheet(#[from] calamine::Error),
+    #[error("Workbook has no sheet `{0}`")]
+    UnknownSheet(String),
+    #[error("Unsupported path expression `{0}`")]
+    InvalidPath(String),
+    #[error("Unsupported reference formulation `{0}`")]
+    UnsupportedReferenceFormulation(String),
+}
+
+/// A single iteration of a logical source
+pub type Record = Box<dyn RmlIteration + Send + Sync>;
+
+/// The iterations of a logical source, in source order
+pub type Records = Box<dyn Iterator<Item = Result<Record, SourceError>> + Send>;
+
+/// Open a source file or standard input, undoing its declared compression
+/// and encoding
+pub fn open(description: &SourceDescription) -> Result<Box<dyn Read + Send>, SourceError> {
+    let reader = if description.is_stdin() {
+        decompress(io::stdin(), description)?
+    } else {
+        let file = File::open(&description.path)
+            .map_err(|e| SourceError::Open(description.path.clone(), e))?;
+        match description.compression {
+            Compression::Zip => Box::new(first_zip_entry(BufReader::new(file), &description.path)?),
+            _ => decompress(file, description)?,
+        }
+    };
+
+    Ok(match description.encoding {
+        Encoding::Utf8 => reader,
+        // Honour a byte order mark when present, big endian otherwise as
+        // RFC 2781 requires
+        Encoding::Utf16 => Box::new(
+            DecodeReaderBytesBuilder::new()
+                .encoding(Some(encoding_rs::UTF_16BE))
+                .bom_override(true)
+                .build(reader),
+        ),
+    })
+}
+
+/// Read the iterations of a file logical source. Readers may drop the fields
+/// outside `projection`, which must hold every field that will be referenced.
+pub fn read(
+    source: &FileLogicalSource,
+    projection: Option<&HashSet<String>>,
+) -> Result<Records, SourceError> {
+    let formulation = reference_formulation(source)?;
+    // Parquet and spreadsheets are read with random access rather than as a
+    // stream
+    match formulation.as_str() {
+        rml::ReferenceFormulation::PARQUET => {
+            return self::parquet::records(source, projection, None)
+        }
+        rml::ReferenceFormulation::SPREADSHEET => {
+            return self::spreadsheet::records(source, projection)
+        }
+        _ => {}
+    }
+
+    let reader = open(&source.source)?;
+    let nulls = Arc::new(source.nulls.clone());
+    let iterator = source.iterator.as_deref();
+
+    match formulation.as_str() {
+        rml::ReferenceFormulation::CSV => self::csv::records(reader, projection, nulls),
+        rml::ReferenceFormulation::JSON_LINES => {
+            self::json::lines(reader, iterator, projection, nulls)
+        }
+        rml::ReferenceFormulation::JSON_PATH if is_json_lines(source) => {
+            self::json::lines(reader, iterator, projection, nulls)
+        }
+        rml::ReferenceFormulation::JSON_PATH => match extension(&source.source).as_deref() {
+            Some("yaml") | Some("yml") => self::yaml::records(reader, iterator, projection, nulls),
+            Some("toml") => self::toml::records(reader, iterator, nulls),
+            _ => self::json::records(reader, iterator, projection, nulls),
+        },
+        rml::ReferenceFormulation::XPATH => self::xml::records(reader, iterator, nulls),
+        rml::ReferenceFormulation::SPARQL => {
+            let format = extension(&source.source)
+                .and_then(|ext| RdfFormat::from_extension(&ext))
+                .ok_or_else(|| SourceError::UnknownRdfFormat(source.source.path.clone()))?;
+            self::sparql::records(reader, format, iterator, nulls)
+        }
+        other => Err(SourceError::UnsupportedReferenceFormulation(
+            other.to_string(),
+        )),
+    }
+}
+
+/// Read the iterations of a logical source, fetching web sources through
+/// `transport`. The iterations also answer the pseudo-references of their
+/// position in the source.
+pub fn read_logical_source(
+    logical_source: &LogicalSourceType,
+    transport: Arc<dyn Transport>,
+    projection: Option<&HashSet<String>>,
+) -> Result<Records, SourceError> {
+    let projection: Option<HashSet<String>> = projection.map(|fields| {
+        fields
+            .iter()
+            .filter(|field| !pseudo::is_pseudo_reference(field))
+            .cloned()
+            .collect()
+    });
+    let projection = projection.as_ref();
+
+    let (records, file) = match logical_source {
+        LogicalSourceType::File(file) => {
+            let name = file
+                .source
+                .path
+                .file_name()
+                .filter(|_| !file.source.is_stdin())
+                .map(|name| name.to_string_lossy().into());
+            (read(file, projection)?, name)
+        }
+        LogicalSourceType::Web(web) => (
+            self::web::read(web, transport, projection)?,
+            Some(web.url.as_str().into()),
+        ),
+        LogicalSourceType::Database(database) => {
+            (self::database::read(database, projection)?, None)
+        }
+        LogicalSourceType::View(view) => (self::view::read(view, transport)?, None),
+    };
+    Ok(pseudo::positioned(records, file))
+}
+
+/// The values `reference` selects in the values of `document`, each read as
+/// a document of `reference_formulation`, eg the tags of JSON objects held in
+/// a CSV column. Values that are not valid documents are skipped.
+pub fn embedded_values(
+    iteration: &dyn RmlIteration,
+    document: &str,
+    reference_formulation: &str,
+    reference: &str,
+) -> Vec<String> {
+    let nulls = iteration.get_nulls();
+    let mut values = Vec::new();
+    for embedded in iteration.get_values_for(document) {
+        if nulls.contains(&embedded) {
+            continue;
+        }
+        let found = read_document(&embedded, reference_formulation, None, Arc::default()).and_then(
+            |records| {
+                records
+                    .map(|record| record.map(|record| record.get_values_for(reference)))
+                    .collect::<Result<Vec<_>, _>>()
+            },
+        );
+        match found {
+            Ok(found) => values.extend(
+                found
+                    .into_iter()
+                    .flatten()
+                    .filter(|value| !nulls.contains(value)),
+            ),
+            Err(e) => log::warn!(
+                "Skipping value of `{}` that is not a document: {}",
+                document,
+                e
+            ),
+        }
+    }
+    values
+}
+
+/// Read the iterations of a document embedded in a value, such as JSON held
+/// in a CSV column
+pub fn read_document(
+    document: &str,
+    reference_formulation: &str,
+    iterator: Option<&str>,
+    nulls: Arc<HashSet<String>>,
+) -> Result<Records, SourceError> {
+    let reader = || Box::new(Cursor::new(document.as_bytes().to_vec())) as Box<dyn Read + Send>;
+    match reference_formulation {
+        rml::ReferenceFormulation::CSV => self::csv::records(reader(), None, nulls),
+        rml::ReferenceFormulation::JSON_LINES => self::json::lines(reader(), iterator, None, nulls),
+        // Embedded documents are small, so they are parsed whole rather than
+        // streamed
+        rml::ReferenceFormulation::JSON_PATH => {
+            let value: serde_json::Value = serde_json::from_str(document)?;
+            let iterator = self::jsonpath::JsonPath::parse(iterator.unwrap_or("$"))?;
+            Ok(self::json::select(&value, &iterator, nulls))
+        }
+        rml::ReferenceFormulation::XPATH => self::xml::records(reader(), iterator, nulls),
+        other => Err(SourceError::UnsupportedReferenceFormulation(
+            other.to_string(),
+        )),
+    }
+}
+
+/// The declared reference formulation, or one guessed from the file extension
+fn reference_formulation(source: &FileLogicalSource) -> Result<String, SourceError> {
+    if let Some(formulation) = &source.reference_formulation {
+        return Ok(formulation.clone());
+    }
+
+    match extension(&source.source).as_deref() {
+        Some("csv") => Ok(rml::ReferenceFormulation::CSV.to_string()),
+        Some("json") | Some("yaml") | Some("yml") | Some("toml") => {
+            Ok(rml::ReferenceFormulation::JSON_PATH.to_string())
+        }
+        Some("jsonl") | Some("ndjson") => Ok(rml::ReferenceFormulation::JSON_LINES.to_string()),
+        Some("xml") => Ok(rml::ReferenceFormulation::XPATH.to_string()),
+        Some("parquet") => Ok(rml::ReferenceFormulation::PARQUET.to_string()),
+        Some("xlsx") | Some("xlsm") | Some("xls") | Some("ods") => {
+            Ok(rml::ReferenceFormulation::SPREADSHEET.to_string())
+        }
+        Some(ext) if RdfFormat::from_extension(ext).is_some() => {
+            Ok(rml::ReferenceFormulation::SPARQL.to_string())
+        }
+        _ => Err(SourceError::UnsupportedReferenceFormulation(
+            source.source.path.display().to_string(),
+        )),
+    }
+}
+
+/// JSON Lines files hold one JSON document per line, and are read as such
+/// even when declared as JSONPath
+fn is_json_lines(source: &FileLogicalSource) -> bool {
+    matches!(
+        extension(&source.source).as_deref(),
+        Some("jsonl") | Some("ndjson")
+    )
+}
+
+/// The extension of the decompressed file, eg `csv` for `data.csv.gz`
+fn extension(description: &SourceDescription) -> Option<String> {
+    let path = match description.compression {
+        Compression::None => description.path.clone(),
+        _ => PathBuf::from(description.path.file_stem()?),
+    };
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+}
+
+/// Undo the compression of a stream. Zip archives need random access, so a
+/// zipped stream is read into memory first.
+fn decompress<R: Read + Send + 'static>(
+    input: R,
+    description: &SourceDescription,
+) -> Result<Box<dyn Read + Send>, SourceError> {
+    Ok(match description.compression {
+        Compression::None => Box::new(BufReader::new(input)),
+        Compression::Gzip => Box::new(MultiGzDecoder::new(BufReader::new(input))),
+        Compression::Zip => {
+            let mut buffer = Vec::new();
+            BufReader::new(input).read_to_end(&mut buffer)?;
+            Box::new(first_zip_entry(Cursor::new(buffer), &description.path)?)
+        }
+        Compression::TarXz => Box::new(first_tar_entry(
+            XzDecoder::new(BufReader::new(input)),
+            &description.path,
+        )?),
+        Compression::TarGz => Box::new(first_tar_entry(
+            MultiGzDecoder::new(BufReader::new(input)),
+            &description.path,
+        )?),
+        Compression::Zstd => Box::new(zstd::Decoder::new(input)?),
+        Compression::Bzip2 => Box::new(MultiBzDecoder::new(BufReader::new(input))),
+        Compression::Xz => Box::new(XzDecoder::new_multi_decoder(BufReader::new(input))),
+    })
+}
+
+fn first_zip_entry<R: Read + Seek>(reader: R, path: &Path) -> Result<Cursor<Vec<u8>>, SourceError> {
+    let mut archive = zip::ZipArchive::new(reader)?;
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        if entry.is_file() {
+            let mut buffer = Vec::new();
+            entry.read_to_end(&mut buffer)?;
+            return Ok(Cursor::new(buffer));
+        }
+    }
+    Err(SourceError::EmptyArchive(path.to_path_buf()))
+}
+
+fn first_tar_entry<R: Read>(reader: R, path: &Path) -> Result<Cursor<Vec<u8>>, SourceError> {
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.header().entry_type().is_file() {
+            let mut buffer = Vec::new();
+            entry.read_to_end(&mut buffer)?;
+            return Ok(Cursor::new(buffer));
+        }
+    }
+    Err(SourceError::EmptyArchive(path.to_path_buf()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_source(path: &str) -> FileLogicalSource {
+        FileLogicalSource {
+            source: SourceDescription::new(PathBuf::from(path)),
+            reference_formulation: None,
+            iterator: None,
+            nulls: HashSet::new(),
+        }
+    }
+
+    #[test]
+    fn guesses_formulation_past_compression_extension() {
+        let formulation = reference_formulation(&file_source("data.csv.gz")).unwrap();
+        assert_eq!(formulation, rml::ReferenceFormulation::CSV);
+    }
+
+    #[test]
+    fn guesses_json_lines_from_extension() {
+        for path in ["data.jsonl", "data.ndjson.gz"] {
+            let formulation = reference_formulation(&file_source(path)).unwrap();
+            assert_eq!(formulation, rml::ReferenceFormulation::JSON_LINES);
+        }
+        let formulation = reference_formulation(&file_source("data.json")).unwrap();
+        assert_eq!(formulation, rml::ReferenceFormulation::JSON_PATH);
+    }
+
+    #[test]
+    fn reads_json_lines_declared_as_json_path() {
+        let file = tempfile::Builder::new()
+            .suffix(".jsonl")
+            .tempfile()
+            .unwrap();
+        std::fs::write(file.path(), "{\"id\": 1}\n{\"id\": 2}\n").unwrap();
+        let mut source = file_source(file.path().to_str().unwrap());
+        source.reference_formulation = Some(rml::ReferenceFormulation::JSON_PATH.to_string());
+
+        let ids: Vec<_> = read(&source, None)
+            .unwrap()
+            .flat_map(|record| record.unwrap().get_values_for("$.id"))
+            .collect();
+        assert_eq!(ids, ["1", "2"]);
+    }
+
+    fn read_all(description: &SourceDescription) -> String {
+        let mut content = String::new();
+        open(description)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        content
+    }
+
+    #[test]
+    fn reads_utf_16_as_big_endian_without_a_byte_order_mark() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let mut description = SourceDescription::new(file.path().to_path_buf());
+        description.encoding = Encoding::Utf16;
+
+        let big_endian: Vec<u8> = "id\né".encode_utf16().flat_map(u16::to_be_bytes).collect();
+        std::fs::write(file.path(), &big_endian).unwrap();
+        assert_eq!(read_all(&description), "id\né");
+
+        let mut little_endian = vec![0xFF, 0xFE];
+        little_endian.extend("id\né".encode_utf16().flat_map(u16::to_le_bytes));
+        std::fs::write(file.path(), &little_endian).unwrap();
+        assert_eq!(read_all(&description), "id\né");
+    }
+
+    #[test]
+    fn reads_the_first_file_of_a_gzipped_tarball() {
+        let file = tempfile::Builder::new().suffix(".tgz").tempfile().unwrap();
+        let encoder = flate2::write::GzEncoder::new(file.reopen().unwrap(), Default::default());
+        let mut archive = tar::Builder::new(encoder);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5);
+        header.set_mode(0o644);
+        header.set_cksum();
+        archive
+            .append_data(&mut header, "data.csv", "id\n1\n".as_bytes())
+            .unwrap();
+        archive.into_inner().unwrap().finish().unwrap();
+
+        let description = SourceDescription::new(file.path().to_path_buf());
+        assert_eq!(description.compression, Compression::TarGz);
+        assert_eq!(read_all(&description), "id\n1\n");
+    }
+
+    fn csv_record(text: &str, nulls: &[&str]) -> Record {
+        let nulls = Arc::new(nulls.iter().map(|null| null.to_string()).collect());
+        let reader = Box::new(Cursor::new(text.as_bytes().to_vec()));
+        self::csv::records(reader, None, nulls)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn reads_json_embedded_in_csv() {
+        let record = csv_record(
+            "id,person\n1,\"{\"\"tags\"\": [\"\"a\"\", \"\"b\"\"]}\"\n",
+            &[],
+        );
+        let tags = embedded_values(
+            record.as_ref(),
+            "person",
+            rml::ReferenceFormulation::JSON_PATH,
+            "$.tags[*]",
+        );
+        assert_eq!(tags, ["a", "b"]);
+    }
+
+    #[test]
+    fn reads_csv_embedded_in_json() {
+        let value = serde_json::json!({"people": "name,age\nAlice,30\nBob,40\n"});
+        let record = self::json::JsonRecord::new(value, Arc::default());
+        let names = embedded_values(&record, "$.people", rml::ReferenceFormulation::CSV, "name");
+        assert_eq!(names, ["Alice", "Bob"]);
+    }
+
+    #[test]
+    fn reads_xml_embedded_in_json() {
+        let value = serde_json::json!({"page": "<page><title>Home</title></page>"});
+        let record = self::json::JsonRecord::new(value, Arc::default());
+        let titles = embedded_values(&record, "$.page", rml::ReferenceFormulation::XPATH, "title");
+        assert_eq!(titles, ["Home"]);
+    }
+
+    #[test]
+    fn skips_embedded_nulls_and_invalid_documents() {
+        let record = csv_record("id,person\n1,NULL\n", &["NULL"]);
+        let values = embedded_values(
+            record.as_ref(),
+            "person",
+            rml::ReferenceFormulation::JSON_PATH,
+            "$",
+        );
+        assert!(values.is_empty());
+
+        let record = csv_record("id,person\n1,{not json\n", &[]);
+        let values = embedded_values(
+            record.as_ref(),
+            "person",
+            rml::ReferenceFormulation::JSON_PATH,
+            "$.name",
+        );
+        assert!(values.is_empty());
+
+        let record = csv_record("id,person\n1,\"{\"\"name\"\": \"\"NULL\"\"}\"\n", &["NULL"]);
+        let values = embedded_values(
+            record.as_ref(),
+            "person",
+            rml::ReferenceFormulation::JSON_PATH,
+            "$.name",
+        );
+        assert!(values.is_empty());
+    }
+
+    #[test]
+    fn reads_embedded_documents_with_an_iterator() {
+        let names: Vec<_> = read_document(
+            "{\"people\": [{\"name\": \"Alice\"}, {\"name\": \"Bob\"}]}",
+            rml::ReferenceFormulation::JSON_PATH,
+            Some("$.people[*]"),
+            Arc::default(),
+        )
+        .unwrap()
+        .flat_map(|record| record.unwrap().get_values_for("$.name"))
+        .collect();
+        assert_eq!(names, ["Alice", "Bob"]);
+
+        assert!(matches!(
+            read_document(
+                "id\n1\n",
+                rml::ReferenceFormulation::PARQUET,
+                None,
+                Arc::default()
+            ),
+            Err(SourceError::UnsupportedReferenceFormulation(_))
+        ));
+    }
+
+    #[test]
+    fn answers_positions_in_file_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("people.csv");
+        std::fs::write(&path, "name,bio\nAlice,\"one\ntwo\"\nBob,three\n").unwrap();
+        let source = LogicalSourceType::File(file_source(path.to_str().unwrap()));
+
+        let positions: Vec<_> = read_logical_source(
+            &source,
+            Arc::new(self::web::HttpTransport::default()),
+            Some(&HashSet::from([
+                "name".to_string(),
+                pseudo::FILE.to_string(),
+            ])),
+        )
+        .unwrap()
+        .map(|record| {
+            let record = record.unwrap();
+            [pseudo::INDEX, pseudo::LINE, pseudo::FILE, "name"]
+                .map(|reference| record.get_values_for(reference).join(","))
+        })
+        .collect();
+        assert_eq!(
+            positions,
+            [
+                ["0", "2", "people.csv", "Alice"],
+                ["1", "4", "people.csv", "Bob"]
+            ]
+        );
+    }
+
+    #[test]
+    fn does_not_read_tsv_as_csv() {
+        assert!(matches!(
+            reference_formulation(&file_source("data.tsv")),
+            Err(SourceError::UnsupportedReferenceFormulation(_))
+        ));
+    }
+}
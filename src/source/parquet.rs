@@ -0,0 +1,381 @@
+use std::{collections::HashSet, fs::File, io::Read, ops::Range, sync::Arc};
+
+use ::parquet::file::reader::{FileReader, SerializedFileReader};
+use ::parquet::file::serialized_reader::{ReadOptions, ReadOptionsBuilder};
+use ::parquet::record::reader::RowIter;
+use ::parquet::record::{Field, Row};
+use ::parquet::schema::types::Type;
+use bytes::Bytes;
+use oxrdf::vocab::xsd;
+use oxrdf::NamedNodeRef;
+
+use super::lexical::{date_time_value, date_value, double_value};
+use super::partition::Partition;
+use super::{open, Record, Records, SourceError};
+use crate::model::{Compression, FileLogicalSource, RmlIteration};
+
+/// A Parquet row, flattened into the values of its column paths. Struct
+/// fields are named `parent.child`, map entries `map.key`, and list elements
+/// give several values for the same path.
+pub struct ParquetRecord {
+    values: Vec<(String, String, Option<NamedNodeRef<'static>>)>,
+    nulls: Arc<HashSet<String>>,
+}
+
+impl ParquetRecord {
+    fn new(row: &Row, nulls: Arc<HashSet<String>>) -> Self {
+        let mut values = Vec::new();
+        for (name, field) in row.get_column_iter() {
+            flatten(name.clone(), field, &mut values);
+        }
+        Self { values, nulls }
+    }
+}
+
+impl RmlIteration for ParquetRecord {
+    fn get_values_for(&self, reference: &str) -> Vec<String> {
+        self.values
+            .iter()
+            .filter(|(path, ..)| path == reference)
+            .map(|(_, value, _)| value.clone())
+            .collect()
+    }
+
+    fn get_strings_for(&self, reference: &str) -> Vec<String> {
+        self.get_values_for(reference)
+    }
+
+    fn get_nulls(&self) -> &HashSet<String> {
+        &self.nulls
+    }
+
+    fn get_datatype_for(&self, reference: &str) -> Option<NamedNodeRef<'_>> {
+        self.values
+            .iter()
+            .find(|(path, ..)| path == reference)
+            .and_then(|(.., datatype)| *datatype)
+    }
+}
+
+fn flatten(
+    path: String,
+    field: &Field,
+    values: &mut Vec<(String, String, Option<NamedNodeRef<'static>>)>,
+) {
+    match field {
+        Field::Group(row) => {
+            for (name, field) in row.get_column_iter() {
+                flatten(format!("{path}.{name}"), field, values);
+            }
+        }
+        Field::ListInternal(list) => {
+            for element in list.elements() {
+                flatten(path.clone(), element, values);
+            }
+        }
+        Field::MapInternal(map) => {
+            for (key, value) in map.entries() {
+                if let Some((key, _)) = scalar_value(key) {
+                    flatten(format!("{path}.{key}"), value, values);
+                }
+            }
+        }
+        field => {
+            if let Some((value, datatype)) = scalar_value(field) {
+                values.push((path, value, datatype));
+            }
+        }
+    }
+}
+
+/// The lexical form and natural RDF datatype of a scalar Parquet value.
+/// Strings are plain literals, and nulls have no value.
+fn scalar_value(field: &Field) -> Option<(String, Option<NamedNodeRef<'static>>)> {
+    Some(match field {
+        Field::Bool(value) => (value.to_string(), Some(xsd::BOOLEAN)),
+        Field::Byte(value) => (value.to_string(), Some(xsd::INTEGER)),
+        Field::Short(value) => (value.to_string(), Some(xsd::INTEGER)),
+        Field::Int(value) => (value.to_string(), Some(xsd::INTEGER)),
+        Field::Long(value) => (value.to_string(), Some(xsd::INTEGER)),
+        Field::UByte(value) => (value.to_string(), Some(xsd::INTEGER)),
+        Field::UShort(value) => (value.to_string(), Some(xsd::INTEGER)),
+        Field::UInt(value) => (value.to_string(), Some(xsd::INTEGER)),
+        Field::ULong(value) => (value.to_string(), Some(xsd::INTEGER)),
+        Field::Float16(value) => (double_value(*value), Some(xsd::DOUBLE)),
+        Field::Float(value) => (double_value(*value), Some(xsd::DOUBLE)),
+        Field::Double(value) => (double_value(*value), Some(xsd::DOUBLE)),
+        Field::Decimal(_) => (field.to_string(), Some(xsd::DECIMAL)),
+        Field::Str(value) => (value.clone(), None),
+        Field::Bytes(value) => {
+            let hex: String = value.data().iter().map(|b| format!("{b:02X}")).collect();
+            (hex, Some(xsd::HEX_BINARY))
+        }
+        Field::Date(days) => (date_value(i64::from(*days)), Some(xsd::DATE)),
+        Field::TimestampMillis(millis) => (date_time_value(*millis, 3), Some(xsd::DATE_TIME)),
+        Field::TimestampMicros(micros) => (date_time_value(*micros, 6), Some(xsd::DATE_TIME)),
+        Field::Null | Field::Group(_) | Field::ListInternal(_) | Field::MapInternal(_) => {
+            return None
+        }
+    })
+}
+
+/// Read the rows of a Parquet source, or of the row groups in `row_groups`.
+/// With a `projection`, only the columns it refers to are decoded.
+pub fn records(
+    source: &FileLogicalSource,
+    projection: Option<&HashSet<String>>,
+    row_groups: Option<Range<usize>>,
+) -> Result<Records, SourceError> {
+    let mut options = ReadOptionsBuilder::new();
+    if let Some(row_groups) = row_groups {
+        options = options.with_predicate(Box::new(move |_, index| row_groups.contains(&index)));
+    }
+    let reader = file_reader(source, options.build())?;
+    let schema = projected_schema(reader.metadata().file_metadata().schema(), projection);
+    let rows = RowIter::from_file_into(reader).project(schema)?;
+
+    let nulls = Arc::new(source.nulls.clone());
+    Ok(Box::new(rows.map(move |row| {
+        Ok(Box::new(ParquetRecord::new(&row?, nulls.clone())) as Record)
+    })))
+}
+
+/// Consecutive row groups of about `size` compressed bytes, as partitions of
+/// row group indices. Gives `None` for sources with a single partition.
+pub fn row_group_partitions(
+    source: &FileLogicalSource,
+    size: u64,
+) -> Result<Option<Vec<Partition>>, SourceError> {
+    let reader = file_reader(source, ReadOptionsBuilder::new().build())?;
+    let mut partitions = Vec::new();
+    let mut start = 0;
+    let mut bytes = 0;
+    for (index, row_group) in reader.metadata().row_groups().iter().enumerate() {
+        bytes += row_group.compressed_size().max(0) as u64;
+        if bytes >= size {
+            partitions.push(Partition {
+                start,
+                end: index as u64 + 1,
+                headers: None,
+            });
+            start = index as u64 + 1;
+            bytes = 0;
+        }
+    }
+    let row_groups = reader.metadata().num_row_groups() as u64;
+    if start < row_groups {
+        partitions.push(Partition {
+            start,
+            end: row_groups,
+            headers: None,
+        });
+    }
+    Ok(Some(partitions).filter(|partitions| partitions.len() > 1))
+}
+
+fn file_reader(
+    source: &FileLogicalSource,
+    options: ReadOptions,
+) -> Result<Box<dyn FileReader>, SourceError> {
+    let description = &source.source;
+    if description.compression == Compression::None && !description.is_stdin() {
+        let file = File::open(&description.path)
+            .map_err(|e| SourceError::Open(description.path.clone(), e))?;
+        return Ok(Box::new(SerializedFileReader::new_with_options(
+            file, options,
+        )?));
+    }
+
+    // Parquet needs random access, so compressed files and standard input
+    // are read into memory
+    let mut buffer = Vec::new();
+    open(description)?.read_to_end(&mut buffer)?;
+    Ok(Box::new(SerializedFileReader::new_with_options(
+        Bytes::from(buffer),
+        options,
+    )?))
+}
+
+/// The top-level columns that the `projection` fields are in
+fn projected_schema(schema: &Type, projection: Option<&HashSet<String>>) -> Option<Type> {
+    let fields = projection?;
+    let mut columns: Vec<_> = schema
+        .get_fields()
+        .iter()
+        .filter(|column| {
+            let name = column.name();
+            fields.iter().any(|field| {
+                field == name
+                    || field
+                        .strip_prefix(name)
+                        .is_some_and(|rest| rest.starts_with('.'))
+            })
+        })
+        .cloned()
+        .collect();
+    // Rows are still counted when no column is referenced
+    if columns.is_empty() {
+        columns.extend(schema.get_fields().first().cloned());
+    }
+    Type::group_type_builder(schema.name())
+        .with_fields(columns)
+        .build()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use ::parquet::data_type::{BoolType, ByteArray, ByteArrayType, DoubleType, Int32Type};
+    use ::parquet::file::writer::SerializedFileWriter;
+    use ::parquet::schema::parser::parse_message_type;
+
+    use super::*;
+    use crate::model::SourceDescription;
+
+    /// Ann with every column set, then a person with only an id, each in a
+    /// row group of their own
+    fn write_people(path: &Path) {
+        let schema = parse_message_type(
+            "message people {
+                REQUIRED INT32 id;
+                OPTIONAL BINARY name (UTF8);
+                OPTIONAL DOUBLE score;
+                OPTIONAL BOOLEAN active;
+                OPTIONAL INT32 born (DATE);
+                OPTIONAL group address {
+                    OPTIONAL BINARY city (UTF8);
+                }
+            }",
+        )
+        .unwrap();
+        let file = File::create(path).unwrap();
+        let mut writer =
+            SerializedFileWriter::new(file, Arc::new(schema), Default::default()).unwrap();
+        for id in [1, 2] {
+            let set = id == 1;
+            let levels = |level: i16| [if set { level } else { 0 }];
+            let mut row_group = writer.next_row_group().unwrap();
+            let mut index = 0;
+            while let Some(mut column) = row_group.next_column().unwrap() {
+                let written = match index {
+                    0 => column.typed::<Int32Type>().write_batch(&[id], None, None),
+                    1 => column.typed::<ByteArrayType>().write_batch(
+                        &[ByteArray::from("Ann")][..usize::from(set)],
+                        Some(&levels(1)),
+                        None,
+                    ),
+                    2 => column.typed::<DoubleType>().write_batch(
+                        &[1.5][..usize::from(set)],
+                        Some(&levels(1)),
+                        None,
+                    ),
+                    3 => column.typed::<BoolType>().write_batch(
+                        &[true][..usize::from(set)],
+                        Some(&levels(1)),
+                        None,
+                    ),
+                    4 => column.typed::<Int32Type>().write_batch(
+                        &[19_000][..usize::from(set)],
+                        Some(&levels(1)),
+                        None,
+                    ),
+                    _ => column.typed::<ByteArrayType>().write_batch(
+                        &[ByteArray::from("Ghent")][..usize::from(set)],
+                        Some(&levels(2)),
+                        None,
+                    ),
+                };
+                written.unwrap();
+                column.close().unwrap();
+                index += 1;
+            }
+            row_group.close().unwrap();
+        }
+        writer.close().unwrap();
+    }
+
+    fn people() -> (tempfile::NamedTempFile, FileLogicalSource) {
+        let file = tempfile::Builder::new()
+            .suffix(".parquet")
+            .tempfile()
+            .unwrap();
+        write_people(file.path());
+        let source = FileLogicalSource {
+            source: SourceDescription::new(file.path().to_path_buf()),
+            reference_formulation: None,
+            iterator: None,
+            nulls: HashSet::new(),
+        };
+        (file, source)
+    }
+
+    fn read(source: &FileLogicalSource, projection: Option<&HashSet<String>>) -> Vec<Record> {
+        records(source, projection, None)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    #[test]
+    fn reads_typed_columns() {
+        let (_file, source) = people();
+        let records = read(&source, None);
+        assert_eq!(records.len(), 2);
+
+        let ann = &records[0];
+        for (reference, value, datatype) in [
+            ("id", "1", Some(xsd::INTEGER)),
+            ("name", "Ann", None),
+            ("score", "1.5", Some(xsd::DOUBLE)),
+            ("active", "true", Some(xsd::BOOLEAN)),
+            ("born", "2022-01-08", Some(xsd::DATE)),
+            ("address.city", "Ghent", None),
+        ] {
+            assert_eq!(ann.get_values_for(reference), [value], "{reference}");
+            assert_eq!(ann.get_datatype_for(reference), datatype, "{reference}");
+        }
+    }
+
+    #[test]
+    fn reads_nulls_as_no_value() {
+        let (_file, source) = people();
+        let records = read(&source, None);
+        assert_eq!(records[1].get_values_for("id"), ["2"]);
+        for reference in ["name", "score", "active", "born", "address.city"] {
+            assert!(
+                records[1].get_values_for(reference).is_empty(),
+                "{reference}"
+            );
+        }
+    }
+
+    #[test]
+    fn reads_only_projected_columns() {
+        let (_file, source) = people();
+        let projection = HashSet::from(["address.city".to_string()]);
+        let records = read(&source, Some(&projection));
+        assert_eq!(records[0].get_values_for("address.city"), ["Ghent"]);
+        assert!(records[0].get_values_for("id").is_empty());
+    }
+
+    #[test]
+    fn partitions_row_groups() {
+        let (_file, source) = people();
+        let partitions = row_group_partitions(&source, 1).unwrap().unwrap();
+        assert_eq!(
+            partitions
+                .iter()
+                .map(|partition| partition.start..partition.end)
+                .collect::<Vec<_>>(),
+            [0..1, 1..2]
+        );
+        let second: Vec<_> = records(&source, None, Some(1..2))
+            .unwrap()
+            .map(|record| record.unwrap().get_values_for("id"))
+            .collect();
+        assert_eq!(second, [["2"]]);
+        assert_eq!(row_group_partitions(&source, u64::MAX).unwrap(), None);
+    }
+}
@@ -0,0 +1,376 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{BufRead, BufReader, Read, Seek, SeekFrom},
+    path::Path,
+    sync::Arc,
+};
+
+use csv::StringRecord;
+use rayon::prelude::*;
+
+use super::{is_json_lines, reference_formulation, Records, SourceError};
+use crate::model::{Compression, Encoding, FileLogicalSource};
+use crate::vocab::rml;
+
+/// The approximate size of a partition. Smaller sources are not split.
+pub const PARTITION_SIZE: u64 = 64 * 1024 * 1024;
+
+/// A part of a source file that starts and ends on record boundaries: a byte
+/// range, or a range of row groups for Parquet
+#[derive(Debug, Clone, PartialEq)]
+pub struct Partition {
+    pub start: u64,
+    pub end: u64,
+    /// The header of a CSV source, read once for all its partitions
+    pub headers: Option<Arc<StringRecord>>,
+}
+
+enum Layout {
+    Csv,
+    JsonLines,
+    Parquet,
+}
+
+fn layout(source: &FileLogicalSource) -> Result<Option<Layout>, SourceError> {
+    Ok(match reference_formulation(source)?.as_str() {
+        rml::ReferenceFormulation::CSV => Some(Layout::Csv),
+        rml::ReferenceFormulation::JSON_LINES => Some(Layout::JsonLines),
+        rml::ReferenceFormulation::PARQUET => Some(Layout::Parquet),
+        rml::ReferenceFormulation::JSON_PATH if is_json_lines(source) => Some(Layout::JsonLines),
+        _ => None,
+    })
+}
+
+/// Split a CSV or JSON Lines source into partitions of about `size` bytes.
+/// Gives `None` for sources that are small, compressed, not UTF-8, read from
+/// standard input or in another format, which can only be read as a whole.
+pub fn partitions(
+    source: &FileLogicalSource,
+    size: u64,
+) -> Result<Option<Vec<Partition>>, SourceError> {
+    let description = &source.source;
+    if description.compression != Compression::None
+        || description.encoding != Encoding::Utf8
+        || description.is_stdin()
+    {
+        return Ok(None);
+    }
+    let Some(layout) = layout(source)? else {
+        return Ok(None);
+    };
+
+    let mut file = open(source)?;
+    let length = file.metadata()?.len();
+    if length <= size {
+        return Ok(None);
+    }
+
+    let (boundaries, headers) = match layout {
+        Layout::Csv => {
+            let (headers, data) = super::csv::headers(BufReader::new(&mut file))?;
+            let boundaries = csv_boundaries(&description.path, data, length, size)?;
+            (boundaries, Some(Arc::new(headers)))
+        }
+        Layout::JsonLines => (line_boundaries(file, length, size)?, None),
+        Layout::Parquet => return super::parquet::row_group_partitions(source, size),
+    };
+
+    Ok(Some(
+        boundaries
+            .windows(2)
+            .map(|range| Partition {
+                start: range[0],
+                end: range[1],
+                headers: headers.clone(),
+            })
+            .collect(),
+    ))
+}
+
+/// Read the records of a partition made by [`partitions`], keeping only the
+/// fields in `projection` when given
+pub fn read_partition(
+    source: &FileLogicalSource,
+    partition: &Partition,
+    projection: Option<&HashSet<String>>,
+) -> Result<Records, SourceError> {
+    let layout = layout(source)?;
+    if let Some(Layout::Parquet) = layout {
+        let row_groups = partition.start as usize..partition.end as usize;
+        return super::parquet::records(source, projection, Some(row_groups));
+    }
+
+    let nulls: Arc<HashSet<String>> = Arc::new(source.nulls.clone());
+    let mut file = open(source)?;
+    file.seek(SeekFrom::Start(partition.start))?;
+    let range = Box::new(BufReader::new(file.take(partition.end - partition.start)));
+
+    match layout {
+        Some(Layout::Csv) => {
+            let headers = match &partition.headers {
+                Some(headers) => headers.clone(),
+                None => Arc::new(super::csv::headers(BufReader::new(open(source)?))?.0),
+            };
+            Ok(super::csv::rows(range, &headers, projection, nulls))
+        }
+        Some(Layout::JsonLines) => {
+            super::json::lines(range, source.iterator.as_deref(), projection, nulls)
+        }
+        Some(Layout::Parquet) | None => Err(SourceError::UnsupportedReferenceFormulation(
+            reference_formulation(source)?,
+        )),
+    }
+}
+
+fn open(source: &FileLogicalSource) -> Result<File, SourceError> {
+    let path = &source.source.path;
+    File::open(path).map_err(|e| SourceError::Open(path.clone(), e))
+}
+
+/// Quoted CSV fields may span lines, so whether a newline ends a record
+/// depends on every quote before it. Each chunk of about `size` bytes is
+/// scanned concurrently, for its first record end under either quoting state
+/// at its start, and the actual states are then resolved in order. This
+/// still reads the whole file before any partition is processed.
+fn csv_boundaries(
+    path: &Path,
+    start: u64,
+    length: u64,
+    size: u64,
+) -> Result<Vec<u64>, SourceError> {
+    // A chunk starts one byte before its target offset, so that a newline
+    // right before the target ends a record at it
+    let mut offsets = vec![start];
+    offsets.extend(
+        (1..)
+            .map(|chunk| start + chunk * size - 1)
+            .take_while(|offset| *offset < length),
+    );
+    offsets.push(length);
+
+    let scans = offsets
+        .par_windows(2)
+        .map(|range| {
+            let file = File::open(path).map_err(|e| SourceError::Open(path.to_path_buf(), e))?;
+            scan_csv(file, range[0], range[1])
+        })
+        .collect::<Result<Vec<_>, SourceError>>()?;
+
+    let mut boundaries = vec![start];
+    let mut quoted = false;
+    for (chunk, scan) in scans.iter().enumerate() {
+        if chunk > 0
+            && let Some(end) = scan.ends[quoted as usize]
+            && end < length
+        {
+            boundaries.push(end);
+        }
+        quoted ^= scan.flips;
+    }
+    boundaries.push(length);
+    Ok(boundaries)
+}
+
+/// What a scan of a chunk of a CSV file finds without knowing whether the
+/// chunk starts inside quotes
+struct CsvScan {
+    /// Whether the chunk holds an odd number of quotes
+    flips: bool,
+    /// The offset after the first record end, when the chunk starts outside
+    /// and inside quotes
+    ends: [Option<u64>; 2],
+}
+
+fn scan_csv(mut file: File, start: u64, end: u64) -> Result<CsvScan, SourceError> {
+    file.seek(SeekFrom::Start(start))?;
+    let mut reader = BufReader::with_capacity(1 << 20, file.take(end - start));
+
+    let mut offset = start;
+    // Whether the quoting state differs from the one at the chunk start
+    let mut flips = false;
+    let mut ends = [None, None];
+    loop {
+        let buffer = reader.fill_buf()?;
+        if buffer.is_empty() {
+            break;
+        }
+        for byte in buffer {
+            offset += 1;
+            match byte {
+                // An escaped `""` toggles twice
+                b'"' => flips = !flips,
+                // Outside quotes when the state is back to an unquoted
+                // start, or flipped from a quoted one
+                b'\n' => {
+                    ends[flips as usize].get_or_insert(offset);
+                }
+                _ => {}
+            }
+        }
+        let consumed = buffer.len();
+        reader.consume(consumed);
+    }
+    Ok(CsvScan { flips, ends })
+}
+
+/// JSON strings cannot hold raw newlines, so a boundary is the end of the
+/// line containing each target offset
+fn line_boundaries(mut file: File, length: u64, size: u64) -> Result<Vec<u64>, SourceError> {
+    let mut boundaries = vec![0];
+    let mut target = size;
+    while target < length {
+        file.seek(SeekFrom::Start(target))?;
+        let mut line = Vec::new();
+        let read = BufReader::new(&mut file).read_until(b'\n', &mut line)? as u64;
+        let boundary = target + read;
+        if boundary >= length {
+            break;
+        }
+        boundaries.push(boundary);
+        target = boundary + size;
+    }
+
+    boundaries.push(length);
+    Ok(boundaries)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::path::Path;
+
+    use tempfile::NamedTempFile;
+
+    use super::*;
+    use crate::model::SourceDescription;
+
+    fn file_source(path: &Path) -> FileLogicalSource {
+        FileLogicalSource {
+            source: SourceDescription::new(path.to_path_buf()),
+            reference_formulation: None,
+            iterator: None,
+            nulls: HashSet::new(),
+        }
+    }
+
+    fn write(suffix: &str, content: &str) -> NamedTempFile {
+        let mut file = tempfile::Builder::new().suffix(suffix).tempfile().unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+        file
+    }
+
+    /// The values of `reference` in every partition of a source, in order
+    fn partitioned_values(source: &FileLogicalSource, size: u64, reference: &str) -> Vec<String> {
+        let partitions = partitions(source, size)
+            .unwrap()
+            .expect("a partitioned source");
+        assert!(partitions.len() > 1);
+        partitions
+            .into_iter()
+            .flat_map(|partition| read_partition(source, &partition, None).unwrap())
+            .flat_map(|record| record.unwrap().get_values_for(reference))
+            .collect()
+    }
+
+    /// Boundaries start and end the file, and otherwise follow a newline
+    fn check_boundaries(content: &str, boundaries: &[u64], start: u64) {
+        assert_eq!(boundaries.first(), Some(&start));
+        assert_eq!(boundaries.last(), Some(&(content.len() as u64)));
+        assert!(boundaries.windows(2).all(|range| range[0] < range[1]));
+        for boundary in &boundaries[1..boundaries.len() - 1] {
+            assert_eq!(content.as_bytes()[*boundary as usize - 1], b'\n');
+        }
+    }
+
+    #[test]
+    fn splits_csv_on_record_ends() {
+        let mut content = String::from("id,name\n");
+        for id in 0..100 {
+            content.push_str(&format!("{id},Name {id}\n"));
+        }
+        let file = write(".csv", &content);
+        let header = "id,name\n".len() as u64;
+        let length = content.len() as u64;
+
+        let boundaries = csv_boundaries(file.path(), header, length, 64).unwrap();
+        check_boundaries(&content, &boundaries, header);
+        assert!(boundaries.len() > 10);
+
+        let ids = partitioned_values(&file_source(file.path()), 64, "id");
+        assert_eq!(ids, (0..100).map(|id| id.to_string()).collect::<Vec<_>>());
+
+        // The header is read once and shared by every partition
+        let partitions = partitions(&file_source(file.path()), 64).unwrap().unwrap();
+        let headers = partitions[0].headers.clone().unwrap();
+        assert_eq!(*headers, vec!["id", "name"]);
+        assert!(partitions
+            .iter()
+            .all(|partition| Arc::ptr_eq(partition.headers.as_ref().unwrap(), &headers)));
+    }
+
+    #[test]
+    fn keeps_quoted_newlines_inside_a_partition() {
+        // Each record holds a quoted field of many lines, so most split
+        // targets fall inside quotes, some right before a quoted newline
+        let mut content = String::from("id,text\n");
+        for id in 0..20 {
+            let text = format!("line {id}\n").repeat(8);
+            content.push_str(&format!("{id},\"{text}with \"\"quotes\"\"\n\"\n"));
+        }
+        let file = write(".csv", &content);
+        let header = "id,text\n".len() as u64;
+
+        for size in [1, 7, 30, 100] {
+            let boundaries =
+                csv_boundaries(file.path(), header, content.len() as u64, size).unwrap();
+            check_boundaries(&content, &boundaries, header);
+            for boundary in &boundaries[1..boundaries.len() - 1] {
+                // Records end with a closing quote and a newline
+                assert_eq!(&content[*boundary as usize - 2..*boundary as usize], "\"\n");
+            }
+
+            let source = file_source(file.path());
+            let texts = partitioned_values(&source, size, "text");
+            assert_eq!(texts.len(), 20);
+            assert_eq!(
+                texts[3],
+                format!("{}with \"quotes\"\n", "line 3\n".repeat(8))
+            );
+            let ids = partitioned_values(&source, size, "id");
+            assert_eq!(ids, (0..20).map(|id| id.to_string()).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn splits_json_lines_on_line_ends() {
+        let content: String = (0..50).map(|id| format!("{{\"id\": {id}}}\n")).collect();
+        let file = write(".jsonl", &content);
+
+        // Sizes near the line length put split targets on newlines and on
+        // line starts
+        for size in [1, 12, 13, 40] {
+            let boundaries =
+                line_boundaries(File::open(file.path()).unwrap(), content.len() as u64, size)
+                    .unwrap();
+            check_boundaries(&content, &boundaries, 0);
+        }
+
+        let mut source = file_source(file.path());
+        source.reference_formulation = Some(rml::ReferenceFormulation::JSON_LINES.to_string());
+        let ids = partitioned_values(&source, 40, "$.id");
+        assert_eq!(ids, (0..50).map(|id| id.to_string()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn does_not_split_small_or_compressed_sources() {
+        let file = write(".csv", "id\n1\n2\n");
+        assert_eq!(partitions(&file_source(file.path()), 1024).unwrap(), None);
+
+        let compressed = write(".csv.gz", "id\n1\n2\n");
+        assert_eq!(
+            partitions(&file_source(compressed.path()), 1).unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use std::{collections::HashSet, sync::Arc};
+
+use oxrdf::{vocab::xsd, NamedNodeRef};
+
+use super::{Record, Records};
+use crate::model::RmlIteration;
+
+/// The position of the iteration in its logical source, counted from 0
+pub const INDEX: &str = "#";
+/// The line of the source an iteration starts on, counted from 1, for CSV and
+/// JSON Lines sources
+pub const LINE: &str = "#line";
+/// The file name or URL of the source
+pub const FILE: &str = "#file";
+
+/// Whether a reference names a pseudo-reference rather than source data
+pub fn is_pseudo_reference(reference: &str) -> bool {
+    matches!(reference, INDEX | LINE | FILE)
+}
+
+/// An iteration that also answers the pseudo-references of its position in
+/// the logical source. Line numbers are answered by the source readers.
+pub struct PositionedRecord {
+    record: Record,
+    index: usize,
+    file: Option<Arc<str>>,
+}
+
+impl RmlIteration for PositionedRecord {
+    fn get_values_for(&self, reference: &str) -> Vec<String> {
+        match reference {
+            INDEX => vec![self.index.to_string()],
+            FILE => self.file.iter().map(|file| file.to_string()).collect(),
+            _ => self.record.get_values_for(reference),
+        }
+    }
+
+    fn get_strings_for(&self, reference: &str) -> Vec<String> {
+        match reference {
+            INDEX | FILE => self.get_values_for(reference),
+            _ => self.record.get_strings_for(reference),
+        }
+    }
+
+    fn get_nulls(&self) -> &HashSet<String> {
+        self.record.get_nulls()
+    }
+
+    fn get_datatype_for(&self, reference: &str) -> Option<NamedNodeRef<'_>> {
+        match reference {
+            INDEX | LINE => Some(xsd::INTEGER),
+            FILE => None,
+            _ => self.record.get_datatype_for(reference),
+        }
+    }
+
+    fn get_iterations_for(&self, iterator: &str) -> Vec<Record> {
+        self.record.get_iterations_for(iterator)
+    }
+}
+
+/// Number the iterations of a logical source read from `file`
+pub fn positioned(records: Records, file: Option<Arc<str>>) -> Records {
+    Box::new(records.enumerate().map(move |(index, record)| {
+        record.map(|record| {
+            Box::new(PositionedRecord {
+                record,
+                index,
+                file: file.clone(),
+            }) as Record
+        })
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn read(text: &str, file: Option<Arc<str>>) -> Vec<Record> {
+        let reader = Box::new(Cursor::new(text.as_bytes().to_vec()));
+        let records = super::super::csv::records(reader, None, Arc::default()).unwrap();
+        positioned(records, file).map(Result::unwrap).collect()
+    }
+
+    #[test]
+    fn answers_index_line_and_file() {
+        let records = read("name\nAlice\nBob\n", Some("people.csv".into()));
+        assert_eq!(records[1].get_values_for(INDEX), ["1"]);
+        assert_eq!(records[1].get_values_for(LINE), ["3"]);
+        assert_eq!(records[1].get_values_for(FILE), ["people.csv"]);
+        assert_eq!(records[1].get_strings_for(FILE), ["people.csv"]);
+        assert_eq!(records[1].get_values_for("name"), ["Bob"]);
+    }
+
+    #[test]
+    fn has_no_file_for_stdin() {
+        let records = read("name\nAlice\n", None);
+        assert!(records[0].get_values_for(FILE).is_empty());
+        assert_eq!(records[0].get_values_for(INDEX), ["0"]);
+    }
+
+    #[test]
+    fn types_index_and_line_as_integers() {
+        let records = read("name\nAlice\n", Some("people.csv".into()));
+        assert_eq!(records[0].get_datatype_for(INDEX), Some(xsd::INTEGER));
+        assert_eq!(records[0].get_datatype_for(LINE), Some(xsd::INTEGER));
+        assert_eq!(records[0].get_datatype_for(FILE), None);
+        assert_eq!(records[0].get_datatype_for("name"), None);
+    }
+
+    #[test]
+    fn recognises_pseudo_references() {
+        assert!([INDEX, LINE, FILE].into_iter().all(is_pseudo_reference));
+        assert!(!is_pseudo_reference("#name"));
+    }
+}
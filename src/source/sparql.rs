@@ -0,0 +1,256 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::Read,
+    sync::Arc,
+};
+
+use oxrdf::vocab::xsd;
+use oxrdf::{BlankNode, Dataset, Literal, NamedNode, NamedNodeRef, Term};
+use oxrdfio::{RdfFormat, RdfParser};
+use serde::Deserialize;
+use spareval::{QueryEvaluator, QueryResults};
+use spargebra::Query;
+
+use super::{Record, Records, SourceError};
+use crate::model::RmlIteration;
+
+/// Every triple of the default graph, when no query is given
+pub const DEFAULT_QUERY: &str = "SELECT * WHERE { ?s ?p ?o }";
+
+/// A solution of a SPARQL SELECT query, where references name its variables
+pub struct SparqlRecord {
+    bindings: Vec<(String, Term)>,
+    nulls: Arc<HashSet<String>>,
+}
+
+impl SparqlRecord {
+    fn term(&self, reference: &str) -> Option<&Term> {
+        let variable = reference.strip_prefix('?').unwrap_or(reference);
+        self.bindings
+            .iter()
+            .find(|(name, _)| name == variable)
+            .map(|(_, term)| term)
+    }
+}
+
+impl RmlIteration for SparqlRecord {
+    fn get_values_for(&self, reference: &str) -> Vec<String> {
+        self.term(reference)
+            .map(|term| {
+                vec![match term {
+                    Term::NamedNode(node) => node.as_str().to_string(),
+                    Term::BlankNode(node) => node.as_str().to_string(),
+                    Term::Literal(literal) => literal.value().to_string(),
+                    Term::Triple(triple) => triple.to_string(),
+                }]
+            })
+            .unwrap_or_default()
+    }
+
+    fn get_strings_for(&self, reference: &str) -> Vec<String> {
+        self.get_values_for(reference)
+    }
+
+    fn get_nulls(&self) -> &HashSet<String> {
+        &self.nulls
+    }
+
+    fn get_datatype_for(&self, reference: &str) -> Option<NamedNodeRef<'_>> {
+        match self.term(reference)? {
+            Term::Literal(literal) if literal.language().is_none() => {
+                Some(literal.datatype()).filter(|datatype| *datatype != xsd::STRING)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The solutions of the SPARQL SELECT `query` over an RDF file. The whole
+/// file is loaded into memory as a dataset before the query is evaluated.
+pub fn records(
+    reader: Box<dyn Read + Send>,
+    format: RdfFormat,
+    query: Option<&str>,
+    nulls: Arc<HashSet<String>>,
+) -> Result<Records, SourceError> {
+    let query_text = query.unwrap_or(DEFAULT_QUERY);
+    let query = Query::parse(query_text, None)?;
+    if !matches!(query, Query::Select { .. }) {
+        return Err(SourceError::UnsupportedQuery(query_text.to_string()));
+    }
+
+    let mut dataset = Dataset::new();
+    for quad in RdfParser::from_format(format).for_reader(reader) {
+        dataset.insert(&quad?);
+    }
+
+    let QueryResults::Solutions(solutions) = QueryEvaluator::new().execute(dataset, &query)? else {
+        return Err(SourceError::UnsupportedQuery(query_text.to_string()));
+    };
+    // The solution iterator cannot be sent to other threads, so solutions
+    // are collected up front
+    let mut records = Vec::new();
+    for solution in solutions {
+        let bindings = solution?
+            .iter()
+            .map(|(variable, term)| (variable.as_str().to_string(), term.clone()))
+            .collect();
+        records.push(Box::new(SparqlRecord {
+            bindings,
+            nulls: nulls.clone(),
+        }) as Record);
+    }
+    Ok(Box::new(records.into_iter().map(Ok)))
+}
+
+/// SPARQL query results in JSON
+#[derive(Deserialize)]
+struct JsonResults {
+    results: JsonBindings,
+}
+
+#[derive(Deserialize)]
+struct JsonBindings {
+    bindings: Vec<HashMap<String, JsonTerm>>,
+}
+
+#[derive(Deserialize)]
+struct JsonTerm {
+    #[serde(rename = "type")]
+    kind: String,
+    value: String,
+    datatype: Option<String>,
+    #[serde(rename = "xml:lang")]
+    language: Option<String>,
+}
+
+impl JsonTerm {
+    fn into_term(self) -> Option<Term> {
+        Some(match (self.kind.as_str(), self.language, self.datatype) {
+            ("uri", ..) => NamedNode::new_unchecked(self.value).into(),
+            ("bnode", ..) => BlankNode::new_unchecked(self.value).into(),
+            ("literal" | "typed-literal", Some(language), _) => {
+                Literal::new_language_tagged_literal_unchecked(self.value, language).into()
+            }
+            ("literal" | "typed-literal", None, Some(datatype)) => {
+                Literal::new_typed_literal(self.value, NamedNode::new_unchecked(datatype)).into()
+            }
+            ("literal" | "typed-literal", None, None) => {
+                Literal::new_simple_literal(self.value).into()
+            }
+            _ => return None,
+        })
+    }
+}
+
+/// The solutions of SPARQL query results in JSON, as returned by SPARQL
+/// endpoints
+pub fn json_results(
+    reader: Box<dyn Read + Send>,
+    nulls: Arc<HashSet<String>>,
+) -> Result<Records, SourceError> {
+    let results: JsonResults = serde_json::from_reader(reader)?;
+    Ok(Box::new(results.results.bindings.into_iter().map(
+        move |solution| {
+            let bindings = solution
+                .into_iter()
+                .filter_map(|(variable, term)| Some((variable, term.into_term()?)))
+                .collect();
+            Ok(Box::new(SparqlRecord {
+                bindings,
+                nulls: nulls.clone(),
+            }) as Record)
+        },
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    const PEOPLE: &str = r#"
+        @prefix ex: <http://example.com/> .
+        ex:ann ex:name "Ann"; ex:age 30; ex:greeting "hallo"@nl; ex:home ex:ghent .
+        ex:bob ex:name "Bob" .
+    "#;
+
+    const QUERY: &str = "
+        PREFIX ex: <http://example.com/>
+        SELECT ?person ?name ?age ?greeting
+        WHERE {
+          ?person ex:name ?name .
+          OPTIONAL { ?person ex:age ?age }
+          OPTIONAL { ?person ex:greeting ?greeting }
+        }
+        ORDER BY ?name
+    ";
+
+    fn solutions(query: Option<&str>) -> Result<Vec<Record>, SourceError> {
+        let reader = Box::new(Cursor::new(PEOPLE));
+        Ok(records(reader, RdfFormat::Turtle, query, Arc::default())?
+            .map(Result::unwrap)
+            .collect())
+    }
+
+    #[test]
+    fn binds_references_to_query_variables() {
+        let solutions = solutions(Some(QUERY)).unwrap();
+        assert_eq!(solutions.len(), 2);
+        let (ann, bob) = (&solutions[0], &solutions[1]);
+
+        assert_eq!(ann.get_values_for("person"), ["http://example.com/ann"]);
+        assert_eq!(ann.get_values_for("?name"), ["Ann"]);
+        assert_eq!(ann.get_values_for("age"), ["30"]);
+        assert_eq!(ann.get_datatype_for("age"), Some(xsd::INTEGER));
+        assert_eq!(ann.get_datatype_for("name"), None);
+        assert_eq!(ann.get_values_for("greeting"), ["hallo"]);
+        assert_eq!(ann.get_datatype_for("greeting"), None);
+
+        // Unbound and unknown variables have no value
+        assert!(bob.get_values_for("age").is_empty());
+        assert!(bob.get_values_for("home").is_empty());
+    }
+
+    #[test]
+    fn selects_every_triple_without_a_query() {
+        assert_eq!(solutions(None).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn rejects_queries_other_than_select() {
+        assert!(matches!(
+            solutions(Some("ASK { ?s ?p ?o }")),
+            Err(SourceError::UnsupportedQuery(_))
+        ));
+        assert!(solutions(Some("SELECT")).is_err());
+    }
+
+    #[test]
+    fn reads_json_results() {
+        let results = r#"{
+            "head": {"vars": ["person", "name", "age", "node", "other"]},
+            "results": {"bindings": [{
+                "person": {"type": "uri", "value": "http://example.com/ann"},
+                "name": {"type": "literal", "value": "Ann", "xml:lang": "en"},
+                "age": {"type": "literal", "value": "30",
+                        "datatype": "http://www.w3.org/2001/XMLSchema#integer"},
+                "node": {"type": "bnode", "value": "b0"},
+                "other": {"type": "unknown", "value": "?"}
+            }]}
+        }"#;
+        let solutions: Vec<_> = json_results(Box::new(Cursor::new(results)), Arc::default())
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        let ann = &solutions[0];
+        assert_eq!(ann.get_values_for("person"), ["http://example.com/ann"]);
+        assert_eq!(ann.get_values_for("name"), ["Ann"]);
+        assert_eq!(ann.get_datatype_for("name"), None);
+        assert_eq!(ann.get_values_for("age"), ["30"]);
+        assert_eq!(ann.get_datatype_for("age"), Some(xsd::INTEGER));
+        assert_eq!(ann.get_values_for("node"), ["b0"]);
+        assert!(ann.get_values_for("other").is_empty());
+    }
+}
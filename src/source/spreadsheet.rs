@@ -0,0 +1,404 @@
+use std::{
+    collections::HashSet,
+    io::{Cursor, Read},
+    sync::Arc,
+};
+
+use calamine::{open_workbook_auto_from_rs, Data, Reader};
+use oxrdf::vocab::xsd;
+use oxrdf::NamedNodeRef;
+
+use super::lexical::double_value;
+use super::{open, Record, Records, SourceError};
+use crate::model::{reference_field, FileLogicalSource, RmlIteration};
+
+type Cell = Option<(String, Option<NamedNodeRef<'static>>)>;
+
+/// A spreadsheet row, where references name columns of the header row
+pub struct SpreadsheetRecord {
+    headers: Arc<Vec<String>>,
+    cells: Vec<Cell>,
+    nulls: Arc<HashSet<String>>,
+}
+
+impl SpreadsheetRecord {
+    fn cell(&self, reference: &str) -> Option<&(String, Option<NamedNodeRef<'static>>)> {
+        self.headers
+            .iter()
+            .position(|header| header == reference)
+            .and_then(|index| self.cells.get(index))
+            .and_then(Option::as_ref)
+    }
+}
+
+impl RmlIteration for SpreadsheetRecord {
+    fn get_values_for(&self, reference: &str) -> Vec<String> {
+        self.cell(reference)
+            .map(|(value, _)| vec![value.clone()])
+            .unwrap_or_default()
+    }
+
+    fn get_strings_for(&self, reference: &str) -> Vec<String> {
+        self.get_values_for(reference)
+    }
+
+    fn get_nulls(&self) -> &HashSet<String> {
+        &self.nulls
+    }
+
+    fn get_datatype_for(&self, reference: &str) -> Option<NamedNodeRef<'_>> {
+        self.cell(reference).and_then(|(_, datatype)| *datatype)
+    }
+}
+
+/// The lexical form and natural RDF datatype of a cell. Text cells are plain
+/// literals, and empty or error cells have no value.
+fn cell_value(cell: &Data) -> Cell {
+    Some(match cell {
+        Data::Int(value) => (value.to_string(), Some(xsd::INTEGER)),
+        // Spreadsheets store every number as a float
+        Data::Float(value) if value.fract() == 0.0 && value.abs() < 1e15 => {
+            ((*value as i64).to_string(), Some(xsd::INTEGER))
+        }
+        Data::Float(value) => (double_value(*value), Some(xsd::DOUBLE)),
+        Data::String(value) => (value.clone(), None),
+        Data::Bool(value) => (value.to_string(), Some(xsd::BOOLEAN)),
+        Data::DateTime(value) if value.is_duration() => {
+            let seconds = (value.as_f64() * 86_400_000.0).round() / 1000.0;
+            let sign = if seconds < 0.0 { "-" } else { "" };
+            (format!("{sign}PT{}S", seconds.abs()), Some(xsd::DURATION))
+        }
+        Data::DateTime(value) => {
+            let datetime = value.as_datetime()?;
+            // Whole serial numbers are dates without a time of day
+            if value.as_f64().fract() == 0.0 {
+                (datetime.format("%Y-%m-%d").to_string(), Some(xsd::DATE))
+            } else {
+                let lexical = datetime.format("%Y-%m-%dT%H:%M:%S%.f").to_string();
+                (lexical, Some(xsd::DATE_TIME))
+            }
+        }
+        Data::DateTimeIso(value) if value.contains('T') => (value.clone(), Some(xsd::DATE_TIME)),
+        Data::DateTimeIso(value) if value.contains(':') => (value.clone(), Some(xsd::TIME)),
+        Data::DateTimeIso(value) => (value.clone(), Some(xsd::DATE)),
+        Data::DurationIso(value) => (value.clone(), Some(xsd::DURATION)),
+        Data::Error(_) | Data::Empty => return None,
+    })
+}
+
+/// A sheet and optional cell range, eg `Sales`, `Sales!A1:D20` or
+/// `'Q1 Sales'!B2:C9`
+struct Selection {
+    sheet: String,
+    range: Option<((u32, u32), (u32, u32))>,
+}
+
+impl Selection {
+    fn parse(iterator: &str) -> Result<Self, SourceError> {
+        let invalid = || SourceError::InvalidPath(iterator.to_string());
+        let (sheet, range) = match iterator.rsplit_once('!') {
+            Some((sheet, range)) => (sheet, Some(range)),
+            None => (iterator, None),
+        };
+        let sheet = sheet
+            .strip_prefix('\'')
+            .and_then(|sheet| sheet.strip_suffix('\''))
+            .map(|sheet| sheet.replace("''", "'"))
+            .unwrap_or_else(|| sheet.to_string());
+
+        let range = match range {
+            Some(range) => {
+                let (start, end) = range.split_once(':').unwrap_or((range, range));
+                let start = cell_position(start).ok_or_else(invalid)?;
+                let end = cell_position(end).ok_or_else(invalid)?;
+                Some((
+                    (start.0.min(end.0), start.1.min(end.1)),
+                    (start.0.max(end.0), start.1.max(end.1)),
+                ))
+            }
+            None => None,
+        };
+        Ok(Self { sheet, range })
+    }
+}
+
+/// The zero-based row and column of an `A1` style cell reference
+fn cell_position(cell: &str) -> Option<(u32, u32)> {
+    let cell = cell.trim().replace('$', "");
+    let digits = cell.find(|c: char| c.is_ascii_digit())?;
+    let (letters, row) = cell.split_at(digits);
+    if letters.is_empty() {
+        return None;
+    }
+
+    let mut column: u32 = 0;
+    for letter in letters.chars() {
+        if !letter.is_ascii_alphabetic() {
+            return None;
+        }
+        let value = letter.to_ascii_uppercase() as u32 - 'A' as u32 + 1;
+        column = column.checked_mul(26)?.checked_add(value)?;
+    }
+    let row: u32 = row.parse().ok()?;
+    Some((row.checked_sub(1)?, column - 1))
+}
+
+/// The rows of a sheet of an xlsx, xls or ods workbook, below its header row.
+/// The iterator selects the sheet and cell range, the first sheet otherwise.
+pub fn records(
+    source: &FileLogicalSource,
+    projection: Option<&HashSet<String>>,
+) -> Result<Records, SourceError> {
+    // Workbooks are archives that need random access
+    let mut buffer = Vec::new();
+    open(&source.source)?.read_to_end(&mut buffer)?;
+    let mut workbook = open_workbook_auto_from_rs(Cursor::new(buffer))?;
+
+    let selection = match source.iterator.as_deref() {
+        Some(iterator) => Selection::parse(iterator)?,
+        None => Selection {
+            sheet: workbook
+                .sheet_names()
+                .into_iter()
+                .next()
+                .unwrap_or_default(),
+            range: None,
+        },
+    };
+    if !workbook.sheet_names().contains(&selection.sheet) {
+        return Err(SourceError::UnknownSheet(selection.sheet));
+    }
+    let mut range = workbook.worksheet_range(&selection.sheet)?;
+    if let Some((start, end)) = selection.range {
+        range = range.range(start, end);
+    }
+
+    let mut rows = range.rows();
+    let headers: Vec<String> = rows
+        .next()
+        .map(|row| {
+            row.iter()
+                .map(|cell| cell_value(cell).map(|(value, _)| value).unwrap_or_default())
+                .collect()
+        })
+        .unwrap_or_default();
+    let columns: Vec<usize> = (0..headers.len())
+        .filter(|index| {
+            projection.is_none_or(|fields| fields.contains(reference_field(&headers[*index])))
+        })
+        .collect();
+    let headers: Arc<Vec<String>> = Arc::new(
+        columns
+            .iter()
+            .map(|index| headers[*index].clone())
+            .collect(),
+    );
+
+    let nulls = Arc::new(source.nulls.clone());
+    let records: Vec<Record> = rows
+        .map(|row| {
+            Box::new(SpreadsheetRecord {
+                headers: headers.clone(),
+                cells: columns
+                    .iter()
+                    .map(|index| row.get(*index).and_then(cell_value))
+                    .collect(),
+                nulls: nulls.clone(),
+            }) as Record
+        })
+        .collect();
+    Ok(Box::new(records.into_iter().map(Ok)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use tempfile::NamedTempFile;
+    use zip::write::SimpleFileOptions;
+
+    use super::*;
+    use crate::model::SourceDescription;
+
+    const XLSX: &[(&str, &str)] = &[
+        (
+            "[Content_Types].xml",
+            r#"<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">
+              <Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>
+              <Default Extension="xml" ContentType="application/xml"/>
+              <Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/>
+              <Override PartName="/xl/worksheets/sheet1.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/>
+              <Override PartName="/xl/worksheets/sheet2.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/>
+              <Override PartName="/xl/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.styles+xml"/>
+            </Types>"#,
+        ),
+        (
+            "_rels/.rels",
+            r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+              <Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="xl/workbook.xml"/>
+            </Relationships>"#,
+        ),
+        (
+            "xl/workbook.xml",
+            r#"<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships">
+              <sheets>
+                <sheet name="People" sheetId="1" r:id="rId1"/>
+                <sheet name="Q1 Sales" sheetId="2" r:id="rId2"/>
+              </sheets>
+            </workbook>"#,
+        ),
+        (
+            "xl/_rels/workbook.xml.rels",
+            r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+              <Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet1.xml"/>
+              <Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet2.xml"/>
+              <Relationship Id="rId3" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles" Target="styles.xml"/>
+            </Relationships>"#,
+        ),
+        (
+            "xl/styles.xml",
+            r#"<styleSheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main">
+              <cellXfs count="2"><xf numFmtId="0"/><xf numFmtId="14"/></cellXfs>
+            </styleSheet>"#,
+        ),
+        (
+            "xl/worksheets/sheet1.xml",
+            r#"<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><sheetData>
+              <row r="1">
+                <c r="A1" t="inlineStr"><is><t>id</t></is></c>
+                <c r="B1" t="inlineStr"><is><t>name</t></is></c>
+                <c r="C1" t="inlineStr"><is><t>born</t></is></c>
+                <c r="D1" t="inlineStr"><is><t>active</t></is></c>
+                <c r="E1" t="inlineStr"><is><t>score</t></is></c>
+              </row>
+              <row r="2">
+                <c r="A2"><v>1</v></c>
+                <c r="B2" t="inlineStr"><is><t>Ann</t></is></c>
+                <c r="C2" s="1"><v>44927</v></c>
+                <c r="D2" t="b"><v>1</v></c>
+                <c r="E2"><v>1.5</v></c>
+              </row>
+              <row r="3">
+                <c r="A3"><v>2</v></c>
+                <c r="B3" t="inlineStr"><is><t>Bob</t></is></c>
+              </row>
+            </sheetData></worksheet>"#,
+        ),
+        (
+            "xl/worksheets/sheet2.xml",
+            r#"<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><sheetData>
+              <row r="1"><c r="A1" t="inlineStr"><is><t>total</t></is></c></row>
+              <row r="2"><c r="A2"><v>250</v></c></row>
+            </sheetData></worksheet>"#,
+        ),
+    ];
+
+    const ODS: &[(&str, &str)] = &[
+        ("mimetype", "application/vnd.oasis.opendocument.spreadsheet"),
+        (
+            "META-INF/manifest.xml",
+            r#"<manifest:manifest xmlns:manifest="urn:oasis:names:tc:opendocument:xmlns:manifest:1.0" manifest:version="1.2">
+              <manifest:file-entry manifest:full-path="/" manifest:media-type="application/vnd.oasis.opendocument.spreadsheet"/>
+              <manifest:file-entry manifest:full-path="content.xml" manifest:media-type="text/xml"/>
+            </manifest:manifest>"#,
+        ),
+        // calamine rejects whitespace between the cells of a row
+        (
+            "content.xml",
+            concat!(
+                r#"<office:document-content xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0" xmlns:table="urn:oasis:names:tc:opendocument:xmlns:table:1.0" xmlns:text="urn:oasis:names:tc:opendocument:xmlns:text:1.0" office:version="1.2"><office:body><office:spreadsheet><table:table table:name="People">"#,
+                r#"<table:table-row><table:table-cell office:value-type="string"><text:p>id</text:p></table:table-cell><table:table-cell office:value-type="string"><text:p>name</text:p></table:table-cell><table:table-cell office:value-type="string"><text:p>born</text:p></table:table-cell><table:table-cell office:value-type="string"><text:p>active</text:p></table:table-cell></table:table-row>"#,
+                r#"<table:table-row><table:table-cell office:value-type="float" office:value="1"><text:p>1</text:p></table:table-cell><table:table-cell office:value-type="string"><text:p>Ann</text:p></table:table-cell><table:table-cell office:value-type="date" office:date-value="2023-01-01"><text:p>01/01/23</text:p></table:table-cell><table:table-cell office:value-type="boolean" office:boolean-value="true"><text:p>TRUE</text:p></table:table-cell></table:table-row></table:table></office:spreadsheet></office:body></office:document-content>"#,
+            ),
+        ),
+    ];
+
+    fn workbook(suffix: &str, files: &[(&str, &str)]) -> NamedTempFile {
+        let file = tempfile::Builder::new().suffix(suffix).tempfile().unwrap();
+        let mut zip = zip::ZipWriter::new(file.reopen().unwrap());
+        let options =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        for (name, content) in files {
+            zip.start_file(*name, options).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+        file
+    }
+
+    fn read(file: &NamedTempFile, iterator: Option<&str>) -> Result<Vec<Record>, SourceError> {
+        let source = FileLogicalSource {
+            source: SourceDescription::new(file.path().to_path_buf()),
+            reference_formulation: None,
+            iterator: iterator.map(str::to_string),
+            nulls: HashSet::new(),
+        };
+        Ok(records(&source, None)?.map(Result::unwrap).collect())
+    }
+
+    fn check_ann(ann: &Record) {
+        for (reference, value, datatype) in [
+            ("id", "1", Some(xsd::INTEGER)),
+            ("name", "Ann", None),
+            ("born", "2023-01-01", Some(xsd::DATE)),
+            ("active", "true", Some(xsd::BOOLEAN)),
+        ] {
+            assert_eq!(ann.get_values_for(reference), [value], "{reference}");
+            assert_eq!(ann.get_datatype_for(reference), datatype, "{reference}");
+        }
+    }
+
+    #[test]
+    fn reads_typed_xlsx_cells_below_the_header() {
+        let file = workbook(".xlsx", XLSX);
+        let records = read(&file, None).unwrap();
+        assert_eq!(records.len(), 2);
+        check_ann(&records[0]);
+        assert_eq!(records[0].get_values_for("score"), ["1.5"]);
+        assert_eq!(records[0].get_datatype_for("score"), Some(xsd::DOUBLE));
+        assert_eq!(records[1].get_values_for("name"), ["Bob"]);
+        assert!(records[1].get_values_for("born").is_empty());
+    }
+
+    #[test]
+    fn reads_typed_ods_cells_below_the_header() {
+        let file = workbook(".ods", ODS);
+        let records = read(&file, Some("People")).unwrap();
+        assert_eq!(records.len(), 1);
+        check_ann(&records[0]);
+    }
+
+    #[test]
+    fn selects_sheets_and_ranges() {
+        let file = workbook(".xlsx", XLSX);
+        let totals = read(&file, Some("'Q1 Sales'")).unwrap();
+        assert_eq!(totals[0].get_values_for("total"), ["250"]);
+
+        // The first row of the range is its header
+        let range = read(&file, Some("People!B2:C3")).unwrap();
+        assert_eq!(range.len(), 1);
+        assert_eq!(range[0].get_values_for("Ann"), ["Bob"]);
+        assert!(range[0].get_values_for("name").is_empty());
+
+        assert!(matches!(
+            read(&file, Some("Missing")),
+            Err(SourceError::UnknownSheet(sheet)) if sheet == "Missing"
+        ));
+        assert!(matches!(
+            read(&file, Some("People!A0")),
+            Err(SourceError::InvalidPath(_))
+        ));
+    }
+
+    #[test]
+    fn parses_cell_positions() {
+        assert_eq!(cell_position("A1"), Some((0, 0)));
+        assert_eq!(cell_position("$AB$12"), Some((11, 27)));
+        assert_eq!(cell_position("A"), None);
+        assert_eq!(cell_position("12"), None);
+
+        let selection = Selection::parse("'Bob''s'!C3:A1").unwrap();
+        assert_eq!(selection.sheet, "Bob's");
+        assert_eq!(selection.range, Some(((0, 0), (2, 2))));
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::{BTreeSet, HashSet};
+
+/// Rewrite a `SELECT * FROM ...` query to select only the `projection`
+/// columns, each quoted with `quote`. Other queries are returned unchanged,
+/// as are queries when nothing is referenced or when a reference does not
+/// name a column plainly.
+pub fn projected_query(query: &str, projection: &HashSet<String>, quote: char) -> String {
+    let trimmed = query.trim_start();
+    let rest = match strip_keyword(trimmed, "SELECT")
+        .map(str::trim_start)
+        .and_then(|rest| rest.strip_prefix('*'))
+    {
+        Some(rest) => rest,
+        None => return query.to_string(),
+    };
+    if projection.is_empty() || strip_keyword(rest.trim_start(), "FROM").is_none() {
+        return query.to_string();
+    }
+
+    let Some(columns) = projection
+        .iter()
+        .map(|reference| column(reference))
+        .collect::<Option<BTreeSet<_>>>()
+    else {
+        return query.to_string();
+    };
+    let columns: Vec<String> = columns
+        .into_iter()
+        .map(|column| format!("{quote}{column}{quote}"))
+        .collect();
+    format!("SELECT {} {}", columns.join(", "), rest.trim_start())
+}
+
+/// The column a reference names: a plain identifier, or a delimited
+/// identifier between double quotes. Other references, such as expressions
+/// or names holding quotes, give `None`.
+fn column(reference: &str) -> Option<&str> {
+    let column = match reference
+        .strip_prefix('"')
+        .and_then(|column| column.strip_suffix('"'))
+    {
+        Some(column) if !column.is_empty() && !column.contains('"') => column,
+        Some(_) => return None,
+        None => {
+            let plain = reference
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_alphabetic() || c == '_')
+                && reference.chars().all(|c| c.is_alphanumeric() || c == '_');
+            plain.then_some(reference)?
+        }
+    };
+    // Either quote character would end the identifier early
+    (!column.contains('`')).then_some(column)
+}
+
+/// Strip a leading SQL keyword, matched case-insensitively as a whole word
+fn strip_keyword<'q>(query: &'q str, keyword: &str) -> Option<&'q str> {
+    let head = query.get(..keyword.len())?;
+    let rest = &query[keyword.len()..];
+    let whole_word = rest
+        .chars()
+        .next()
+        .is_none_or(|c| !c.is_alphanumeric() && c != '_');
+    (head.eq_ignore_ascii_case(keyword) && whole_word).then_some(rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn projection(references: &[&str]) -> HashSet<String> {
+        references
+            .iter()
+            .map(|reference| reference.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn selects_the_referenced_columns() {
+        let query = projected_query(
+            "select * FROM people WHERE age > 3",
+            &projection(&["name", "id"]),
+            '"',
+        );
+        assert_eq!(query, r#"SELECT "id", "name" FROM people WHERE age > 3"#);
+
+        let query = projected_query("SELECT * FROM people", &projection(&["name"]), '`');
+        assert_eq!(query, "SELECT `name` FROM people");
+    }
+
+    #[test]
+    fn strips_delimited_identifiers_before_quoting() {
+        let references = projection(&["\"First Name\"", "id", "\"id\""]);
+        let query = projected_query("SELECT * FROM people", &references, '"');
+        assert_eq!(query, r#"SELECT "First Name", "id" FROM people"#);
+
+        let query = projected_query("SELECT * FROM people", &references, '`');
+        assert_eq!(query, "SELECT `First Name`, `id` FROM people");
+    }
+
+    #[test]
+    fn keeps_queries_with_references_that_are_not_columns() {
+        let query = "SELECT * FROM people";
+        for reference in [
+            "first name",
+            "\"a\"\"b\"",
+            "\"\"",
+            "a`b",
+            "count(*)",
+            "people.id",
+            "1st",
+        ] {
+            assert_eq!(
+                projected_query(query, &projection(&["id", reference]), '"'),
+                query
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_other_queries() {
+        let references = projection(&["id"]);
+        for query in [
+            "SELECT id FROM people",
+            "SELECT *, 1 AS one FROM people",
+            "SELECTED * FROM t",
+        ] {
+            assert_eq!(projected_query(query, &references, '"'), query);
+        }
+        assert_eq!(
+            projected_query("SELECT * FROM t", &HashSet::new(), '"'),
+            "SELECT * FROM t"
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use std::{collections::HashSet, io::Read, sync::Arc};
+
+use ::toml::Value as TomlValue;
+use serde_json::Value;
+
+use super::json::select;
+use super::jsonpath::JsonPath;
+use super::{Records, SourceError};
+
+/// The values selected by the JSONPath `iterator` in a TOML document, read
+/// into the same values as JSON. Dates and times become strings.
+pub fn records(
+    mut reader: Box<dyn Read + Send>,
+    iterator: Option<&str>,
+    nulls: Arc<HashSet<String>>,
+) -> Result<Records, SourceError> {
+    let iterator = JsonPath::parse(iterator.unwrap_or("$"))?;
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    let document = json_value(::toml::from_str(&text)?);
+    Ok(select(&document, &iterator, nulls))
+}
+
+fn json_value(value: TomlValue) -> Value {
+    match value {
+        TomlValue::String(value) => Value::String(value),
+        TomlValue::Integer(value) => Value::from(value),
+        TomlValue::Float(value) => Value::from(value),
+        TomlValue::Boolean(value) => Value::Bool(value),
+        TomlValue::Datetime(value) => Value::String(value.to_string()),
+        TomlValue::Array(values) => Value::Array(values.into_iter().map(json_value).collect()),
+        TomlValue::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(key, value)| (key, json_value(value)))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    const HOSTS: &str = r#"
+[[hosts]]
+name = "web"
+port = 8080
+load = 0.75
+public = true
+since = 2024-03-01T10:30:00Z
+tags = ["edge", "tls"]
+
+[[hosts]]
+name = "db"
+port = 5432
+since = 2023-12-24
+"#;
+
+    fn values(reference: &str) -> Vec<Vec<String>> {
+        let reader = Box::new(Cursor::new(HOSTS));
+        records(reader, Some("$.hosts[*]"), Arc::default())
+            .unwrap()
+            .map(|record| record.unwrap().get_values_for(reference))
+            .collect()
+    }
+
+    #[test]
+    fn reads_scalars_as_json_values() {
+        assert_eq!(values("name"), [["web"], ["db"]]);
+        assert_eq!(values("port"), [["8080"], ["5432"]]);
+        assert_eq!(values("load"), [vec!["0.75"], vec![]]);
+        assert_eq!(values("public"), [vec!["true"], vec![]]);
+        assert_eq!(values("tags[*]"), [vec!["edge", "tls"], vec![]]);
+    }
+
+    #[test]
+    fn reads_dates_and_times_as_strings() {
+        assert_eq!(values("since"), [["2024-03-01T10:30:00Z"], ["2023-12-24"]]);
+    }
+
+    #[test]
+    fn reports_invalid_documents() {
+        let reader = Box::new(Cursor::new("name = "));
+        assert!(records(reader, None, Arc::default()).is_err());
+    }
+}
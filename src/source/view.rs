@@ -0,0 +1,553 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use oxrdf::{NamedNode, NamedNodeRef};
+
+use super::{
+    embedded_values, read_document, read_logical_source, web::Transport, Record, Records,
+    SourceError,
+};
+use crate::model::{
+    reference_field, render_template, Expression, FieldKind, JoinCondition, LogicalView,
+    RmlIteration, ViewField, ViewJoin,
+};
+
+/// The field values of a view iteration, with their natural datatypes
+type Row = Vec<(String, String, Option<NamedNode>)>;
+
+/// An iteration of a logical view, where references name fields
+pub struct ViewRecord {
+    fields: Row,
+    nulls: Arc<HashSet<String>>,
+}
+
+impl ViewRecord {
+    fn field(&self, reference: &str) -> Option<&(String, String, Option<NamedNode>)> {
+        self.fields.iter().find(|(name, _, _)| name == reference)
+    }
+}
+
+impl RmlIteration for ViewRecord {
+    fn get_values_for(&self, reference: &str) -> Vec<String> {
+        self.field(reference)
+            .map(|(_, value, _)| vec![value.clone()])
+            .unwrap_or_default()
+    }
+
+    fn get_strings_for(&self, reference: &str) -> Vec<String> {
+        self.get_values_for(reference)
+    }
+
+    fn get_nulls(&self) -> &HashSet<String> {
+        &self.nulls
+    }
+
+    fn get_datatype_for(&self, reference: &str) -> Option<NamedNodeRef<'_>> {
+        self.field(reference)
+            .and_then(|(_, _, datatype)| datatype.as_ref())
+            .map(NamedNode::as_ref)
+    }
+}
+
+/// The iterations of a parent view, indexed by the values of the first join
+/// condition
+struct JoinedParents {
+    entries: Vec<(Vec<Vec<String>>, Record)>,
+    by_first: HashMap<String, Vec<usize>>,
+}
+
+/// Read the iterations of a logical view. The parents of its joins are read
+/// whole first, while the view itself is streamed from its source.
+pub fn read(view: &LogicalView, transport: Arc<dyn Transport>) -> Result<Records, SourceError> {
+    let joins = view
+        .joins
+        .iter()
+        .map(|join| Ok((join.clone(), parents(join, transport.clone())?)))
+        .collect::<Result<Vec<_>, SourceError>>()?;
+    let projection = source_fields(&view.fields);
+    let records = read_logical_source(&view.view_on, transport, projection.as_ref())?;
+    let fields = view.fields.clone();
+    let nulls = Arc::new(view.nulls.clone());
+
+    Ok(Box::new(records.flat_map(move |record| {
+        let rows = record.and_then(|record| {
+            let mut rows = view_rows(&fields, Context::Iteration(record.as_ref()), "")?;
+            for (join, parents) in &joins {
+                rows = join_rows(rows, join, parents, &nulls)?;
+            }
+            Ok(rows)
+        });
+        let records: Vec<Result<Record, SourceError>> = match rows {
+            Ok(rows) => rows
+                .into_iter()
+                .map(|fields| {
+                    Ok(Box::new(ViewRecord {
+                        fields,
+                        nulls: nulls.clone(),
+                    }) as Record)
+                })
+                .collect(),
+            Err(e) => vec![Err(e)],
+        };
+        records
+    })))
+}
+
+/// What the fields of a view are read from
+#[derive(Clone, Copy)]
+enum Context<'a> {
+    /// An iteration of the source, or of an iterable field
+    Iteration(&'a dyn RmlIteration),
+    /// The value of an expression field
+    Value(&'a str),
+}
+
+/// One row for every combination of the values of `fields`. A field without
+/// values is left out of the rows rather than dropping them.
+fn view_rows(
+    fields: &[ViewField],
+    context: Context<'_>,
+    prefix: &str,
+) -> Result<Vec<Row>, SourceError> {
+    let mut rows = vec![Row::new()];
+    for field in fields {
+        let field_rows = field_rows(field, context, prefix)?;
+        if !field_rows.is_empty() {
+            rows = product(&rows, &field_rows);
+        }
+    }
+    Ok(rows)
+}
+
+/// The rows of a single field and the fields nested in it
+fn field_rows(
+    field: &ViewField,
+    context: Context<'_>,
+    prefix: &str,
+) -> Result<Vec<Row>, SourceError> {
+    let name = format!("{prefix}{}", field.name);
+    let nested_prefix = format!("{name}.");
+    let mut field_rows = Vec::new();
+
+    match (&field.kind, context) {
+        (FieldKind::Expression(expression), Context::Iteration(iteration)) => {
+            for (value, datatype) in values(expression, iteration) {
+                let value_field = vec![(name.clone(), value.clone(), datatype)];
+                let nested = view_rows(&field.fields, Context::Value(&value), &nested_prefix)?;
+                field_rows.extend(product(&[value_field], &nested));
+            }
+        }
+        // Only iterable fields are nested in expression fields
+        (FieldKind::Expression(_), Context::Value(_)) => {}
+        (
+            FieldKind::Iterable {
+                reference_formulation,
+                iterator,
+            },
+            context,
+        ) => {
+            let iterations = match (context, reference_formulation) {
+                // A malformed embedded document only leaves the field out
+                (Context::Value(document), Some(formulation)) => {
+                    let nulls = Arc::new(HashSet::new());
+                    match read_document(document, formulation, iterator.as_deref(), nulls)
+                        .and_then(|records| records.collect::<Result<Vec<_>, _>>())
+                    {
+                        Ok(iterations) => iterations,
+                        Err(e) => {
+                            log::warn!("Skipping field `{}`: {}", name, e);
+                            Vec::new()
+                        }
+                    }
+                }
+                (Context::Iteration(iteration), _) => match iterator {
+                    Some(iterator) => iteration.get_iterations_for(iterator),
+                    None => Vec::new(),
+                },
+                (Context::Value(_), None) => Vec::new(),
+            };
+            for iteration in &iterations {
+                let context = Context::Iteration(iteration.as_ref());
+                field_rows.extend(view_rows(&field.fields, context, &nested_prefix)?);
+            }
+        }
+    }
+    Ok(field_rows)
+}
+
+/// The values of a field expression. Function executions are rejected when
+/// parsing the view, and fields never generate blank nodes.
+fn values(
+    expression: &Expression,
+    iteration: &dyn RmlIteration,
+) -> Vec<(String, Option<NamedNode>)> {
+    let nulls = iteration.get_nulls();
+    match expression {
+        Expression::Constant { constant } => vec![(constant.clone(), None)],
+        Expression::Reference { reference } => {
+            let datatype = iteration
+                .get_datatype_for(reference)
+                .map(NamedNodeRef::into_owned);
+            iteration
+                .get_values_for(reference)
+                .into_iter()
+                .filter(|value| !nulls.contains(value))
+                .map(|value| (value, datatype.clone()))
+                .collect()
+        }
+        Expression::EmbeddedReference {
+            document,
+            reference_formulation,
+            reference,
+        } => embedded_values(iteration, document, reference_formulation, reference)
+            .into_iter()
+            .map(|value| (value, None))
+            .collect(),
+        Expression::Template { template } => render_template(template, iteration, false)
+            .into_iter()
+            .map(|value| (value, None))
+            .collect(),
+        Expression::FunctionExecution { .. } | Expression::Unlabelled { .. } => Vec::new(),
+    }
+}
+
+fn product(left: &[Row], right: &[Row]) -> Vec<Row> {
+    left.iter()
+        .flat_map(|left| {
+            right
+                .iter()
+                .map(move |right| left.iter().chain(right).cloned().collect())
+        })
+        .collect()
+}
+
+/// The values of one side of each join condition
+fn join_values(
+    join: &ViewJoin,
+    iteration: &dyn RmlIteration,
+    side: fn(&JoinCondition) -> &Expression,
+) -> Vec<Vec<String>> {
+    join.join_conditions
+        .iter()
+        .map(|condition| {
+            values(side(condition), iteration)
+                .into_iter()
+                .map(|(value, _)| value)
+                .collect()
+        })
+        .collect()
+}
+
+/// The fields of a source that `fields` read, or `None` when an iterable
+/// field reads nested iterations, whose references are relative to them
+fn source_fields(fields: &[ViewField]) -> Option<HashSet<String>> {
+    let mut references = HashSet::new();
+    for field in fields {
+        match &field.kind {
+            // Nested fields read the value rather than the source
+            FieldKind::Expression(expression) => references.extend(reference_fields(expression)),
+            FieldKind::Iterable { .. } => return None,
+        }
+    }
+    Some(references)
+}
+
+/// The fields of the parent view that a join reads
+fn parent_fields(join: &ViewJoin) -> Option<HashSet<String>> {
+    let mut references = source_fields(&join.fields)?;
+    for condition in &join.join_conditions {
+        references.extend(reference_fields(&condition.parent_map.expression));
+    }
+    Some(references)
+}
+
+fn reference_fields(expression: &Expression) -> impl Iterator<Item = String> {
+    expression
+        .references()
+        .into_iter()
+        .map(|reference| reference_field(&reference).to_string())
+}
+
+fn parents(join: &ViewJoin, transport: Arc<dyn Transport>) -> Result<JoinedParents, SourceError> {
+    let mut parents = JoinedParents {
+        entries: Vec::new(),
+        by_first: HashMap::new(),
+    };
+    let projection = parent_fields(join);
+    for record in read_logical_source(&join.parent_view, transport, projection.as_ref())? {
+        let record = record?;
+        let values = join_values(join, record.as_ref(), |jc| &jc.parent_map.expression);
+        for value in values.first().into_iter().flatten() {
+            parents
+                .by_first
+                .entry(value.clone())
+                .or_default()
+                .push(parents.entries.len());
+        }
+        parents.entries.push((values, record));
+    }
+    Ok(parents)
+}
+
+/// Extend each row with the fields of the parent iterations it joins with.
+/// Without join conditions, every parent iteration matches.
+fn join_rows(
+    rows: Vec<Row>,
+    join: &ViewJoin,
+    parents: &JoinedParents,
+    nulls: &Arc<HashSet<String>>,
+) -> Result<Vec<Row>, SourceError> {
+    let mut joined = Vec::new();
+    for row in rows {
+        let child = ViewRecord {
+            fields: row,
+            nulls: nulls.clone(),
+        };
+        let child_values = join_values(join, &child, |jc| &jc.child_map.expression);
+        let candidates: Vec<usize> = match child_values.first() {
+            Some(first) => {
+                let mut candidates: Vec<usize> = first
+                    .iter()
+                    .filter_map(|value| parents.by_first.get(value))
+                    .flatten()
+                    .copied()
+                    .collect();
+                candidates.sort_unstable();
+                candidates.dedup();
+                candidates
+            }
+            None => (0..parents.entries.len()).collect(),
+        };
+
+        let mut matched = false;
+        for index in candidates {
+            let (parent_values, parent) = &parents.entries[index];
+            let matches = child_values
+                .iter()
+                .zip(parent_values)
+                .skip(1)
+                .all(|(child, parent)| child.iter().any(|value| parent.contains(value)));
+            if matches {
+                matched = true;
+                let parent_rows = view_rows(&join.fields, Context::Iteration(parent.as_ref()), "")?;
+                joined.extend(product(std::slice::from_ref(&child.fields), &parent_rows));
+            }
+        }
+        if !matched && !join.inner {
+            joined.push(child.fields);
+        }
+    }
+    Ok(joined)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use oxrdf::vocab::xsd;
+
+    use super::*;
+    use crate::model::{ExpressionMap, FileLogicalSource, LogicalSourceType, SourceDescription};
+    use crate::source::web::HttpTransport;
+    use crate::vocab::rml;
+
+    fn csv_source(path: &Path, nulls: &[&str]) -> Box<LogicalSourceType> {
+        Box::new(LogicalSourceType::File(FileLogicalSource {
+            source: SourceDescription::new(path.to_path_buf()),
+            reference_formulation: Some(rml::ReferenceFormulation::CSV.to_string()),
+            iterator: None,
+            nulls: nulls.iter().map(|null| null.to_string()).collect(),
+        }))
+    }
+
+    fn field(name: &str, expression: Expression) -> ViewField {
+        ViewField {
+            name: name.to_string(),
+            kind: FieldKind::Expression(expression),
+            fields: Vec::new(),
+        }
+    }
+
+    fn reference(reference: &str) -> Expression {
+        Expression::Reference {
+            reference: reference.to_string(),
+        }
+    }
+
+    fn view(view_on: Box<LogicalSourceType>, fields: Vec<ViewField>) -> LogicalView {
+        LogicalView {
+            view_on,
+            fields,
+            joins: Vec::new(),
+            nulls: HashSet::new(),
+        }
+    }
+
+    fn read_view(view: &LogicalView) -> Vec<Record> {
+        read(view, Arc::new(HttpTransport::default()))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    /// The values of `name` in each iteration
+    fn values(records: &[Record], name: &str) -> Vec<Vec<String>> {
+        records
+            .iter()
+            .map(|record| record.get_values_for(name))
+            .collect()
+    }
+
+    fn people(directory: &Path) -> Box<LogicalSourceType> {
+        let path = directory.join("people.csv");
+        fs::write(
+            &path,
+            "id,name,tags\n1,Ann,\"[\"\"a\"\", \"\"b\"\"]\"\n2,,[]\n",
+        )
+        .unwrap();
+        csv_source(&path, &[""])
+    }
+
+    #[test]
+    fn evaluates_fields() {
+        let directory = tempfile::tempdir().unwrap();
+        let tags = ViewField {
+            name: "tags".to_string(),
+            kind: FieldKind::Expression(reference("tags")),
+            fields: vec![ViewField {
+                name: "item".to_string(),
+                kind: FieldKind::Iterable {
+                    reference_formulation: Some(rml::ReferenceFormulation::JSON_PATH.to_string()),
+                    iterator: Some("$[*]".to_string()),
+                },
+                fields: vec![field("value", reference("$"))],
+            }],
+        };
+        let view = view(
+            people(directory.path()),
+            vec![
+                field("id", reference("id")),
+                field(
+                    "label",
+                    Expression::Template {
+                        template: "person {name}".to_string(),
+                    },
+                ),
+                field(
+                    "kind",
+                    Expression::Constant {
+                        constant: "person".to_string(),
+                    },
+                ),
+                tags,
+            ],
+        );
+        let records = read_view(&view);
+
+        // A row for every value of the nested field
+        assert_eq!(values(&records, "id"), [["1"], ["1"], ["2"]]);
+        assert_eq!(
+            values(&records, "tags.item.value"),
+            [vec!["a"], vec!["b"], vec![]]
+        );
+        assert_eq!(
+            values(&records, "label"),
+            [vec!["person Ann"], vec!["person Ann"], vec![]]
+        );
+        assert_eq!(
+            values(&records, "kind"),
+            [["person"], ["person"], ["person"]]
+        );
+        assert_eq!(values(&records, "tags")[2], ["[]"]);
+    }
+
+    #[test]
+    fn numbers_iterations_in_index_fields() {
+        let directory = tempfile::tempdir().unwrap();
+        let view = view(
+            people(directory.path()),
+            vec![field("index", reference("#")), field("id", reference("id"))],
+        );
+        let records = read_view(&view);
+        assert_eq!(values(&records, "index"), [["0"], ["1"]]);
+        assert_eq!(records[0].get_datatype_for("index"), Some(xsd::INTEGER));
+        assert_eq!(records[0].get_datatype_for("id"), None);
+    }
+
+    fn sports_join(directory: &Path, inner: bool) -> ViewJoin {
+        let path = directory.join("sports.csv");
+        fs::write(&path, "person,sport\n1,tennis\n1,chess\n3,golf\n").unwrap();
+        ViewJoin {
+            parent_view: csv_source(&path, &[]),
+            join_conditions: vec![JoinCondition::new(
+                ExpressionMap::new(reference("person")),
+                ExpressionMap::new(reference("id")),
+            )],
+            fields: vec![field("sport", reference("sport"))],
+            inner,
+        }
+    }
+
+    #[test]
+    fn left_joins_keep_iterations_without_parents() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut view = view(people(directory.path()), vec![field("id", reference("id"))]);
+        view.joins.push(sports_join(directory.path(), false));
+        let records = read_view(&view);
+        assert_eq!(values(&records, "id"), [["1"], ["1"], ["2"]]);
+        assert_eq!(
+            values(&records, "sport"),
+            [vec!["tennis"], vec!["chess"], vec![]]
+        );
+    }
+
+    #[test]
+    fn inner_joins_drop_iterations_without_parents() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut view = view(people(directory.path()), vec![field("id", reference("id"))]);
+        view.joins.push(sports_join(directory.path(), true));
+        let records = read_view(&view);
+        assert_eq!(values(&records, "id"), [["1"], ["1"]]);
+        assert_eq!(values(&records, "sport"), [["tennis"], ["chess"]]);
+    }
+
+    #[test]
+    fn reads_only_the_referenced_fields_of_sources() {
+        let directory = tempfile::tempdir().unwrap();
+        let join = sports_join(directory.path(), true);
+        assert_eq!(
+            parent_fields(&join),
+            Some(HashSet::from(["sport".to_string(), "person".to_string()]))
+        );
+        assert_eq!(
+            source_fields(&[
+                field("id", reference("$.id")),
+                field(
+                    "label",
+                    Expression::Template {
+                        template: "{first} {last}".to_string()
+                    }
+                ),
+            ]),
+            Some(HashSet::from([
+                "id".to_string(),
+                "first".to_string(),
+                "last".to_string()
+            ]))
+        );
+
+        // References of nested iterations are relative to them
+        let iterable = ViewField {
+            name: "items".to_string(),
+            kind: FieldKind::Iterable {
+                reference_formulation: None,
+                iterator: Some("$.items[*]".to_string()),
+            },
+            fields: vec![field("name", reference("name"))],
+        };
+        assert_eq!(source_fields(&[iterable]), None);
+    }
+}
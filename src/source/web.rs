@@ -0,0 +1,688 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashSet},
+    hash::{Hash, Hasher},
+    io::{Cursor, Read},
+    iter,
+    sync::Arc,
+};
+
+use oxiri::Iri;
+use oxrdfio::RdfFormat;
+
+use super::{sparql, Record, Records, SourceError};
+use crate::model::{Pagination, WebLogicalSource};
+use crate::vocab::rml;
+
+/// The media type of SPARQL query results in JSON
+const SPARQL_RESULTS_JSON: &str = "application/sparql-results+json";
+
+/// An HTTP GET request
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Request {
+    pub url: String,
+    /// Query parameters, percent-encoded into the URL
+    pub query: Vec<(String, String)>,
+    pub headers: Vec<(String, String)>,
+}
+
+/// A successful HTTP response
+pub struct Response {
+    pub headers: Vec<(String, String)>,
+    pub body: Box<dyn Read + Send>,
+}
+
+impl Response {
+    /// The values of a header, by case-insensitive name
+    pub fn header<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.headers
+            .iter()
+            .filter(move |(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The media type of the body, without parameters
+    fn content_type(&self) -> Option<String> {
+        self.header("Content-Type").next().map(|value| {
+            let media_type = value.split(';').next().unwrap_or_default();
+            media_type.trim().to_ascii_lowercase()
+        })
+    }
+}
+
+/// Sends the HTTP requests of web sources, so that tests can answer them
+/// from a local stand-in rather than a real server
+pub trait Transport: Send + Sync {
+    fn get(&self, request: &Request) -> Result<Response, SourceError>;
+}
+
+/// Sends requests over the network
+pub struct HttpTransport {
+    agent: ureq::Agent,
+}
+
+impl Default for HttpTransport {
+    fn default() -> Self {
+        Self {
+            agent: ureq::Agent::new(),
+        }
+    }
+}
+
+impl Transport for HttpTransport {
+    fn get(&self, request: &Request) -> Result<Response, SourceError> {
+        let mut call = self.agent.get(&request.url);
+        for (name, value) in &request.query {
+            call = call.query(name, value);
+        }
+        for (name, value) in &request.headers {
+            call = call.set(name, value);
+        }
+        let response = call
+            .call()
+            .map_err(|e| SourceError::Http(request.url.clone(), Box::new(e)))?;
+
+        let mut headers = Vec::new();
+        for name in response.headers_names() {
+            for value in response.all(&name) {
+                headers.push((name.clone(), value.to_string()));
+            }
+        }
+        Ok(Response {
+            headers,
+            body: Box::new(response.into_reader()),
+        })
+    }
+}
+
+/// Read the iterations of a web source through `transport`, requesting
+/// further pages as the previous ones are consumed. The first page is
+/// requested straight away.
+pub fn read(
+    source: &WebLogicalSource,
+    transport: Arc<dyn Transport>,
+    projection: Option<&HashSet<String>>,
+) -> Result<Records, SourceError> {
+    let offset = match source.pagination {
+        Some(Pagination::Offset { .. }) => Some(0),
+        _ => None,
+    };
+    let mut pages = Pages {
+        next: Some(request(source, offset)),
+        source: source.clone(),
+        transport,
+        projection: projection.cloned(),
+        nulls: Arc::new(source.nulls.clone()),
+        offset,
+        records: Box::new(iter::empty()),
+        count: 0,
+        requested: HashSet::new(),
+        digests: HashSet::new(),
+    };
+    pages.fetch()?;
+    Ok(Box::new(pages))
+}
+
+/// The request for the first page, or for the page at `offset`. SPARQL
+/// endpoints are sent the iterator as their query, which must then not have
+/// its own `LIMIT` or `OFFSET` when paged.
+fn request(source: &WebLogicalSource, offset: Option<usize>) -> Request {
+    let page = match (source.pagination, offset) {
+        (Some(Pagination::Offset { size }), Some(offset)) => Some((size, offset)),
+        _ => None,
+    };
+    let mut query = Vec::new();
+    let mut headers = source.headers.clone();
+    if source.sparql_endpoint {
+        let mut sparql = source
+            .iterator
+            .clone()
+            .unwrap_or_else(|| sparql::DEFAULT_QUERY.to_string());
+        if let Some((size, offset)) = page {
+            sparql = format!("{sparql}\nLIMIT {size} OFFSET {offset}");
+        }
+        query.push(("query".to_string(), sparql));
+        if !headers
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case("Accept"))
+        {
+            headers.push(("Accept".to_string(), SPARQL_RESULTS_JSON.to_string()));
+        }
+    } else if let Some((size, offset)) = page {
+        query.push(("limit".to_string(), size.to_string()));
+        query.push(("offset".to_string(), offset.to_string()));
+    }
+
+    Request {
+        url: source.url.clone(),
+        query,
+        headers,
+    }
+}
+
+/// The records of the pages of a web source
+struct Pages {
+    source: WebLogicalSource,
+    transport: Arc<dyn Transport>,
+    projection: Option<HashSet<String>>,
+    nulls: Arc<HashSet<String>>,
+    /// The request for the page after the current one
+    next: Option<Request>,
+    /// The offset of the current page, when paging by offset
+    offset: Option<usize>,
+    records: Records,
+    /// The records read from the current page
+    count: usize,
+    /// The requests sent so far, so that a next link back to an earlier page
+    /// ends the paging
+    requested: HashSet<Request>,
+    /// Digests of the pages read so far, so that a server repeating a page,
+    /// eg because it ignores the paging parameters, ends the paging
+    digests: HashSet<u64>,
+}
+
+impl Pages {
+    /// Request the next page, giving whether there was one
+    fn fetch(&mut self) -> Result<bool, SourceError> {
+        let Some(request) = self.next.take() else {
+            return Ok(false);
+        };
+        if !self.requested.insert(request.clone()) {
+            log::warn!(
+                "Stopped paging `{}` at `{}`, which was already requested",
+                self.source.url,
+                request.url
+            );
+            return Ok(false);
+        }
+        let mut response = self.transport.get(&request)?;
+
+        // Pages are read whole to compare them with the previous ones
+        if self.source.pagination.is_some() {
+            let mut body = Vec::new();
+            response.body.read_to_end(&mut body)?;
+            let mut hasher = DefaultHasher::new();
+            body.hash(&mut hasher);
+            if !self.digests.insert(hasher.finish()) {
+                log::warn!(
+                    "Stopped paging `{}` at `{}`, which repeats an earlier page",
+                    self.source.url,
+                    request.url
+                );
+                return Ok(false);
+            }
+            response.body = Box::new(Cursor::new(body));
+        }
+
+        self.next = match self.source.pagination {
+            Some(Pagination::NextLink) => next_link(&request, &response).map(|url| Request {
+                url,
+                query: Vec::new(),
+                headers: request.headers.clone(),
+            }),
+            Some(Pagination::Offset { size }) => {
+                let offset = self.offset.unwrap_or_default() + size;
+                self.offset = Some(offset);
+                Some(self::request(&self.source, Some(offset)))
+            }
+            None => None,
+        };
+        self.count = 0;
+        let records = page_records(
+            &self.source,
+            response,
+            self.projection.as_ref(),
+            self.nulls.clone(),
+        );
+        if records.is_err() {
+            self.next = None;
+        }
+        self.records = records?;
+        Ok(true)
+    }
+}
+
+impl Iterator for Pages {
+    type Item = Result<Record, SourceError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(record) = self.records.next() {
+                self.count += 1;
+                return Some(record);
+            }
+            // A short page is the last one
+            if let Some(Pagination::Offset { size }) = self.source.pagination
+                && self.count < size
+            {
+                return None;
+            }
+            match self.fetch() {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// The target of the `rel="next"` link of a response, resolved against the
+/// request URL
+fn next_link(request: &Request, response: &Response) -> Option<String> {
+    let link = response
+        .header("Link")
+        .flat_map(|value| value.split(','))
+        .find_map(|link| {
+            let mut parts = link.split(';');
+            let url = parts.next()?.trim().strip_prefix('<')?.strip_suffix('>')?;
+            let next = parts.any(|param| match param.split_once('=') {
+                Some((name, value)) => {
+                    name.trim().eq_ignore_ascii_case("rel")
+                        && value
+                            .trim()
+                            .trim_matches('"')
+                            .split_whitespace()
+                            .any(|rel| rel.eq_ignore_ascii_case("next"))
+                }
+                None => false,
+            });
+            next.then_some(url)
+        })?;
+    match Iri::parse(request.url.as_str()) {
+        Ok(base) => base.resolve(link).ok().map(Iri::into_inner),
+        Err(_) => Some(link.to_string()),
+    }
+}
+
+/// The records of a page, read with the declared reference formulation or
+/// one implied by the content type. SPARQL endpoints answer with query
+/// results rather than documents to iterate over.
+fn page_records(
+    source: &WebLogicalSource,
+    response: Response,
+    projection: Option<&HashSet<String>>,
+    nulls: Arc<HashSet<String>>,
+) -> Result<Records, SourceError> {
+    let content_type = response.content_type();
+    if content_type.as_deref() == Some(SPARQL_RESULTS_JSON) {
+        return sparql::json_results(response.body, nulls);
+    }
+
+    let formulation = match (&source.reference_formulation, content_type.as_deref()) {
+        (Some(formulation), _) if !source.sparql_endpoint => formulation.as_str(),
+        (_, Some("text/csv")) => rml::ReferenceFormulation::CSV,
+        (_, Some("application/x-ndjson")) | (_, Some("application/jsonl")) => {
+            rml::ReferenceFormulation::JSON_LINES
+        }
+        (_, Some(media_type)) if media_type.contains("json") => {
+            rml::ReferenceFormulation::JSON_PATH
+        }
+        (_, Some(media_type)) if media_type.contains("xml") => rml::ReferenceFormulation::XPATH,
+        (_, Some(media_type)) if RdfFormat::from_media_type(media_type).is_some() => {
+            rml::ReferenceFormulation::SPARQL
+        }
+        (_, media_type) => {
+            return Err(SourceError::UnsupportedContentType(
+                source.url.clone(),
+                media_type.unwrap_or_default().to_string(),
+            ))
+        }
+    };
+
+    let body = response.body;
+    // The iterator of a SPARQL endpoint is its query
+    let iterator = if source.sparql_endpoint {
+        None
+    } else {
+        source.iterator.as_deref()
+    };
+    match formulation {
+        rml::ReferenceFormulation::CSV => super::csv::records(body, projection, nulls),
+        rml::ReferenceFormulation::JSON_PATH => {
+            super::json::records(body, iterator, projection, nulls)
+        }
+        rml::ReferenceFormulation::JSON_LINES => {
+            super::json::lines(body, iterator, projection, nulls)
+        }
+        rml::ReferenceFormulation::XPATH => super::xml::records(body, iterator, nulls),
+        rml::ReferenceFormulation::SPARQL => {
+            let format = content_type
+                .as_deref()
+                .and_then(RdfFormat::from_media_type)
+                .ok_or_else(|| SourceError::UnknownRdfFormat(source.url.clone().into()))?;
+            sparql::records(body, format, iterator, nulls)
+        }
+        other => Err(SourceError::UnsupportedReferenceFormulation(
+            other.to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// A page served by a `Stub`
+    struct Page {
+        content_type: &'static str,
+        body: String,
+        link: Option<&'static str>,
+    }
+
+    fn page(content_type: &'static str, body: &str) -> Page {
+        Page {
+            content_type,
+            body: body.to_string(),
+            link: None,
+        }
+    }
+
+    /// Answers requests from pages keyed by URL and query, recording the
+    /// requests it gets
+    #[derive(Default)]
+    struct Stub {
+        pages: Vec<(String, Page)>,
+        requests: Mutex<Vec<Request>>,
+    }
+
+    impl Stub {
+        fn with_page(mut self, key: &str, page: Page) -> Self {
+            self.pages.push((key.to_string(), page));
+            self
+        }
+
+        fn requested(&self) -> Vec<String> {
+            self.requests.lock().unwrap().iter().map(key).collect()
+        }
+    }
+
+    /// The URL of a request with its query, unencoded
+    fn key(request: &Request) -> String {
+        let query: Vec<_> = request
+            .query
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect();
+        match query.is_empty() {
+            true => request.url.clone(),
+            false => format!("{}?{}", request.url, query.join("&")),
+        }
+    }
+
+    impl Transport for Stub {
+        fn get(&self, request: &Request) -> Result<Response, SourceError> {
+            self.requests.lock().unwrap().push(request.clone());
+            let key = key(request);
+            let (_, page) = self
+                .pages
+                .iter()
+                .find(|(candidate, _)| *candidate == key)
+                .ok_or_else(|| std::io::Error::other(format!("no page at {key}")))?;
+            let mut headers = vec![("content-type".to_string(), page.content_type.to_string())];
+            if let Some(link) = page.link {
+                headers.push(("Link".to_string(), link.to_string()));
+            }
+            Ok(Response {
+                headers,
+                body: Box::new(Cursor::new(page.body.clone())),
+            })
+        }
+    }
+
+    fn source(url: &str) -> WebLogicalSource {
+        WebLogicalSource {
+            url: url.to_string(),
+            sparql_endpoint: false,
+            reference_formulation: None,
+            iterator: None,
+            headers: Vec::new(),
+            pagination: None,
+            nulls: HashSet::new(),
+        }
+    }
+
+    /// The values of `reference` in every record read from `source`
+    fn values(source: &WebLogicalSource, stub: &Arc<Stub>, reference: &str) -> Vec<String> {
+        read(source, stub.clone(), None)
+            .unwrap()
+            .flat_map(|record| record.unwrap().get_values_for(reference))
+            .collect()
+    }
+
+    const URL: &str = "http://example.com/items";
+
+    #[test]
+    fn reads_the_format_of_the_content_type() {
+        let cases = [
+            (page("text/csv; charset=utf-8", "id\n1\n2\n"), None, "id"),
+            (
+                page("application/json", r#"[{"id": 1}, {"id": 2}]"#),
+                Some("$[*]"),
+                "$.id",
+            ),
+            (
+                page("application/x-ndjson", "{\"id\": 1}\n{\"id\": 2}\n"),
+                None,
+                "$.id",
+            ),
+            (
+                page("application/xml", "<r><i id='1'/><i id='2'/></r>"),
+                Some("/r/i"),
+                "@id",
+            ),
+            (
+                page(
+                    "text/turtle",
+                    "<http://e/1> <http://e/id> 1 . <http://e/2> <http://e/id> 2 .",
+                ),
+                None,
+                "o",
+            ),
+        ];
+        for (page, iterator, reference) in cases {
+            let content_type = page.content_type;
+            let stub = Arc::new(Stub::default().with_page(URL, page));
+            let mut source = source(URL);
+            source.iterator = iterator.map(str::to_string);
+            let mut ids = values(&source, &stub, reference);
+            ids.sort();
+            assert_eq!(ids, ["1", "2"], "{content_type}");
+        }
+    }
+
+    #[test]
+    fn prefers_the_declared_reference_formulation() {
+        let stub =
+            Arc::new(Stub::default().with_page(URL, page("application/octet-stream", "id\n1\n")));
+        assert!(matches!(
+            read(&source(URL), stub.clone(), None),
+            Err(SourceError::UnsupportedContentType(url, media_type))
+                if url == URL && media_type == "application/octet-stream"
+        ));
+
+        let mut source = source(URL);
+        source.reference_formulation = Some(rml::ReferenceFormulation::CSV.to_string());
+        assert_eq!(values(&source, &stub, "id"), ["1"]);
+    }
+
+    #[test]
+    fn sends_headers_and_sparql_queries() {
+        let results = r#"{"head": {"vars": ["o"]}, "results": {"bindings": [
+            {"o": {"type": "literal", "value": "1"}}
+        ]}}"#;
+        let query = format!("{URL}?query={}", sparql::DEFAULT_QUERY);
+        let stub = Arc::new(Stub::default().with_page(&query, page(SPARQL_RESULTS_JSON, results)));
+        let mut source = source(URL);
+        source.sparql_endpoint = true;
+        source.headers = vec![("Authorization".to_string(), "Bearer token".to_string())];
+
+        assert_eq!(values(&source, &stub, "o"), ["1"]);
+        let requests = stub.requests.lock().unwrap();
+        assert_eq!(
+            requests[0].headers,
+            [
+                ("Authorization".to_string(), "Bearer token".to_string()),
+                ("Accept".to_string(), SPARQL_RESULTS_JSON.to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn pages_by_offset_until_a_short_page() {
+        let stub = Arc::new(
+            Stub::default()
+                .with_page(
+                    &format!("{URL}?limit=2&offset=0"),
+                    page("text/csv", "id\n1\n2\n"),
+                )
+                .with_page(
+                    &format!("{URL}?limit=2&offset=2"),
+                    page("text/csv", "id\n3\n4\n"),
+                )
+                .with_page(
+                    &format!("{URL}?limit=2&offset=4"),
+                    page("text/csv", "id\n5\n"),
+                ),
+        );
+        let mut source = source(URL);
+        source.pagination = Some(Pagination::Offset { size: 2 });
+
+        assert_eq!(values(&source, &stub, "id"), ["1", "2", "3", "4", "5"]);
+        assert_eq!(stub.requested().len(), 3);
+    }
+
+    #[test]
+    fn pages_by_offset_until_an_empty_page() {
+        let stub = Arc::new(
+            Stub::default()
+                .with_page(
+                    &format!("{URL}?limit=2&offset=0"),
+                    page("text/csv", "id\n1\n2\n"),
+                )
+                .with_page(&format!("{URL}?limit=2&offset=2"), page("text/csv", "id\n")),
+        );
+        let mut source = source(URL);
+        source.pagination = Some(Pagination::Offset { size: 2 });
+
+        assert_eq!(values(&source, &stub, "id"), ["1", "2"]);
+        assert_eq!(stub.requested().len(), 2);
+    }
+
+    #[test]
+    fn pages_sparql_queries_by_offset() {
+        let results = |value: &str| {
+            format!(
+                r#"{{"head": {{"vars": ["o"]}}, "results": {{"bindings": [
+                    {{"o": {{"type": "literal", "value": "{value}"}}}}
+                ]}}}}"#
+            )
+        };
+        let query = "SELECT ?o WHERE { ?s ?p ?o }";
+        let stub = Arc::new(
+            Stub::default()
+                .with_page(
+                    &format!("{URL}?query={query}\nLIMIT 1 OFFSET 0"),
+                    page(SPARQL_RESULTS_JSON, &results("a")),
+                )
+                .with_page(
+                    &format!("{URL}?query={query}\nLIMIT 1 OFFSET 1"),
+                    page(
+                        SPARQL_RESULTS_JSON,
+                        r#"{"head": {"vars": ["o"]}, "results": {"bindings": []}}"#,
+                    ),
+                ),
+        );
+        let mut source = source(URL);
+        source.sparql_endpoint = true;
+        source.iterator = Some(query.to_string());
+        source.pagination = Some(Pagination::Offset { size: 1 });
+
+        assert_eq!(values(&source, &stub, "o"), ["a"]);
+        assert_eq!(stub.requested().len(), 2);
+    }
+
+    #[test]
+    fn follows_next_links() {
+        let stub = Arc::new(
+            Stub::default()
+                .with_page(
+                    URL,
+                    Page {
+                        link: Some(r#"<items?page=2>; rel="next", <items>; rel="first""#),
+                        ..page("text/csv", "id\n1\n")
+                    },
+                )
+                .with_page(
+                    "http://example.com/items?page=2",
+                    Page {
+                        link: Some("<http://example.com/items?page=3>; rel=next"),
+                        ..page("text/csv", "id\n2\n")
+                    },
+                )
+                .with_page(
+                    "http://example.com/items?page=3",
+                    page("text/csv", "id\n3\n"),
+                ),
+        );
+        let mut source = source(URL);
+        source.pagination = Some(Pagination::NextLink);
+
+        assert_eq!(values(&source, &stub, "id"), ["1", "2", "3"]);
+        assert_eq!(
+            stub.requested(),
+            [
+                URL,
+                "http://example.com/items?page=2",
+                "http://example.com/items?page=3"
+            ]
+        );
+    }
+
+    #[test]
+    fn stops_at_a_next_link_to_an_earlier_page() {
+        let stub = Arc::new(
+            Stub::default()
+                .with_page(
+                    URL,
+                    Page {
+                        link: Some("<items?page=2>; rel=next"),
+                        ..page("text/csv", "id\n1\n")
+                    },
+                )
+                .with_page(
+                    "http://example.com/items?page=2",
+                    Page {
+                        link: Some(r#"<http://example.com/items>; rel="next""#),
+                        ..page("text/csv", "id\n2\n")
+                    },
+                ),
+        );
+        let mut source = source(URL);
+        source.pagination = Some(Pagination::NextLink);
+
+        assert_eq!(values(&source, &stub, "id"), ["1", "2"]);
+        assert_eq!(stub.requested().len(), 2);
+    }
+
+    #[test]
+    fn stops_at_a_repeated_page() {
+        // A server ignoring the paging parameters answers every page alike
+        let stub = Arc::new(
+            Stub::default()
+                .with_page(
+                    &format!("{URL}?limit=2&offset=0"),
+                    page("text/csv", "id\n1\n2\n"),
+                )
+                .with_page(
+                    &format!("{URL}?limit=2&offset=2"),
+                    page("text/csv", "id\n1\n2\n"),
+                ),
+        );
+        let mut source = source(URL);
+        source.pagination = Some(Pagination::Offset { size: 2 });
+
+        assert_eq!(values(&source, &stub, "id"), ["1", "2"]);
+        assert_eq!(stub.requested().len(), 2);
+    }
+}
@@ -0,0 +1,306 @@
+use std::{collections::HashSet, io::Read, sync::Arc};
+
+use super::{Record, Records, SourceError};
+use crate::model::RmlIteration;
+
+/// An owned XML element, so records can outlive the parsed document
+#[derive(Debug, Clone, Default)]
+pub struct XmlElement {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<XmlElement>,
+    pub text: String,
+}
+
+impl XmlElement {
+    fn from_node(node: roxmltree::Node<'_, '_>) -> Self {
+        let mut element = Self {
+            name: node.tag_name().name().to_string(),
+            attributes: node
+                .attributes()
+                .map(|a| (a.name().to_string(), a.value().to_string()))
+                .collect(),
+            ..Default::default()
+        };
+        for child in node.children() {
+            if child.is_element() {
+                element.children.push(Self::from_node(child));
+            } else if let Some(text) = child.text().filter(|_| child.is_text()) {
+                element.text.push_str(text);
+            }
+        }
+        element
+    }
+
+    /// The XPath string value: all descendant text in document order
+    pub fn string_value(&self) -> String {
+        let mut value = self.text.clone();
+        for child in &self.children {
+            value.push_str(&child.string_value());
+        }
+        value
+    }
+
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// A step of the supported XPath subset
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Child(String),
+    Descendant(String),
+    Attribute(String),
+    Text,
+    SelfNode,
+}
+
+/// Location paths made of child (`a/b`), descendant (`//a`), wildcard (`*`),
+/// attribute (`@a`), `text()` and `.` steps. Predicates are not supported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct XPath {
+    absolute: bool,
+    steps: Vec<Step>,
+}
+
+impl XPath {
+    pub fn parse(path: &str) -> Result<Self, SourceError> {
+        let invalid = || SourceError::InvalidPath(path.to_string());
+        let path = path.trim();
+        if path.contains('[') || path.is_empty() {
+            return Err(invalid());
+        }
+
+        let absolute = path.starts_with('/');
+        let mut steps = Vec::new();
+        let mut descendant = false;
+        for (i, part) in path.split('/').enumerate() {
+            if part.is_empty() {
+                // Leading `/` or the middle of `//`
+                descendant = i > 0 || path.starts_with("//");
+                continue;
+            }
+            let step = match part {
+                "." => Step::SelfNode,
+                "text()" => Step::Text,
+                _ if part.starts_with('@') => Step::Attribute(part[1..].to_string()),
+                _ if descendant => Step::Descendant(part.to_string()),
+                _ => Step::Child(part.to_string()),
+            };
+            descendant = false;
+            steps.push(step);
+        }
+        if descendant {
+            return Err(invalid());
+        }
+
+        Ok(Self { absolute, steps })
+    }
+
+    /// Evaluate the path against `context`, or against `document` when the
+    /// path is absolute
+    fn select_elements<'a>(
+        &self,
+        document: &'a XmlElement,
+        context: &'a XmlElement,
+    ) -> (Vec<&'a XmlElement>, Option<&Step>) {
+        let mut current = vec![if self.absolute { document } else { context }];
+        let mut steps = self.steps.as_slice();
+
+        // A trailing attribute or text step selects strings, not elements
+        let last = match steps.last() {
+            Some(step @ (Step::Attribute(_) | Step::Text)) => {
+                steps = &steps[..steps.len() - 1];
+                Some(step)
+            }
+            _ => None,
+        };
+
+        for step in steps {
+            current = current
+                .into_iter()
+                .flat_map(|element| match step {
+                    Step::Child(name) => element
+                        .children
+                        .iter()
+                        .filter(|c| name == "*" || &c.name == name)
+                        .collect(),
+                    Step::Descendant(name) => {
+                        let mut found = Vec::new();
+                        descendants(element, name, &mut found);
+                        found
+                    }
+                    Step::SelfNode => vec![element],
+                    Step::Attribute(_) | Step::Text => Vec::new(),
+                })
+                .collect();
+        }
+
+        (current, last)
+    }
+
+    fn select_strings(&self, document: &XmlElement, context: &XmlElement) -> Vec<String> {
+        let (elements, last) = self.select_elements(document, context);
+        elements
+            .into_iter()
+            .filter_map(|element| match last {
+                Some(Step::Attribute(name)) => element.attribute(name).map(str::to_string),
+                Some(Step::Text) => Some(element.text.clone()),
+                _ => Some(element.string_value()),
+            })
+            .collect()
+    }
+}
+
+fn descendants<'a>(element: &'a XmlElement, name: &str, found: &mut Vec<&'a XmlElement>) {
+    for child in &element.children {
+        if name == "*" || child.name == name {
+            found.push(child);
+        }
+        descendants(child, name, found);
+    }
+}
+
+/// An XML element selected by the logical source iterator, where references
+/// are XPath expressions relative to the element
+pub struct XmlRecord {
+    document: Arc<XmlElement>,
+    element: XmlElement,
+    nulls: Arc<HashSet<String>>,
+}
+
+impl RmlIteration for XmlRecord {
+    fn get_values_for(&self, reference: &str) -> Vec<String> {
+        match XPath::parse(reference) {
+            Ok(path) => path.select_strings(&self.document, &self.element),
+            Err(e) => {
+                log::warn!("{}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    fn get_strings_for(&self, reference: &str) -> Vec<String> {
+        self.get_values_for(reference)
+    }
+
+    fn get_nulls(&self) -> &HashSet<String> {
+        &self.nulls
+    }
+
+    fn get_iterations_for(&self, iterator: &str) -> Vec<Record> {
+        match XPath::parse(iterator) {
+            Ok(path) => path
+                .select_elements(&self.document, &self.element)
+                .0
+                .into_iter()
+                .map(|element| {
+                    Box::new(XmlRecord {
+                        document: self.document.clone(),
+                        element: element.clone(),
+                        nulls: self.nulls.clone(),
+                    }) as Record
+                })
+                .collect(),
+            Err(e) => {
+                log::warn!("{}", e);
+                Vec::new()
+            }
+        }
+    }
+}
+
+pub fn records(
+    mut reader: Box<dyn Read + Send>,
+    iterator: Option<&str>,
+    nulls: Arc<HashSet<String>>,
+) -> Result<Records, SourceError> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    let parsed = roxmltree::Document::parse(&text)?;
+
+    // The document node is the parent of the root element
+    let document = Arc::new(XmlElement {
+        children: vec![XmlElement::from_node(parsed.root_element())],
+        ..Default::default()
+    });
+
+    let iterator = XPath::parse(iterator.unwrap_or("/*"))?;
+    let elements: Vec<XmlElement> = iterator
+        .select_elements(&document, &document)
+        .0
+        .into_iter()
+        .cloned()
+        .collect();
+
+    Ok(Box::new(elements.into_iter().map(move |element| {
+        Ok(Box::new(XmlRecord {
+            document: document.clone(),
+            element,
+            nulls: nulls.clone(),
+        }) as Record)
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    const LIBRARY: &str = r#"<library>
+  <book id="1" lang="en"><title>Dune</title><author>Herbert</author></book>
+  <book id="2"><title>Solaris <em>novel</em></title><author>Lem</author><author>Anon</author></book>
+</library>"#;
+
+    fn read(iterator: Option<&str>) -> Vec<Record> {
+        let reader = Box::new(Cursor::new(LIBRARY.as_bytes().to_vec()));
+        records(reader, iterator, Arc::default())
+            .unwrap()
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    #[test]
+    fn selects_elements_attributes_and_text() {
+        let books = read(Some("/library/book"));
+        assert_eq!(books.len(), 2);
+        assert_eq!(books[0].get_values_for("@id"), ["1"]);
+        assert_eq!(books[0].get_values_for("title"), ["Dune"]);
+        assert_eq!(books[1].get_values_for("@lang"), Vec::<String>::new());
+        assert_eq!(books[1].get_values_for("author"), ["Lem", "Anon"]);
+        assert_eq!(books[1].get_values_for("title"), ["Solaris novel"]);
+        assert_eq!(books[1].get_values_for("title/text()"), ["Solaris "]);
+        assert_eq!(books[1].get_values_for("/library/book/@id"), ["1", "2"]);
+    }
+
+    #[test]
+    fn selects_descendants_and_nested_iterations() {
+        assert_eq!(read(Some("//author")).len(), 3);
+        assert_eq!(read(None)[0].get_values_for("*/@id"), ["1", "2"]);
+
+        let book = &read(Some("/library/book"))[1];
+        let authors: Vec<_> = book
+            .get_iterations_for("author")
+            .iter()
+            .flat_map(|author| author.get_values_for("."))
+            .collect();
+        assert_eq!(authors, ["Lem", "Anon"]);
+    }
+
+    #[test]
+    fn rejects_unsupported_paths() {
+        for path in ["book[1]", "", "book//"] {
+            assert!(matches!(
+                XPath::parse(path),
+                Err(SourceError::InvalidPath(_))
+            ));
+        }
+        let reader = Box::new(Cursor::new(LIBRARY.as_bytes().to_vec()));
+        assert!(records(reader, Some("book[@id]"), Arc::default()).is_err());
+    }
+}
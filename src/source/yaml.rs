@@ -0,0 +1,75 @@
+use std::{collections::HashSet, io::Read, sync::Arc};
+
+use serde::de::DeserializeSeed;
+
+use super::json::{select, JsonProjection};
+use super::jsonpath::JsonPath;
+use super::{Records, SourceError};
+
+/// The values selected by the JSONPath `iterator` in a YAML document, read
+/// into the same values as JSON so that numbers and booleans keep their type
+pub fn records(
+    reader: Box<dyn Read + Send>,
+    iterator: Option<&str>,
+    projection: Option<&HashSet<String>>,
+    nulls: Arc<HashSet<String>>,
+) -> Result<Records, SourceError> {
+    let iterator = JsonPath::parse(iterator.unwrap_or("$"))?;
+    let projection = match projection {
+        Some(fields) => JsonProjection::new(&iterator, fields),
+        None => JsonProjection::all(),
+    };
+    let document = (&projection).deserialize(serde_yaml::Deserializer::from_reader(reader))?;
+    Ok(select(&document, &iterator, nulls))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    const HOSTS: &str = "
+hosts:
+  - name: web
+    port: 8080
+    load: 0.75
+    public: true
+    owner: null
+    code: '007'
+  - name: db
+    port: 5432
+    public: no
+";
+
+    fn values(projection: Option<&HashSet<String>>, reference: &str) -> Vec<Vec<String>> {
+        let reader = Box::new(Cursor::new(HOSTS));
+        records(reader, Some("$.hosts[*]"), projection, Arc::default())
+            .unwrap()
+            .map(|record| record.unwrap().get_values_for(reference))
+            .collect()
+    }
+
+    #[test]
+    fn reads_scalars_as_json_values() {
+        assert_eq!(values(None, "name"), [["web"], ["db"]]);
+        assert_eq!(values(None, "port"), [["8080"], ["5432"]]);
+        assert_eq!(values(None, "load"), [vec!["0.75"], vec![]]);
+        assert_eq!(values(None, "public"), [vec!["true"], vec!["no"]]);
+        assert_eq!(values(None, "code"), [vec!["007"], vec![]]);
+        assert!(values(None, "owner").iter().all(Vec::is_empty));
+    }
+
+    #[test]
+    fn keeps_only_referenced_fields() {
+        let projection = HashSet::from(["name".to_string()]);
+        assert_eq!(values(Some(&projection), "name"), [["web"], ["db"]]);
+        assert!(values(Some(&projection), "port").iter().all(Vec::is_empty));
+    }
+
+    #[test]
+    fn reports_invalid_documents() {
+        let reader = Box::new(Cursor::new("hosts: [unclosed"));
+        assert!(records(reader, None, None, Arc::default()).is_err());
+    }
+}
@@ -0,0 +1,115 @@
+use std::io::{self, Write};
+
+use bzip2::write::BzEncoder;
+use flate2::write::GzEncoder;
+use xz2::write::XzEncoder;
+
+use crate::model::Compression;
+
+/// A writer compressing its output as a single stream
+pub enum CompressedWriter<W: Write> {
+    None(W),
+    Gzip(GzEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+    Bzip2(BzEncoder<W>),
+    Xz(XzEncoder<W>),
+}
+
+impl<W: Write> CompressedWriter<W> {
+    pub fn new(writer: W, compression: Compression) -> io::Result<Self> {
+        Ok(match compression {
+            Compression::None => CompressedWriter::None(writer),
+            Compression::Gzip => {
+                CompressedWriter::Gzip(GzEncoder::new(writer, flate2::Compression::default()))
+            }
+            Compression::Zstd => CompressedWriter::Zstd(zstd::Encoder::new(writer, 0)?),
+            Compression::Bzip2 => {
+                CompressedWriter::Bzip2(BzEncoder::new(writer, bzip2::Compression::default()))
+            }
+            Compression::Xz => CompressedWriter::Xz(XzEncoder::new(writer, 6)),
+            Compression::Zip | Compression::TarXz | Compression::TarGz => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("{compression:?} archives cannot be written"),
+                ))
+            }
+        })
+    }
+
+    /// Write the end of the compressed stream and return the inner writer
+    pub fn finish(self) -> io::Result<W> {
+        let mut writer = match self {
+            CompressedWriter::None(writer) => writer,
+            CompressedWriter::Gzip(encoder) => encoder.finish()?,
+            CompressedWriter::Zstd(encoder) => encoder.finish()?,
+            CompressedWriter::Bzip2(encoder) => encoder.finish()?,
+            CompressedWriter::Xz(encoder) => encoder.finish()?,
+        };
+        writer.flush()?;
+        Ok(writer)
+    }
+}
+
+impl<W: Write> Write for CompressedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            CompressedWriter::None(writer) => writer.write(buf),
+            CompressedWriter::Gzip(encoder) => encoder.write(buf),
+            CompressedWriter::Zstd(encoder) => encoder.write(buf),
+            CompressedWriter::Bzip2(encoder) => encoder.write(buf),
+            CompressedWriter::Xz(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            CompressedWriter::None(writer) => writer.flush(),
+            CompressedWriter::Gzip(encoder) => encoder.flush(),
+            CompressedWriter::Zstd(encoder) => encoder.flush(),
+            CompressedWriter::Bzip2(encoder) => encoder.flush(),
+            CompressedWriter::Xz(encoder) => encoder.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::Read;
+
+    use super::*;
+    use crate::model::SourceDescription;
+
+    #[test]
+    fn decompresses_what_it_compresses() {
+        for compression in [
+            Compression::None,
+            Compression::Gzip,
+            Compression::Zstd,
+            Compression::Bzip2,
+            Compression::Xz,
+        ] {
+            let file = tempfile::NamedTempFile::new().unwrap();
+            let mut writer =
+                CompressedWriter::new(File::create(file.path()).unwrap(), compression).unwrap();
+            writer.write_all(b"id,name\n1,Alice\n").unwrap();
+            writer.finish().unwrap();
+
+            let mut description = SourceDescription::new(file.path().to_path_buf());
+            description.compression = compression;
+            let mut content = String::new();
+            crate::source::open(&description)
+                .unwrap()
+                .read_to_string(&mut content)
+                .unwrap();
+            assert_eq!(content, "id,name\n1,Alice\n", "{compression:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_archives() {
+        for compression in [Compression::Zip, Compression::TarXz, Compression::TarGz] {
+            assert!(CompressedWriter::new(Vec::new(), compression).is_err());
+        }
+    }
+}
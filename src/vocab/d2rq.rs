@@ -0,0 +1,11 @@
+pub const NS: &str = "http://www.wiwiss.fu-berlin.de/suhl/bizer/D2RQ/0.1#";
+
+pub struct Properties;
+
+impl Properties {
+    pub const JDBC_DSN: &'static str = "http://www.wiwiss.fu-berlin.de/suhl/bizer/D2RQ/0.1#jdbcDSN";
+    pub const USERNAME: &'static str =
+        "http://www.wiwiss.fu-berlin.de/suhl/bizer/D2RQ/0.1#username";
+    pub const PASSWORD: &'static str =
+        "http://www.wiwiss.fu-berlin.de/suhl/bizer/D2RQ/0.1#password";
+}
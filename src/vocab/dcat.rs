@@ -0,0 +1,8 @@
+pub const NS: &str = "http://www.w3.org/ns/dcat#";
+
+pub struct Properties;
+
+impl Properties {
+    pub const ACCESS_URL: &'static str = "http://www.w3.org/ns/dcat#accessURL";
+    pub const DOWNLOAD_URL: &'static str = "http://www.w3.org/ns/dcat#downloadURL";
+}
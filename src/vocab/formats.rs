@@ -0,0 +1,13 @@
+pub const NS: &str = "http://www.w3.org/ns/formats/";
+
+pub struct Formats;
+
+impl Formats {
+    pub const JSON_LD: &'static str = "http://www.w3.org/ns/formats/JSON-LD";
+    pub const N3: &'static str = "http://www.w3.org/ns/formats/N3";
+    pub const N_QUADS: &'static str = "http://www.w3.org/ns/formats/N-Quads";
+    pub const N_TRIPLES: &'static str = "http://www.w3.org/ns/formats/N-Triples";
+    pub const RDF_XML: &'static str = "http://www.w3.org/ns/formats/RDF_XML";
+    pub const TRIG: &'static str = "http://www.w3.org/ns/formats/TriG";
+    pub const TURTLE: &'static str = "http://www.w3.org/ns/formats/Turtle";
+}
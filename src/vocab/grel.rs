@@ -0,0 +1,24 @@
+pub const NS: &str = "http://users.ugent.be/~bjdmeest/function/grel.ttl#";
+
+pub struct Functions;
+
+impl Functions {
+    pub const TO_LOWER_CASE: &'static str =
+        "http://users.ugent.be/~bjdmeest/function/grel.ttl#toLowerCase";
+    pub const TO_UPPER_CASE: &'static str =
+        "http://users.ugent.be/~bjdmeest/function/grel.ttl#toUpperCase";
+    pub const STRING_TRIM: &'static str =
+        "http://users.ugent.be/~bjdmeest/function/grel.ttl#string_trim";
+}
+
+pub struct Parameters;
+
+impl Parameters {
+    pub const VALUE: &'static str = "http://users.ugent.be/~bjdmeest/function/grel.ttl#valueParam";
+}
+
+pub struct Outputs;
+
+impl Outputs {
+    pub const STRING: &'static str = "http://users.ugent.be/~bjdmeest/function/grel.ttl#stringOut";
+}
@@ -0,0 +1,9 @@
+pub const NS: &str = "http://www.w3.org/2011/http#";
+
+pub struct Properties;
+
+impl Properties {
+    pub const HEADERS: &'static str = "http://www.w3.org/2011/http#headers";
+    pub const FIELD_NAME: &'static str = "http://www.w3.org/2011/http#fieldName";
+    pub const FIELD_VALUE: &'static str = "http://www.w3.org/2011/http#fieldValue";
+}
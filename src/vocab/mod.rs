@@ -1 +1,7 @@
-pub mod rml;
\ No newline at end of file
+pub mod d2rq;
+pub mod dcat;
+pub mod formats;
+pub mod grel;
+pub mod htv;
+pub mod rml;
+pub mod sd;
@@ -13,6 +13,17 @@ pub struct ReferenceFormulation;
 impl ReferenceFormulation {
     pub const CSV: &'static str = "http://w3id.org/rml/CSV";
     pub const JSON_PATH: &'static str = "http://w3id.org/rml/JSONPath";
+    /// Newline-delimited JSON, where every line is a JSON document. Not part
+    /// of RML.
+    pub const JSON_LINES: &'static str = "http://w3id.org/rml/JSONLines";
+    /// Apache Parquet files, where references name columns. Not part of RML.
+    pub const PARQUET: &'static str = "http://w3id.org/rml/Parquet";
+    /// xlsx, xls and ods workbooks, where the iterator selects a sheet and
+    /// references name columns. Not part of RML.
+    pub const SPREADSHEET: &'static str = "http://w3id.org/rml/Spreadsheet";
+    /// RDF files, where the iterator is a SPARQL SELECT query and references
+    /// name its variables. Not part of RML.
+    pub const SPARQL: &'static str = "http://w3id.org/rml/SPARQL";
     pub const XPATH: &'static str = "http://w3id.org/rml/XPath";
     pub const SQL2008_TABLE: &'static str = "http://w3id.org/rml/SQL2008Table";
     pub const SQL2008_QUERY: &'static str = "http://w3id.org/rml/SQL2008Query";
@@ -23,40 +34,121 @@ pub struct Classes;
 impl Classes {
     pub const TRIPLES_MAP: &'static str = "http://w3id.org/rml/TriplesMap";
     pub const LOGICAL_SOURCE: &'static str = "http://w3id.org/rml/LogicalSource";
+    pub const LOGICAL_TARGET: &'static str = "http://w3id.org/rml/LogicalTarget";
     pub const REF_OBJECT_MAP: &'static str = "http://w3id.org/rml/RefObjectMap";
+    pub const RELATIVE_PATH_SOURCE: &'static str = "http://w3id.org/rml/RelativePathSource";
+    pub const FILE_PATH: &'static str = "http://w3id.org/rml/FilePath";
+    pub const LOGICAL_VIEW: &'static str = "http://w3id.org/rml/LogicalView";
+    pub const EXPRESSION_FIELD: &'static str = "http://w3id.org/rml/ExpressionField";
+    pub const ITERABLE_FIELD: &'static str = "http://w3id.org/rml/IterableField";
+}
+
+pub struct Graphs;
+
+impl Graphs {
+    pub const DEFAULT_GRAPH: &'static str = "http://w3id.org/rml/defaultGraph";
+}
+
+pub struct SourceRoot;
+
+impl SourceRoot {
+    pub const MAPPING_DIRECTORY: &'static str = "http://w3id.org/rml/MappingDirectory";
+    pub const CURRENT_WORKING_DIRECTORY: &'static str =
+        "http://w3id.org/rml/CurrentWorkingDirectory";
+}
+
+pub struct Streams;
+
+impl Streams {
+    pub const STD_IN: &'static str = "http://w3id.org/rml/StdIn";
+}
+
+pub struct Compression;
+
+impl Compression {
+    pub const NONE: &'static str = "http://w3id.org/rml/none";
+    pub const GZIP: &'static str = "http://w3id.org/rml/gzip";
+    pub const ZIP: &'static str = "http://w3id.org/rml/zip";
+    pub const TAR_XZ: &'static str = "http://w3id.org/rml/tarxz";
+    pub const TAR_GZ: &'static str = "http://w3id.org/rml/targz";
+}
+
+/// Ways of paging through web sources. Not part of RML.
+pub struct Pagination;
+
+impl Pagination {
+    pub const NEXT_LINK: &'static str = "http://w3id.org/rml/NextLink";
+}
+
+pub struct Encoding;
+
+impl Encoding {
+    pub const UTF_8: &'static str = "http://w3id.org/rml/UTF-8";
+    pub const UTF_16: &'static str = "http://w3id.org/rml/UTF-16";
 }
 
 pub struct Properties;
 
 impl Properties {
+    pub const BASE_IRI: &'static str = "http://w3id.org/rml/baseIRI";
     pub const CLASS: &'static str = "http://w3id.org/rml/class";
+    pub const COMPRESSION: &'static str = "http://w3id.org/rml/compression";
+    pub const CHILD: &'static str = "http://w3id.org/rml/child";
     pub const CHILD_MAP: &'static str = "http://w3id.org/rml/childMap";
     pub const CONSTANT: &'static str = "http://w3id.org/rml/constant";
+    pub const DATATYPE: &'static str = "http://w3id.org/rml/datatype";
     pub const DATATYPE_MAP: &'static str = "http://w3id.org/rml/datatypeMap";
+    /// Not part of RML
+    pub const EMBEDDED_IN: &'static str = "http://w3id.org/rml/embeddedIn";
+    pub const ENCODING: &'static str = "http://w3id.org/rml/encoding";
+    pub const FIELD: &'static str = "http://w3id.org/rml/field";
+    pub const FIELD_NAME: &'static str = "http://w3id.org/rml/fieldName";
+    pub const FUNCTION: &'static str = "http://w3id.org/rml/function";
     pub const FUNCTION_EXECUTION: &'static str = "http://w3id.org/rml/functionExecution";
     pub const FUNCTION_MAP: &'static str = "http://w3id.org/rml/functionMap";
     pub const GATHER: &'static str = "http://w3id.org/rml/gather";
     pub const GATHER_AS: &'static str = "http://w3id.org/rml/gatherAs";
+    pub const GRAPH: &'static str = "http://w3id.org/rml/graph";
     pub const GRAPH_MAP: &'static str = "http://w3id.org/rml/graphMap";
+    pub const INNER_JOIN: &'static str = "http://w3id.org/rml/innerJoin";
     pub const INPUT: &'static str = "http://w3id.org/rml/input";
+    pub const INPUT_VALUE: &'static str = "http://w3id.org/rml/inputValue";
     pub const INPUT_VALUE_MAP: &'static str = "http://w3id.org/rml/inputValueMap";
     pub const ITERATOR: &'static str = "http://w3id.org/rml/iterator";
     pub const JOIN_CONDITION: &'static str = "http://w3id.org/rml/joinCondition";
+    pub const LANGUAGE: &'static str = "http://w3id.org/rml/language";
     pub const LANGUAGE_MAP: &'static str = "http://w3id.org/rml/languageMap";
+    pub const LEFT_JOIN: &'static str = "http://w3id.org/rml/leftJoin";
     pub const LOGICAL_SOURCE: &'static str = "http://w3id.org/rml/logicalSource";
+    pub const LOGICAL_TARGET: &'static str = "http://w3id.org/rml/logicalTarget";
     pub const NULL: &'static str = "http://w3id.org/rml/null";
+    pub const OBJECT: &'static str = "http://w3id.org/rml/object";
     pub const OBJECT_MAP: &'static str = "http://w3id.org/rml/objectMap";
+    /// Not part of RML
+    pub const PAGE_SIZE: &'static str = "http://w3id.org/rml/pageSize";
+    /// Not part of RML
+    pub const PAGINATION: &'static str = "http://w3id.org/rml/pagination";
+    pub const PARAMETER: &'static str = "http://w3id.org/rml/parameter";
     pub const PARAMETER_MAP: &'static str = "http://w3id.org/rml/parameterMap";
+    pub const PARENT: &'static str = "http://w3id.org/rml/parent";
+    pub const PARENT_LOGICAL_VIEW: &'static str = "http://w3id.org/rml/parentLogicalView";
     pub const PARENT_MAP: &'static str = "http://w3id.org/rml/parentMap";
     pub const PARENT_TRIPLES_MAP: &'static str = "http://w3id.org/rml/parentTriplesMap";
+    pub const PATH: &'static str = "http://w3id.org/rml/path";
     pub const PREDICATE: &'static str = "http://w3id.org/rml/predicate";
     pub const PREDICATE_MAP: &'static str = "http://w3id.org/rml/predicateMap";
     pub const PREDICATE_OBJECT_MAP: &'static str = "http://w3id.org/rml/predicateObjectMap";
     pub const REFERENCE: &'static str = "http://w3id.org/rml/reference";
     pub const REFERENCE_FORMULATION: &'static str = "http://w3id.org/rml/referenceFormulation";
+    pub const RETURN: &'static str = "http://w3id.org/rml/return";
     pub const RETURN_MAP: &'static str = "http://w3id.org/rml/returnMap";
+    pub const ROOT: &'static str = "http://w3id.org/rml/root";
+    pub const SERIALIZATION: &'static str = "http://w3id.org/rml/serialization";
     pub const SOURCE: &'static str = "http://w3id.org/rml/source";
+    pub const SUBJECT: &'static str = "http://w3id.org/rml/subject";
     pub const SUBJECT_MAP: &'static str = "http://w3id.org/rml/subjectMap";
+    pub const TARGET: &'static str = "http://w3id.org/rml/target";
     pub const TEMPLATE: &'static str = "http://w3id.org/rml/template";
     pub const TERM_TYPE: &'static str = "http://w3id.org/rml/termType";
+    pub const VIEW_ON: &'static str = "http://w3id.org/rml/viewOn";
 }
@@ -0,0 +1,7 @@
+pub const NS: &str = "http://www.w3.org/ns/sparql-service-description#";
+
+pub struct Properties;
+
+impl Properties {
+    pub const ENDPOINT: &'static str = "http://www.w3.org/ns/sparql-service-description#endpoint";
+}